@@ -27,7 +27,7 @@ namespace mandelbrot
                 state.SelectedApproach = Approach.CSharpBaseLine;
                 return state;
             });
-            builder.Services.AddSingleton<IColorProvider, ClassicColormapProvider>();
+            builder.Services.AddSingleton<IColorProvider>(_ => new RustColormapProvider(RustFractals.ColormapMode.Classic));
             builder.Services.AddSingleton<RendererRegistry>(sp => new RendererRegistry([
                 (Approach.CSharpBaseLine, new MandelbrotBaselineRenderer(sp.GetService<IColorProvider>())),
                 (Approach.NaiveRustRenderer, new NaiveRustCallMandelbrotIFractalRenderer()),
@@ -1,3 +1,5 @@
+using RustFractals;
+
 namespace mandelbrot.Render;
 
 public interface IColorProvider
@@ -80,3 +82,49 @@ public sealed class ClassicColormapProvider : IColorProvider
     private static uint PackArgb(byte a, byte r, byte g, byte b)
         => (uint)(a << 24 | r << 16 | g << 8 | b);
 }
+
+/// <summary>
+/// Uses the palette lookup table exported by rust_fractal, so the C# renderers
+/// color exactly like the native ones without re-implementing the colormap.
+/// </summary>
+public sealed class RustColormapProvider : IColorProvider
+{
+    private sealed record Lut(int MaxIteration, uint[] Colors);
+
+    private readonly ColormapMode _mode;
+    private readonly int _cwidth;
+    private volatile Lut? _lut;
+
+    public RustColormapProvider(ColormapMode mode = ColormapMode.Classic, int cwidth = 50)
+    {
+        if (cwidth <= 0) throw new ArgumentOutOfRangeException(nameof(cwidth));
+        _mode = mode;
+        _cwidth = cwidth;
+    }
+
+    public uint GetColor(int iteration, int maxIteration)
+    {
+        if (maxIteration <= 0) return 0xFF000000;
+        if (iteration < 0) iteration = 0;
+        if (iteration >= maxIteration) return 0xFF000000;
+
+        var lut = _lut;
+        if (lut is null || lut.MaxIteration != maxIteration)
+        {
+            lut = Load(maxIteration);
+            _lut = lut;
+        }
+
+        return lut.Colors[iteration];
+    }
+
+    private unsafe Lut Load(int maxIteration)
+    {
+        var colors = new uint[maxIteration + 1];
+        fixed (uint* pColors = colors)
+        {
+            NativeMethods.colormap_lut_u32(_mode, _cwidth, maxIteration, pColors, (UIntPtr)colors.Length);
+        }
+        return new Lut(maxIteration, colors);
+    }
+}
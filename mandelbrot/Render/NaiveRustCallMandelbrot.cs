@@ -21,8 +21,8 @@ public class NaiveRustCallMandelbrotIFractalRenderer: IFractalRenderer
         {
             fixed (uint* pDst = dst)
             {
-                NativeMethods.mandelbrot_baseline_render_u32(center.X, center.Y, camera.Zoom, widthPx, heightPx,
-                    settings.MaxIterations, pDst, len);
+                NativeMethods.mandelbrot_colormap_render_u32(center.X, center.Y, camera.Zoom, widthPx, heightPx,
+                    settings.MaxIterations, ColormapMode.Classic, pDst, len);
             }
         }
 
@@ -1,4 +1,4 @@
-use std::{error::Error, fs, path::PathBuf, str::FromStr};
+use std::{env, error::Error, fs, path::Path, path::PathBuf, process, str::FromStr};
 
 fn main() -> Result<(), Box<dyn Error>> {
     let package_name = env!("CARGO_PKG_NAME");
@@ -7,11 +7,60 @@ fn main() -> Result<(), Box<dyn Error>> {
     let file_name = "Bindings.g.cs".to_string();
 
     fs::create_dir_all(&csharp_dir)?;
+    let generated = PathBuf::from(env::var("OUT_DIR")?).join(&file_name);
 
     csbindgen::Builder::default()
         .csharp_namespace("RustFractals")
         .input_extern_file("./src/lib.rs")
+        .input_extern_file("./src/colormap.rs")
+        .input_extern_file("./src/kernel.rs")
+        .input_extern_file("./src/location.rs")
+        .input_extern_file("./src/pixel.rs")
+        .input_extern_file("./src/render.rs")
+        .input_extern_file("./src/resample.rs")
+        .input_extern_file("./src/view.rs")
         .csharp_dll_name(package_name)
         .csharp_class_accessibility("public")
-        .generate_csharp_file(csharp_dir.join(file_name))
+        .generate_csharp_file(&generated)?;
+    replace_if_changed(&csharp_dir, &file_name, &fs::read(&generated)?)?;
+
+    // the C header the Apple XCFramework ships, see xtask/src/apple.rs
+    let include_dir = build_dir.join("include");
+    fs::create_dir_all(&include_dir)?;
+    let mut header = Vec::new();
+    let config = cbindgen::Config {
+        language: cbindgen::Language::C,
+        include_guard: Some("RUST_FRACTAL_H".to_string()),
+        export: cbindgen::ExportConfig {
+            // the extern functions and the types they take, no crate constants
+            item_types: vec![
+                cbindgen::ItemType::Functions,
+                cbindgen::ItemType::Enums,
+                cbindgen::ItemType::Structs,
+                cbindgen::ItemType::Typedefs,
+                cbindgen::ItemType::OpaqueItems,
+            ],
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    cbindgen::Builder::new()
+        .with_crate(&build_dir)
+        .with_config(config)
+        .generate()?
+        .write(&mut header);
+    replace_if_changed(&include_dir, &format!("{package_name}.h"), &header)?;
+    Ok(())
+}
+
+/// xtask builds several targets at once, each running this script: only
+/// replace the shared file when the bindings changed, and atomically.
+fn replace_if_changed(dir: &Path, file_name: &str, generated: &[u8]) -> Result<(), Box<dyn Error>> {
+    let target = dir.join(file_name);
+    if fs::read(&target).ok().as_deref() != Some(generated) {
+        let temp = dir.join(format!("{file_name}.{}.tmp", process::id()));
+        fs::write(&temp, generated)?;
+        fs::rename(&temp, &target)?;
+    }
+    Ok(())
 }
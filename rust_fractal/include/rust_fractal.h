@@ -0,0 +1,697 @@
+#ifndef RUST_FRACTAL_H
+#define RUST_FRACTAL_H
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * Segment logic used to walk the palette.
+ */
+enum ColormapMode
+#if __STDC_VERSION__ >= 202311L
+  : uint32_t
+#endif // __STDC_VERSION__ >= 202311L
+ {
+  /**
+   * Cycles over all segments, truncating interpolated channels.
+   */
+  Cyclic = 0,
+  /**
+   * Matches `ClassicColormapProvider` in C#: the first segment is a
+   * non-repeating ramp, the rest cycle over `MAP_LEN - 1` segments and
+   * channels are rounded half to even.
+   */
+  Classic = 1,
+};
+#if __STDC_VERSION__ >= 202311L
+typedef enum ColormapMode ColormapMode;
+#else
+typedef uint32_t ColormapMode;
+#endif // __STDC_VERSION__ >= 202311L
+
+/**
+ * Iterated function `z -> f(z) + c`.
+ */
+enum Formula
+#if __STDC_VERSION__ >= 202311L
+  : uint32_t
+#endif // __STDC_VERSION__ >= 202311L
+ {
+  /**
+   * `z^2 + c`
+   */
+  Mandelbrot = 0,
+  /**
+   * `conj(z)^2 + c`, also known as Mandelbar.
+   */
+  Tricorn = 1,
+  /**
+   * `(|re z| + i|im z|)^2 + c`, drawn with world Y up so the ship stands upside down.
+   */
+  BurningShip = 2,
+};
+#if __STDC_VERSION__ >= 202311L
+typedef enum Formula Formula;
+#else
+typedef uint32_t Formula;
+#endif // __STDC_VERSION__ >= 202311L
+
+/**
+ * How `z` is measured against the escape radius.
+ *
+ * Only `Euclidean` with a radius of at least 2 yields the exact Mandelbrot
+ * set; the other norms move the band boundaries for artistic effects.
+ * `RealOnly` and `ImagOnly` ignore one axis, so points that diverge along
+ * the other one may reach `max_iter` and are drawn as interior.
+ */
+enum BailoutNorm
+#if __STDC_VERSION__ >= 202311L
+  : uint32_t
+#endif // __STDC_VERSION__ >= 202311L
+ {
+  /**
+   * `re^2 + im^2 > radius^2`, the classic `|z| > radius` test.
+   */
+  Euclidean = 0,
+  /**
+   * `|re| + |im| > radius`.
+   */
+  Manhattan = 1,
+  /**
+   * `max(|re|, |im|) > radius`.
+   */
+  Max = 2,
+  /**
+   * `|re| > radius`, the imaginary part is ignored.
+   */
+  RealOnly = 3,
+  /**
+   * `|im| > radius`, the real part is ignored.
+   */
+  ImagOnly = 4,
+};
+#if __STDC_VERSION__ >= 202311L
+typedef enum BailoutNorm BailoutNorm;
+#else
+typedef uint32_t BailoutNorm;
+#endif // __STDC_VERSION__ >= 202311L
+
+/**
+ * How iteration counts are turned into palette positions.
+ */
+enum Coloring
+#if __STDC_VERSION__ >= 202311L
+  : uint32_t
+#endif // __STDC_VERSION__ >= 202311L
+ {
+  /**
+   * Integer iteration count, banded.
+   */
+  EscapeTime = 0,
+  /**
+   * Continuous iteration count, no bands. Looks best with a large escape radius.
+   */
+  Smooth = 1,
+  /**
+   * Integer counts equalized over the frame's histogram.
+   */
+  Histogram = 2,
+  /**
+   * Continuous counts equalized over the frame's histogram.
+   */
+  SmoothHistogram = 3,
+};
+#if __STDC_VERSION__ >= 202311L
+typedef enum Coloring Coloring;
+#else
+typedef uint32_t Coloring;
+#endif // __STDC_VERSION__ >= 202311L
+
+/**
+ * Memory layout of one output pixel.
+ */
+enum PixelFormat
+#if __STDC_VERSION__ >= 202311L
+  : uint32_t
+#endif // __STDC_VERSION__ >= 202311L
+ {
+  /**
+   * Packed `0xAARRGGBB` `u32` in native byte order, the layout of the `*_u32` APIs.
+   */
+  Argb32 = 0,
+  /**
+   * Bytes `R, G, B, A`.
+   */
+  Rgba8 = 1,
+  /**
+   * Bytes `B, G, R, A`.
+   */
+  Bgra8 = 2,
+  /**
+   * Little-endian `u16`, `rrrrrggg gggbbbbb`, alpha dropped.
+   */
+  Rgb565 = 3,
+  /**
+   * Bytes `R, G, B, A` with color channels multiplied by alpha.
+   */
+  Rgba8Premultiplied = 4,
+  /**
+   * Bytes `B, G, R, A` with color channels multiplied by alpha.
+   */
+  Bgra8Premultiplied = 5,
+};
+#if __STDC_VERSION__ >= 202311L
+typedef enum PixelFormat PixelFormat;
+#else
+typedef uint32_t PixelFormat;
+#endif // __STDC_VERSION__ >= 202311L
+
+enum Resampling
+#if __STDC_VERSION__ >= 202311L
+  : uint32_t
+#endif // __STDC_VERSION__ >= 202311L
+ {
+  Nearest = 0,
+  Bilinear = 1,
+};
+#if __STDC_VERSION__ >= 202311L
+typedef enum Resampling Resampling;
+#else
+typedef uint32_t Resampling;
+#endif // __STDC_VERSION__ >= 202311L
+
+/**
+ * Text encoding of a [`Location`].
+ */
+enum LocationFormat
+#if __STDC_VERSION__ >= 202311L
+  : uint32_t
+#endif // __STDC_VERSION__ >= 202311L
+ {
+  Toml = 0,
+  Json = 1,
+};
+#if __STDC_VERSION__ >= 202311L
+typedef enum LocationFormat LocationFormat;
+#else
+typedef uint32_t LocationFormat;
+#endif // __STDC_VERSION__ >= 202311L
+
+/**
+ * Everything besides the view that affects the rendered image.
+ */
+typedef struct RenderParams {
+  Formula formula;
+  /**
+   * Iteration limit, points reaching it are drawn black.
+   */
+  int32_t max_iter;
+  /**
+   * Escape radius compared against `bailout`. Radii of 2 or more are exact
+   * for the Euclidean norm; smooth coloring gets smoother with larger radii.
+   * Non-finite or non-positive values fall back to 2.
+   */
+  double escape_radius;
+  /**
+   * Norm of `z` tested against `escape_radius`.
+   */
+  BailoutNorm bailout;
+  ColormapMode colormap;
+  Coloring coloring;
+} RenderParams;
+
+/**
+ * Center, scale and render parameters of a view, independent of the
+ * output size. Enough to reopen the exact location.
+ */
+typedef struct ViewDescription {
+  double center_x;
+  double center_y;
+  /**
+   * pixels per world unit
+   */
+  uint64_t zoom;
+  /**
+   * counter-clockwise rotation in degrees
+   */
+  double rotation;
+  struct RenderParams params;
+} ViewDescription;
+
+uint32_t test(void);
+
+/**
+ * # Safety
+ *
+ * len must be width * height
+ * this is a test
+ */
+void mandelbrot_baseline_render_u32(float center_x,
+                                    float center_y,
+                                    uint64_t zoom,
+                                    int32_t width_px,
+                                    int32_t height_px,
+                                    int32_t max_iter,
+                                    uint32_t *dst,
+                                    uintptr_t dst_len);
+
+/**
+ * Same as `mandelbrot_baseline_render_u32` with a selectable colormap.
+ * `ColormapMode.Classic` produces the same image as the C# baseline renderer.
+ *
+ * # Safety
+ *
+ * len must be width * height
+ */
+void mandelbrot_colormap_render_u32(float center_x,
+                                    float center_y,
+                                    uint64_t zoom,
+                                    int32_t width_px,
+                                    int32_t height_px,
+                                    int32_t max_iter,
+                                    ColormapMode colormap,
+                                    uint32_t *dst,
+                                    uintptr_t dst_len);
+
+/**
+ * Default render parameters: 256 iterations, `|z| > 2`, cyclic colormap,
+ * integer escape-time coloring.
+ */
+struct RenderParams render_params_default(void);
+
+/**
+ * Renders with every parameter of `RenderParams`, see its fields for the
+ * exact bailout semantics.
+ *
+ * # Safety
+ *
+ * len must be width * height
+ */
+void mandelbrot_render_u32(float center_x,
+                           float center_y,
+                           uint64_t zoom,
+                           int32_t width_px,
+                           int32_t height_px,
+                           struct RenderParams params,
+                           uint32_t *dst,
+                           uintptr_t dst_len);
+
+/**
+ * Renders into a caller-owned buffer of any `PixelFormat`, e.g. a locked
+ * platform bitmap. Rows are `stride_bytes` apart; `dst_len` is in bytes and
+ * must cover `stride_bytes * (height - 1) + width * bytes_per_pixel`.
+ *
+ * # Safety
+ *
+ * dst must point to dst_len valid bytes
+ */
+void mandelbrot_render_into(float center_x,
+                            float center_y,
+                            uint64_t zoom,
+                            int32_t width_px,
+                            int32_t height_px,
+                            struct RenderParams params,
+                            PixelFormat format,
+                            uint8_t *dst,
+                            uintptr_t dst_len,
+                            uintptr_t stride_bytes);
+
+/**
+ * Renders with histogram-equalized coloring.
+ *
+ * The escape-time distribution of the whole frame is collected first and
+ * every pixel is colored by its cumulative rank, so clustered iteration
+ * counts at high `max_iter` still span the palette.
+ * `smooth` switches from integer to continuous iteration counts.
+ *
+ * # Safety
+ *
+ * len must be width * height
+ */
+void mandelbrot_histogram_render_u32(float center_x,
+                                     float center_y,
+                                     uint64_t zoom,
+                                     int32_t width_px,
+                                     int32_t height_px,
+                                     int32_t max_iter,
+                                     bool smooth,
+                                     uint32_t *dst,
+                                     uintptr_t dst_len);
+
+/**
+ * Writes raw iteration values instead of colors, `width * height` floats.
+ * Smooth colorings produce continuous values, interior points hold `max_iter`.
+ * Feed the buffer to `colorize_into` to turn it into pixels.
+ *
+ * # Safety
+ *
+ * len must be width * height
+ */
+void mandelbrot_iterations_f32(float center_x,
+                               float center_y,
+                               uint64_t zoom,
+                               int32_t width_px,
+                               int32_t height_px,
+                               struct RenderParams params,
+                               float *dst,
+                               uintptr_t dst_len);
+
+/**
+ * Updates the iteration values of `mandelbrot_iterations_f32` after a pan
+ * instead of recomputing them. `dst` holds the previous view at `prev_zoom`;
+ * the content moved `dx_px` pixels right and `dy_px` down, and `center_x`,
+ * `center_y`, `zoom` describe the new view. The reusable region is shifted in
+ * place and only the exposed strips are iterated. A different zoom or a
+ * shift as large as the view renders everything. Returns the number of
+ * pixels iterated.
+ *
+ * # Safety
+ *
+ * dst must point to dst_len valid floats, at least width * height
+ */
+uintptr_t mandelbrot_scroll_iterations_f32(double center_x,
+                                           double center_y,
+                                           uint64_t zoom,
+                                           int32_t width_px,
+                                           int32_t height_px,
+                                           struct RenderParams params,
+                                           uint64_t prev_zoom,
+                                           int32_t dx_px,
+                                           int32_t dy_px,
+                                           float *dst,
+                                           uintptr_t dst_len);
+
+/**
+ * Instant frame for a zoom gesture: resamples the previous ARGB frame `src`
+ * (view `prev_center_*`, `prev_zoom`) into `dst` (view `center_*`, `zoom`),
+ * both `width * height` pixels. Follow up with `zoom_refine_u32`.
+ *
+ * # Safety
+ *
+ * src and dst must each point to len valid u32s, at least width * height,
+ * and must not overlap
+ */
+void zoom_preview_u32(double prev_center_x,
+                      double prev_center_y,
+                      uint64_t prev_zoom,
+                      double center_x,
+                      double center_y,
+                      uint64_t zoom,
+                      int32_t width_px,
+                      int32_t height_px,
+                      Resampling mode,
+                      const uint32_t *src,
+                      uint32_t *dst,
+                      uintptr_t len);
+
+/**
+ * Final frame after `zoom_preview_u32`: pixels that fall exactly on a pixel
+ * of the previous frame come from `src`, the rest are iterated, so the result
+ * matches a fresh render. Returns the number of pixels iterated.
+ *
+ * # Safety
+ *
+ * src and dst must each point to len valid u32s, at least width * height,
+ * and must not overlap
+ */
+uintptr_t zoom_refine_u32(double prev_center_x,
+                          double prev_center_y,
+                          uint64_t prev_zoom,
+                          double center_x,
+                          double center_y,
+                          uint64_t zoom,
+                          int32_t width_px,
+                          int32_t height_px,
+                          struct RenderParams params,
+                          const uint32_t *src,
+                          uint32_t *dst,
+                          uintptr_t len);
+
+/**
+ * `zoom_preview_u32` for iteration values of `mandelbrot_iterations_f32`.
+ *
+ * # Safety
+ *
+ * src and dst must each point to len valid floats, at least width * height,
+ * and must not overlap
+ */
+void zoom_preview_f32(double prev_center_x,
+                      double prev_center_y,
+                      uint64_t prev_zoom,
+                      double center_x,
+                      double center_y,
+                      uint64_t zoom,
+                      int32_t width_px,
+                      int32_t height_px,
+                      Resampling mode,
+                      int32_t max_iter,
+                      const float *src,
+                      float *dst,
+                      uintptr_t len);
+
+/**
+ * `zoom_refine_u32` for iteration values of `mandelbrot_iterations_f32`.
+ *
+ * # Safety
+ *
+ * src and dst must each point to len valid floats, at least width * height,
+ * and must not overlap
+ */
+uintptr_t zoom_refine_f32(double prev_center_x,
+                          double prev_center_y,
+                          uint64_t prev_zoom,
+                          double center_x,
+                          double center_y,
+                          uint64_t zoom,
+                          int32_t width_px,
+                          int32_t height_px,
+                          struct RenderParams params,
+                          const float *src,
+                          float *dst,
+                          uintptr_t len);
+
+/**
+ * Colors an iteration buffer from `mandelbrot_iterations_f32` into any
+ * `PixelFormat`, see `mandelbrot_render_into` for the layout of `dst`.
+ *
+ * # Safety
+ *
+ * values must point to width * height floats, dst to dst_len valid bytes
+ */
+void colorize_into(const float *values,
+                   int32_t width_px,
+                   int32_t height_px,
+                   struct RenderParams params,
+                   PixelFormat format,
+                   uint8_t *dst,
+                   uintptr_t dst_len,
+                   uintptr_t stride_bytes);
+
+/**
+ * Rows an exponential map strip `strip_width` samples wide needs to reach
+ * from `radius_max` in to `radius_min` (world units), 0 for invalid input.
+ * A zoom video from zoom `a` to `b` at `w * h` pixels needs about
+ * `strip_width = 2π * hypot(w, h) / 2`, `radius_max = hypot(w, h) / 2 / a`
+ * and `radius_min = 0.5 / b`.
+ */
+int32_t expmap_rows(double radius_max, double radius_min, int32_t strip_width);
+
+/**
+ * Renders an exponential map strip around a zoom center with the same kernel
+ * as `mandelbrot_render_u32`: `strip_width * strip_rows` iteration values,
+ * column `c` at angle `2π * c / strip_width`, row `r` at radius
+ * `radius_max * exp(-2π * r / strip_width)`.
+ *
+ * # Safety
+ *
+ * dst must point to dst_len valid floats, at least strip_width * strip_rows
+ */
+void mandelbrot_expmap_f32(double center_x,
+                           double center_y,
+                           double radius_max,
+                           int32_t strip_width,
+                           int32_t strip_rows,
+                           struct RenderParams params,
+                           float *dst,
+                           uintptr_t dst_len);
+
+/**
+ * Reconstructs a frame centered on the strip's center from a strip of
+ * `mandelbrot_expmap_f32`. `zoom` is fractional pixels per world unit,
+ * `rotation` counter-clockwise degrees; pixels outside the strip are
+ * iterated. See `mandelbrot_render_into` for the layout of `dst`.
+ *
+ * # Safety
+ *
+ * values must point to strip_width * strip_rows floats, dst to dst_len valid bytes
+ */
+void expmap_frame_render_into(const float *values,
+                              double center_x,
+                              double center_y,
+                              double radius_max,
+                              int32_t strip_width,
+                              int32_t strip_rows,
+                              double zoom,
+                              double rotation,
+                              int32_t width_px,
+                              int32_t height_px,
+                              struct RenderParams params,
+                              PixelFormat format,
+                              uint8_t *dst,
+                              uintptr_t dst_len,
+                              uintptr_t stride_bytes);
+
+/**
+ * Width and height of the tiles of `tile_render_into` in pixels.
+ */
+int32_t tile_size(void);
+
+/**
+ * Renders slippy-map tile (z, x, y), see the `tiles` module for the grid.
+ * Iteration values are kept in a process-wide LRU cache, so panning over
+ * tiles only computes the newly exposed ones and palette changes only
+ * recolor. See `mandelbrot_render_into` for the layout of `dst`, which must
+ * hold `tile_size()` square. False for a level past 40 or a short buffer.
+ *
+ * # Safety
+ *
+ * dst must point to dst_len valid bytes
+ */
+bool tile_render_into(uint32_t z,
+                      int64_t x,
+                      int64_t y,
+                      struct RenderParams params,
+                      PixelFormat format,
+                      uint8_t *dst,
+                      uintptr_t dst_len,
+                      uintptr_t stride_bytes);
+
+/**
+ * Bounds the tile cache, 64 MiB by default. Shrinks it right away.
+ */
+void tile_cache_set_capacity(uintptr_t capacity_bytes);
+
+void tile_cache_clear(void);
+
+/**
+ * Writes the palette as a lookup table: `dst[i]` is the ARGB color of
+ * iteration `i` for the given `max_iter`, entries from `max_iter` on are black.
+ * `color_width` is the number of iterations per palette segment (50 by default).
+ *
+ * # Safety
+ *
+ * dst must point to dst_len valid u32s
+ */
+void colormap_lut_u32(ColormapMode colormap,
+                      int32_t color_width,
+                      int32_t max_iter,
+                      uint32_t *dst,
+                      uintptr_t dst_len);
+
+/**
+ * Encodes a packed ARGB frame as PNG at `path` (UTF-8, `path_len` bytes)
+ * and stores `view` in its tEXt chunks. Returns false on any failure.
+ *
+ * # Safety
+ *
+ * path must point to path_len bytes, argb to argb_len u32s
+ */
+bool png_write_argb(const uint8_t *path,
+                    uintptr_t path_len,
+                    struct ViewDescription view,
+                    int32_t width_px,
+                    int32_t height_px,
+                    const uint32_t *argb,
+                    uintptr_t argb_len);
+
+/**
+ * Reads the view stored by `png_write_argb` (or the `fractal-render` CLI)
+ * from an in-memory PNG file into `out`. Returns false if the data is not a
+ * PNG or the chunks are missing or malformed.
+ *
+ * # Safety
+ *
+ * data must point to data_len bytes, out to a writable ViewDescription
+ */
+bool png_read_view(const uint8_t *data, uintptr_t data_len, struct ViewDescription *out);
+
+/**
+ * Parses a location file (TOML, or JSON when it starts with `{`) into `out`.
+ * Returns false if the text is malformed, has an unsupported version or
+ * describes a view the renderers cannot represent.
+ *
+ * # Safety
+ *
+ * text must point to text_len UTF-8 bytes, out to a writable ViewDescription
+ */
+bool location_parse(const uint8_t *text, uintptr_t text_len, struct ViewDescription *out);
+
+/**
+ * Serializes `view` as a location file (UTF-8, no terminator).
+ * Returns the length in bytes and only writes if `dst_len` is large enough,
+ * so a first call with `dst_len = 0` queries the size. Returns 0 on failure.
+ *
+ * # Safety
+ *
+ * dst must be null or point to dst_len writable bytes
+ */
+uintptr_t location_serialize(struct ViewDescription view,
+                             LocationFormat format,
+                             uint8_t *dst,
+                             uintptr_t dst_len);
+
+/**
+ * Imports a Kalles Fraktaler `.kfr` file into `out`. KF zooms are relative
+ * to the image height, so the height of the target view is required.
+ * Returns false for malformed files and unsupported formulas.
+ *
+ * # Safety
+ *
+ * text must point to text_len UTF-8 bytes, out to a writable ViewDescription
+ */
+bool location_import_kfr(const uint8_t *text,
+                         uintptr_t text_len,
+                         int32_t height_px,
+                         struct ViewDescription *out);
+
+/**
+ * Imports entry `entry_index` of a Fractint `.par` file into `out`.
+ * Fractint magnifications are relative to the image height, so the height of
+ * the target view is required. Returns false for malformed files, a missing
+ * entry and unsupported formulas.
+ *
+ * # Safety
+ *
+ * text must point to text_len UTF-8 bytes, out to a writable ViewDescription
+ */
+bool location_import_par(const uint8_t *text,
+                         uintptr_t text_len,
+                         int32_t entry_index,
+                         int32_t height_px,
+                         struct ViewDescription *out);
+
+/**
+ * Suggests a max iteration count from the scale alone
+ * (`zoom` = pixels per world unit). The result can be used directly as
+ * `RenderSettings.MaxIterations`.
+ */
+int32_t suggest_max_iterations(uint64_t zoom);
+
+/**
+ * Refines `start_iter` with a cheap low resolution preview of the view:
+ * the limit is doubled while more than `max_iter_fraction` (0..1) of the
+ * preview samples reach it, and as long as doubling lets more of them escape.
+ * Pass `suggest_max_iterations(zoom)` as `start_iter` for a sensible start.
+ * Invalid views return `start_iter` clamped like a valid result.
+ */
+int32_t adaptive_max_iterations(float center_x,
+                                float center_y,
+                                uint64_t zoom,
+                                int32_t width_px,
+                                int32_t height_px,
+                                int32_t start_iter,
+                                float max_iter_fraction);
+
+#endif  /* RUST_FRACTAL_H */
@@ -30,9 +30,507 @@ namespace RustFractals
         [DllImport(__DllName, EntryPoint = "mandelbrot_baseline_render_u32", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
         public static extern void mandelbrot_baseline_render_u32(float center_x, float center_y, ulong zoom, int width_px, int height_px, int max_iter, uint* dst, nuint dst_len);
 
+        /// <summary>
+        ///  Same as `mandelbrot_baseline_render_u32` with a selectable colormap.
+        ///  `ColormapMode.Classic` produces the same image as the C# baseline renderer.
+        ///
+        ///  # Safety
+        ///
+        ///  len must be width * height
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "mandelbrot_colormap_render_u32", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        public static extern void mandelbrot_colormap_render_u32(float center_x, float center_y, ulong zoom, int width_px, int height_px, int max_iter, ColormapMode colormap, uint* dst, nuint dst_len);
+
+        /// <summary>
+        ///  Default render parameters: 256 iterations, `|z| &gt; 2`, cyclic colormap,
+        ///  integer escape-time coloring.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "render_params_default", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        public static extern RenderParams render_params_default();
+
+        /// <summary>
+        ///  Renders with every parameter of `RenderParams`, see its fields for the
+        ///  exact bailout semantics.
+        ///
+        ///  # Safety
+        ///
+        ///  len must be width * height
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "mandelbrot_render_u32", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        public static extern void mandelbrot_render_u32(float center_x, float center_y, ulong zoom, int width_px, int height_px, RenderParams @params, uint* dst, nuint dst_len);
+
+        /// <summary>
+        ///  Renders into a caller-owned buffer of any `PixelFormat`, e.g. a locked
+        ///  platform bitmap. Rows are `stride_bytes` apart; `dst_len` is in bytes and
+        ///  must cover `stride_bytes * (height - 1) + width * bytes_per_pixel`.
+        ///
+        ///  # Safety
+        ///
+        ///  dst must point to dst_len valid bytes
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "mandelbrot_render_into", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        public static extern void mandelbrot_render_into(float center_x, float center_y, ulong zoom, int width_px, int height_px, RenderParams @params, PixelFormat format, byte* dst, nuint dst_len, nuint stride_bytes);
+
+        /// <summary>
+        ///  Renders with histogram-equalized coloring.
+        ///
+        ///  The escape-time distribution of the whole frame is collected first and
+        ///  every pixel is colored by its cumulative rank, so clustered iteration
+        ///  counts at high `max_iter` still span the palette.
+        ///  `smooth` switches from integer to continuous iteration counts.
+        ///
+        ///  # Safety
+        ///
+        ///  len must be width * height
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "mandelbrot_histogram_render_u32", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        public static extern void mandelbrot_histogram_render_u32(float center_x, float center_y, ulong zoom, int width_px, int height_px, int max_iter, [MarshalAs(UnmanagedType.U1)] bool smooth, uint* dst, nuint dst_len);
+
+        /// <summary>
+        ///  Writes raw iteration values instead of colors, `width * height` floats.
+        ///  Smooth colorings produce continuous values, interior points hold `max_iter`.
+        ///  Feed the buffer to `colorize_into` to turn it into pixels.
+        ///
+        ///  # Safety
+        ///
+        ///  len must be width * height
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "mandelbrot_iterations_f32", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        public static extern void mandelbrot_iterations_f32(float center_x, float center_y, ulong zoom, int width_px, int height_px, RenderParams @params, float* dst, nuint dst_len);
+
+        /// <summary>
+        ///  Updates the iteration values of `mandelbrot_iterations_f32` after a pan
+        ///  instead of recomputing them. `dst` holds the previous view at `prev_zoom`;
+        ///  the content moved `dx_px` pixels right and `dy_px` down, and `center_x`,
+        ///  `center_y`, `zoom` describe the new view. The reusable region is shifted in
+        ///  place and only the exposed strips are iterated. A different zoom or a
+        ///  shift as large as the view renders everything. Returns the number of
+        ///  pixels iterated.
+        ///
+        ///  # Safety
+        ///
+        ///  dst must point to dst_len valid floats, at least width * height
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "mandelbrot_scroll_iterations_f32", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        public static extern nuint mandelbrot_scroll_iterations_f32(double center_x, double center_y, ulong zoom, int width_px, int height_px, RenderParams @params, ulong prev_zoom, int dx_px, int dy_px, float* dst, nuint dst_len);
+
+        /// <summary>
+        ///  Instant frame for a zoom gesture: resamples the previous ARGB frame `src`
+        ///  (view `prev_center_*`, `prev_zoom`) into `dst` (view `center_*`, `zoom`),
+        ///  both `width * height` pixels. Follow up with `zoom_refine_u32`.
+        ///
+        ///  # Safety
+        ///
+        ///  src and dst must each point to len valid u32s, at least width * height,
+        ///  and must not overlap
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "zoom_preview_u32", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        public static extern void zoom_preview_u32(double prev_center_x, double prev_center_y, ulong prev_zoom, double center_x, double center_y, ulong zoom, int width_px, int height_px, Resampling mode, uint* src, uint* dst, nuint len);
+
+        /// <summary>
+        ///  Final frame after `zoom_preview_u32`: pixels that fall exactly on a pixel
+        ///  of the previous frame come from `src`, the rest are iterated, so the result
+        ///  matches a fresh render. Returns the number of pixels iterated.
+        ///
+        ///  # Safety
+        ///
+        ///  src and dst must each point to len valid u32s, at least width * height,
+        ///  and must not overlap
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "zoom_refine_u32", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        public static extern nuint zoom_refine_u32(double prev_center_x, double prev_center_y, ulong prev_zoom, double center_x, double center_y, ulong zoom, int width_px, int height_px, RenderParams @params, uint* src, uint* dst, nuint len);
+
+        /// <summary>
+        ///  `zoom_preview_u32` for iteration values of `mandelbrot_iterations_f32`.
+        ///
+        ///  # Safety
+        ///
+        ///  src and dst must each point to len valid floats, at least width * height,
+        ///  and must not overlap
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "zoom_preview_f32", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        public static extern void zoom_preview_f32(double prev_center_x, double prev_center_y, ulong prev_zoom, double center_x, double center_y, ulong zoom, int width_px, int height_px, Resampling mode, int max_iter, float* src, float* dst, nuint len);
 
+        /// <summary>
+        ///  `zoom_refine_u32` for iteration values of `mandelbrot_iterations_f32`.
+        ///
+        ///  # Safety
+        ///
+        ///  src and dst must each point to len valid floats, at least width * height,
+        ///  and must not overlap
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "zoom_refine_f32", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        public static extern nuint zoom_refine_f32(double prev_center_x, double prev_center_y, ulong prev_zoom, double center_x, double center_y, ulong zoom, int width_px, int height_px, RenderParams @params, float* src, float* dst, nuint len);
+
+        /// <summary>
+        ///  Colors an iteration buffer from `mandelbrot_iterations_f32` into any
+        ///  `PixelFormat`, see `mandelbrot_render_into` for the layout of `dst`.
+        ///
+        ///  # Safety
+        ///
+        ///  values must point to width * height floats, dst to dst_len valid bytes
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "colorize_into", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        public static extern void colorize_into(float* values, int width_px, int height_px, RenderParams @params, PixelFormat format, byte* dst, nuint dst_len, nuint stride_bytes);
+
+        /// <summary>
+        ///  Rows an exponential map strip `strip_width` samples wide needs to reach
+        ///  from `radius_max` in to `radius_min` (world units), 0 for invalid input.
+        ///  A zoom video from zoom `a` to `b` at `w * h` pixels needs about
+        ///  `strip_width = 2π * hypot(w, h) / 2`, `radius_max = hypot(w, h) / 2 / a`
+        ///  and `radius_min = 0.5 / b`.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "expmap_rows", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        public static extern int expmap_rows(double radius_max, double radius_min, int strip_width);
+
+        /// <summary>
+        ///  Renders an exponential map strip around a zoom center with the same kernel
+        ///  as `mandelbrot_render_u32`: `strip_width * strip_rows` iteration values,
+        ///  column `c` at angle `2π * c / strip_width`, row `r` at radius
+        ///  `radius_max * exp(-2π * r / strip_width)`.
+        ///
+        ///  # Safety
+        ///
+        ///  dst must point to dst_len valid floats, at least strip_width * strip_rows
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "mandelbrot_expmap_f32", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        public static extern void mandelbrot_expmap_f32(double center_x, double center_y, double radius_max, int strip_width, int strip_rows, RenderParams @params, float* dst, nuint dst_len);
+
+        /// <summary>
+        ///  Reconstructs a frame centered on the strip's center from a strip of
+        ///  `mandelbrot_expmap_f32`. `zoom` is fractional pixels per world unit,
+        ///  `rotation` counter-clockwise degrees; pixels outside the strip are
+        ///  iterated. See `mandelbrot_render_into` for the layout of `dst`.
+        ///
+        ///  # Safety
+        ///
+        ///  values must point to strip_width * strip_rows floats, dst to dst_len valid bytes
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "expmap_frame_render_into", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        public static extern void expmap_frame_render_into(float* values, double center_x, double center_y, double radius_max, int strip_width, int strip_rows, double zoom, double rotation, int width_px, int height_px, RenderParams @params, PixelFormat format, byte* dst, nuint dst_len, nuint stride_bytes);
+
+        /// <summary>
+        ///  Width and height of the tiles of `tile_render_into` in pixels.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "tile_size", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        public static extern int tile_size();
+
+        /// <summary>
+        ///  Renders slippy-map tile (z, x, y), see the `tiles` module for the grid.
+        ///  Iteration values are kept in a process-wide LRU cache, so panning over
+        ///  tiles only computes the newly exposed ones and palette changes only
+        ///  recolor. See `mandelbrot_render_into` for the layout of `dst`, which must
+        ///  hold `tile_size()` square. False for a level past 40 or a short buffer.
+        ///
+        ///  # Safety
+        ///
+        ///  dst must point to dst_len valid bytes
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "tile_render_into", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        [return: MarshalAs(UnmanagedType.U1)]
+        public static extern bool tile_render_into(uint z, long x, long y, RenderParams @params, PixelFormat format, byte* dst, nuint dst_len, nuint stride_bytes);
+
+        /// <summary>
+        ///  Bounds the tile cache, 64 MiB by default. Shrinks it right away.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "tile_cache_set_capacity", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        public static extern void tile_cache_set_capacity(nuint capacity_bytes);
+
+        [DllImport(__DllName, EntryPoint = "tile_cache_clear", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        public static extern void tile_cache_clear();
+
+        /// <summary>
+        ///  Writes the palette as a lookup table: `dst[i]` is the ARGB color of
+        ///  iteration `i` for the given `max_iter`, entries from `max_iter` on are black.
+        ///  `color_width` is the number of iterations per palette segment (50 by default).
+        ///
+        ///  # Safety
+        ///
+        ///  dst must point to dst_len valid u32s
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "colormap_lut_u32", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        public static extern void colormap_lut_u32(ColormapMode colormap, int color_width, int max_iter, uint* dst, nuint dst_len);
+
+        /// <summary>
+        ///  Encodes a packed ARGB frame as PNG at `path` (UTF-8, `path_len` bytes)
+        ///  and stores `view` in its tEXt chunks. Returns false on any failure.
+        ///
+        ///  # Safety
+        ///
+        ///  path must point to path_len bytes, argb to argb_len u32s
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "png_write_argb", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        [return: MarshalAs(UnmanagedType.U1)]
+        public static extern bool png_write_argb(byte* path, nuint path_len, ViewDescription view, int width_px, int height_px, uint* argb, nuint argb_len);
+
+        /// <summary>
+        ///  Reads the view stored by `png_write_argb` (or the `fractal-render` CLI)
+        ///  from an in-memory PNG file into `out`. Returns false if the data is not a
+        ///  PNG or the chunks are missing or malformed.
+        ///
+        ///  # Safety
+        ///
+        ///  data must point to data_len bytes, out to a writable ViewDescription
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "png_read_view", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        [return: MarshalAs(UnmanagedType.U1)]
+        public static extern bool png_read_view(byte* data, nuint data_len, ViewDescription* @out);
+
+        /// <summary>
+        ///  Parses a location file (TOML, or JSON when it starts with `{`) into `out`.
+        ///  Returns false if the text is malformed, has an unsupported version or
+        ///  describes a view the renderers cannot represent.
+        ///
+        ///  # Safety
+        ///
+        ///  text must point to text_len UTF-8 bytes, out to a writable ViewDescription
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "location_parse", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        [return: MarshalAs(UnmanagedType.U1)]
+        public static extern bool location_parse(byte* text, nuint text_len, ViewDescription* @out);
+
+        /// <summary>
+        ///  Serializes `view` as a location file (UTF-8, no terminator).
+        ///  Returns the length in bytes and only writes if `dst_len` is large enough,
+        ///  so a first call with `dst_len = 0` queries the size. Returns 0 on failure.
+        ///
+        ///  # Safety
+        ///
+        ///  dst must be null or point to dst_len writable bytes
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "location_serialize", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        public static extern nuint location_serialize(ViewDescription view, LocationFormat format, byte* dst, nuint dst_len);
+
+        /// <summary>
+        ///  Imports a Kalles Fraktaler `.kfr` file into `out`. KF zooms are relative
+        ///  to the image height, so the height of the target view is required.
+        ///  Returns false for malformed files and unsupported formulas.
+        ///
+        ///  # Safety
+        ///
+        ///  text must point to text_len UTF-8 bytes, out to a writable ViewDescription
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "location_import_kfr", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        [return: MarshalAs(UnmanagedType.U1)]
+        public static extern bool location_import_kfr(byte* text, nuint text_len, int height_px, ViewDescription* @out);
+
+        /// <summary>
+        ///  Imports entry `entry_index` of a Fractint `.par` file into `out`.
+        ///  Fractint magnifications are relative to the image height, so the height of
+        ///  the target view is required. Returns false for malformed files, a missing
+        ///  entry and unsupported formulas.
+        ///
+        ///  # Safety
+        ///
+        ///  text must point to text_len UTF-8 bytes, out to a writable ViewDescription
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "location_import_par", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        [return: MarshalAs(UnmanagedType.U1)]
+        public static extern bool location_import_par(byte* text, nuint text_len, int entry_index, int height_px, ViewDescription* @out);
+
+        /// <summary>
+        ///  Suggests a max iteration count from the scale alone
+        ///  (`zoom` = pixels per world unit). The result can be used directly as
+        ///  `RenderSettings.MaxIterations`.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "suggest_max_iterations", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        public static extern int suggest_max_iterations(ulong zoom);
+
+        /// <summary>
+        ///  Refines `start_iter` with a cheap low resolution preview of the view:
+        ///  the limit is doubled while more than `max_iter_fraction` (0..1) of the
+        ///  preview samples reach it, and as long as doubling lets more of them escape.
+        ///  Pass `suggest_max_iterations(zoom)` as `start_iter` for a sensible start.
+        ///  Invalid views return `start_iter` clamped like a valid result.
+        /// </summary>
+        [DllImport(__DllName, EntryPoint = "adaptive_max_iterations", CallingConvention = CallingConvention.Cdecl, ExactSpelling = true)]
+        public static extern int adaptive_max_iterations(float center_x, float center_y, ulong zoom, int width_px, int height_px, int start_iter, float max_iter_fraction);
+
+
+    }
+
+    /// <summary>
+    ///  Everything besides the view that affects the rendered image.
+    /// </summary>
+    [StructLayout(LayoutKind.Sequential)]
+    public unsafe partial struct RenderParams
+    {
+        public Formula formula;
+        /// <summary>
+        ///  Iteration limit, points reaching it are drawn black.
+        /// </summary>
+        public int max_iter;
+        /// <summary>
+        ///  Escape radius compared against `bailout`. Radii of 2 or more are exact
+        ///  for the Euclidean norm; smooth coloring gets smoother with larger radii.
+        ///  Non-finite or non-positive values fall back to 2.
+        /// </summary>
+        public double escape_radius;
+        /// <summary>
+        ///  Norm of `z` tested against `escape_radius`.
+        /// </summary>
+        public BailoutNorm bailout;
+        public ColormapMode colormap;
+        public Coloring coloring;
+    }
+
+    /// <summary>
+    ///  Center, scale and render parameters of a view, independent of the
+    ///  output size. Enough to reopen the exact location.
+    /// </summary>
+    [StructLayout(LayoutKind.Sequential)]
+    public unsafe partial struct ViewDescription
+    {
+        public double center_x;
+        public double center_y;
+        /// <summary>
+        ///  pixels per world unit
+        /// </summary>
+        public ulong zoom;
+        /// <summary>
+        ///  counter-clockwise rotation in degrees
+        /// </summary>
+        public double rotation;
+        public RenderParams @params;
     }
 
 
+    /// <summary>
+    ///  Segment logic used to walk the palette.
+    /// </summary>
+    public enum ColormapMode : uint
+    {
+        /// <summary>
+        ///  Cycles over all segments, truncating interpolated channels.
+        /// </summary>
+        Cyclic = 0,
+        /// <summary>
+        ///  Matches `ClassicColormapProvider` in C#: the first segment is a
+        ///  non-repeating ramp, the rest cycle over `MAP_LEN - 1` segments and
+        ///  channels are rounded half to even.
+        /// </summary>
+        Classic = 1,
+    }
+
+    /// <summary>
+    ///  How `z` is measured against the escape radius.
+    ///
+    ///  Only `Euclidean` with a radius of at least 2 yields the exact Mandelbrot
+    ///  set; the other norms move the band boundaries for artistic effects.
+    ///  `RealOnly` and `ImagOnly` ignore one axis, so points that diverge along
+    ///  the other one may reach `max_iter` and are drawn as interior.
+    /// </summary>
+    public enum BailoutNorm : uint
+    {
+        /// <summary>
+        ///  `re^2 + im^2 &gt; radius^2`, the classic `|z| &gt; radius` test.
+        /// </summary>
+        Euclidean = 0,
+        /// <summary>
+        ///  `|re| + |im| &gt; radius`.
+        /// </summary>
+        Manhattan = 1,
+        /// <summary>
+        ///  `max(|re|, |im|) &gt; radius`.
+        /// </summary>
+        Max = 2,
+        /// <summary>
+        ///  `|re| &gt; radius`, the imaginary part is ignored.
+        /// </summary>
+        RealOnly = 3,
+        /// <summary>
+        ///  `|im| &gt; radius`, the real part is ignored.
+        /// </summary>
+        ImagOnly = 4,
+    }
+
+    /// <summary>
+    ///  Iterated function `z -&gt; f(z) + c`.
+    /// </summary>
+    public enum Formula : uint
+    {
+        /// <summary>
+        ///  `z^2 + c`
+        /// </summary>
+        Mandelbrot = 0,
+        /// <summary>
+        ///  `conj(z)^2 + c`, also known as Mandelbar.
+        /// </summary>
+        Tricorn = 1,
+        /// <summary>
+        ///  `(|re z| + i|im z|)^2 + c`, drawn with world Y up so the ship stands upside down.
+        /// </summary>
+        BurningShip = 2,
+    }
+
+    /// <summary>
+    ///  Text encoding of a [`Location`].
+    /// </summary>
+    public enum LocationFormat : uint
+    {
+        Toml = 0,
+        Json = 1,
+    }
+
+    /// <summary>
+    ///  Memory layout of one output pixel.
+    /// </summary>
+    public enum PixelFormat : uint
+    {
+        /// <summary>
+        ///  Packed `0xAARRGGBB` `u32` in native byte order, the layout of the `*_u32` APIs.
+        /// </summary>
+        Argb32 = 0,
+        /// <summary>
+        ///  Bytes `R, G, B, A`.
+        /// </summary>
+        Rgba8 = 1,
+        /// <summary>
+        ///  Bytes `B, G, R, A`.
+        /// </summary>
+        Bgra8 = 2,
+        /// <summary>
+        ///  Little-endian `u16`, `rrrrrggg gggbbbbb`, alpha dropped.
+        /// </summary>
+        Rgb565 = 3,
+        /// <summary>
+        ///  Bytes `R, G, B, A` with color channels multiplied by alpha.
+        /// </summary>
+        Rgba8Premultiplied = 4,
+        /// <summary>
+        ///  Bytes `B, G, R, A` with color channels multiplied by alpha.
+        /// </summary>
+        Bgra8Premultiplied = 5,
+    }
+
+    /// <summary>
+    ///  How iteration counts are turned into palette positions.
+    /// </summary>
+    public enum Coloring : uint
+    {
+        /// <summary>
+        ///  Integer iteration count, banded.
+        /// </summary>
+        EscapeTime = 0,
+        /// <summary>
+        ///  Continuous iteration count, no bands. Looks best with a large escape radius.
+        /// </summary>
+        Smooth = 1,
+        /// <summary>
+        ///  Integer counts equalized over the frame's histogram.
+        /// </summary>
+        Histogram = 2,
+        /// <summary>
+        ///  Continuous counts equalized over the frame's histogram.
+        /// </summary>
+        SmoothHistogram = 3,
+    }
+
+    public enum Resampling : uint
+    {
+        Nearest = 0,
+        Bilinear = 1,
+    }
+
 
 }
@@ -0,0 +1,290 @@
+//! Keyframed zoom animations.
+//!
+//! Between two keyframes the zoom changes exponentially, so every frame
+//! magnifies by the same factor, and the center moves such that the one point
+//! both keyframes show at the same pixel stays there: the camera dives
+//! straight in instead of sliding sideways at depth. Easing reshapes time
+//! within a segment.
+//!
+//! Escape-time and smooth colorings only depend on the iteration value, so
+//! structures keep their colors from frame to frame. Histogram colorings blend
+//! each frame's distribution with the previous one ([`FrameRenderer`]) to
+//! avoid flicker.
+//!
+//! Frames deeper than `f64` precision are iterated by perturbation against a
+//! reference orbit. A dive keeps its reference in view for many frames, so
+//! [`FrameRenderer`] reuses the previous frame's orbit until the reference
+//! leaves the view instead of iterating a new one per frame.
+
+use serde::{Deserialize, Serialize};
+
+use crate::histogram::CumulativeHistogram;
+use crate::perturbation::{ReferenceOrbit, iterate_values_perturbed, needs_perturbation, supports};
+use crate::pixel::PixelTarget;
+use crate::render::{
+    RenderParams, Viewport, colorize_into, colorize_with_histogram, frame_histogram,
+    iterate_values, render_into,
+};
+use crate::view::ViewDescription;
+
+/// Time curve of a segment between two keyframes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum Easing {
+    #[default]
+    Linear,
+    /// Starts slow.
+    EaseIn,
+    /// Ends slow.
+    EaseOut,
+    /// Starts and ends slow.
+    EaseInOut,
+}
+
+impl Easing {
+    /// Maps `t` in `0..=1` onto `0..=1`.
+    pub fn apply(self, t: f64) -> f64 {
+        let t = t.clamp(0., 1.);
+        match self {
+            Self::Linear => t,
+            Self::EaseIn => t * t,
+            Self::EaseOut => t * (2. - t),
+            Self::EaseInOut => t * t * (3. - 2. * t),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Keyframe {
+    pub view: ViewDescription,
+    /// Frames from this keyframe to the next one, ignored on the last.
+    pub frames: u32,
+    /// Easing of the segment to the next keyframe.
+    pub easing: Easing,
+}
+
+/// One view of an animation.
+#[derive(Clone, Copy, Debug)]
+pub struct Frame {
+    /// The view with the zoom rounded, e.g. for image metadata.
+    pub view: ViewDescription,
+    /// Exact pixels per world unit.
+    pub zoom: f64,
+}
+
+impl Frame {
+    pub fn viewport(&self, width: usize, height: usize) -> Option<Viewport> {
+        self.view
+            .viewport(width, height)
+            .map(|v| v.with_zoom(self.zoom))
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Animation {
+    keyframes: Vec<Keyframe>,
+}
+
+impl Animation {
+    /// `None` without keyframes or with a zero zoom.
+    pub fn new(keyframes: Vec<Keyframe>) -> Option<Self> {
+        if keyframes.is_empty() || keyframes.iter().any(|k| k.view.zoom == 0) {
+            return None;
+        }
+        Some(Self { keyframes })
+    }
+
+    /// Every segment's frames plus the final keyframe.
+    pub fn frame_count(&self) -> u64 {
+        let (_, segments) = self.keyframes.split_last().expect("checked in new");
+        segments.iter().map(|k| k.frames as u64).sum::<u64>() + 1
+    }
+
+    /// Frame `index`, past the end the last keyframe.
+    pub fn frame(&self, index: u64) -> Frame {
+        let mut index = index;
+        for pair in self.keyframes.windows(2) {
+            let (from, to) = (&pair[0], &pair[1]);
+            if index < from.frames as u64 {
+                let t = from.easing.apply(index as f64 / from.frames as f64);
+                return interpolate(&from.view, &to.view, t);
+            }
+            index -= from.frames as u64;
+        }
+        let last = self.keyframes.last().expect("checked in new").view;
+        Frame {
+            view: last,
+            zoom: last.zoom as f64,
+        }
+    }
+}
+
+/// The view a fraction `t` of the way from `from` to `to`. Render settings
+/// other than the iteration limit switch over at `t = 1`.
+pub fn interpolate(from: &ViewDescription, to: &ViewDescription, t: f64) -> Frame {
+    let (zoom_from, zoom_to) = (from.zoom as f64, to.zoom as f64);
+    let zoom = (zoom_from.ln() + (zoom_to.ln() - zoom_from.ln()) * t).exp();
+
+    // Zooms around the fixed point p with (p - from) * zoom_from = (p - to) * zoom_to,
+    // center = p - (p - from) * zoom_from / zoom, written relative to from and to.
+    let s = if (zoom_to - zoom_from).abs() <= f64::EPSILON * zoom_from {
+        t
+    } else {
+        (1. - zoom_from / zoom) / (1. - zoom_from / zoom_to)
+    };
+
+    let lerp = |a: f64, b: f64, t: f64| a + (b - a) * t;
+    let params = if t < 1. { from.params } else { to.params };
+    let max_iter = lerp(from.params.max_iter as f64, to.params.max_iter as f64, t);
+    Frame {
+        view: ViewDescription {
+            center_x: lerp(from.center_x, to.center_x, s),
+            center_y: lerp(from.center_y, to.center_y, s),
+            zoom: (zoom.round() as u64).max(1),
+            rotation: lerp(from.rotation, to.rotation, t),
+            params: RenderParams {
+                max_iter: max_iter.round() as i32,
+                ..params
+            },
+        },
+        zoom,
+    }
+}
+
+/// Renders consecutive frames, carrying the histogram of histogram
+/// colorings over so the palette drifts instead of jumping, and the
+/// reference orbit of deep frames so it is iterated once per dive.
+pub struct FrameRenderer {
+    /// weight of the previous frames' distribution, `0..=1`
+    keep: f64,
+    previous: Option<CumulativeHistogram>,
+    values: Vec<f32>,
+    orbit: Option<ReferenceOrbit>,
+    orbits_computed: u64,
+}
+
+impl FrameRenderer {
+    /// `keep` is how much of the previous frames' histogram is kept per frame,
+    /// 0 equalizes every frame on its own.
+    pub fn new(keep: f64) -> Self {
+        Self {
+            keep,
+            previous: None,
+            values: Vec::new(),
+            orbit: None,
+            orbits_computed: 0,
+        }
+    }
+
+    pub fn render(&mut self, view: &Viewport, params: &RenderParams, target: &mut PixelTarget) {
+        let deep = supports(params.formula) && needs_perturbation(view);
+        if !deep && !params.coloring.is_histogram() {
+            render_into(view, params, target);
+            return;
+        }
+
+        let mut values = std::mem::take(&mut self.values);
+        values.resize(view.pixel_count(), 0.);
+        if deep {
+            let orbit = self.reference_orbit(view, params);
+            iterate_values_perturbed(view, params, orbit, &mut values);
+        } else {
+            iterate_values(view, params, &mut values);
+        }
+        self.colorize(&values, params, target);
+        self.values = values;
+    }
+
+    /// Reference orbits iterated so far, one per frame at most.
+    pub fn orbits_computed(&self) -> u64 {
+        self.orbits_computed
+    }
+
+    /// The previous frame's orbit if it still fits `view`, else one around
+    /// the view's center.
+    fn reference_orbit(&mut self, view: &Viewport, params: &RenderParams) -> &ReferenceOrbit {
+        let kernel = params.kernel();
+        match &mut self.orbit {
+            Some(orbit) if orbit.reusable_for(view, &kernel) => {}
+            slot => {
+                *slot = Some(ReferenceOrbit::compute(
+                    &kernel,
+                    view.center_x,
+                    view.center_y,
+                ));
+                self.orbits_computed += 1;
+            }
+        }
+        self.orbit.as_ref().expect("set above")
+    }
+
+    /// Colors a frame's iteration values laid out like `target`, e.g. one
+    /// resampled from an exponential map.
+    pub fn colorize(&mut self, values: &[f32], params: &RenderParams, target: &mut PixelTarget) {
+        if !params.coloring.is_histogram() {
+            colorize_into(values, params, target);
+            return;
+        }
+
+        let width = target.width();
+        let values = &values[..width * target.height()];
+        let mut histogram = frame_histogram(values, width, params.max_iter());
+        if let Some(previous) = &self.previous {
+            histogram = histogram.blend(previous, self.keep);
+        }
+        colorize_with_histogram(values, params, target, &histogram);
+        self.previous = Some(histogram);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::Coloring;
+
+    fn view(zoom: u64) -> ViewDescription {
+        ViewDescription {
+            center_x: -0.7436438870371587,
+            center_y: 0.13182590420531198,
+            zoom,
+            rotation: 0.,
+            params: RenderParams {
+                max_iter: 3000,
+                coloring: Coloring::Smooth,
+                ..RenderParams::default()
+            },
+        }
+    }
+
+    #[test]
+    fn deep_frames_share_the_reference_orbit() {
+        let keyframes = [(1 << 44, 12), (1 << 50, 0)].map(|(zoom, frames)| Keyframe {
+            view: view(zoom),
+            frames,
+            easing: Easing::Linear,
+        });
+        let animation = Animation::new(keyframes.to_vec()).unwrap();
+
+        let mut renderer = FrameRenderer::new(0.);
+        let mut pixels = vec![0u32; 32 * 24];
+        for index in 0..animation.frame_count() {
+            let frame = animation.frame(index);
+            let viewport = frame.viewport(32, 24).unwrap();
+            let mut target = PixelTarget::from_argb(&mut pixels, 32, 24).unwrap();
+            renderer.render(&viewport, &frame.view.params, &mut target);
+        }
+        assert_eq!(renderer.orbits_computed(), 1);
+    }
+
+    #[test]
+    fn shallow_frames_iterate_directly() {
+        let frame = interpolate(&view(1000), &view(100_000), 0.5);
+        let viewport = frame.viewport(16, 16).unwrap();
+        let mut pixels = vec![0u32; 16 * 16];
+        let mut target = PixelTarget::from_argb(&mut pixels, 16, 16).unwrap();
+        let mut renderer = FrameRenderer::new(0.);
+        renderer.render(&viewport, &frame.view.params, &mut target);
+        assert_eq!(renderer.orbits_computed(), 0);
+    }
+}
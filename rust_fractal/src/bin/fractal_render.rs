@@ -0,0 +1,730 @@
+//! Headless renderer, writes views to image files.
+//! The view parameters are embedded in PNG text chunks.
+//!
+//! ```text
+//! cargo run --release --features cli --bin fractal-render -- \
+//!     --center-x -0.743643887 --center-y 0.131825904 --zoom 2000000 \
+//!     --width 1920 --height 1080 --coloring smooth-histogram -o seahorse.png
+//! ```
+//!
+//! `--location` starts from a saved view instead (location file, `.kfr`,
+//! `.par` or a PNG written by this tool); the other view options override it.
+//!
+//! `--tiled` renders band by band through a scratch file next to the output,
+//! for posters that do not fit into memory. Running the same command again
+//! after an interruption resumes it. TIFF output is always tiled.
+//!
+//! `--to` animates from the view to a second location, `--keyframes` along a
+//! list of them:
+//!
+//! ```toml
+//! [[keyframe]]
+//! location = "overview.toml"  # relative to this file, any --location format
+//! frames = 240                # frames to the next keyframe
+//! easing = "ease-in-out"
+//!
+//! [[keyframe]]
+//! location = "seahorse.kfr"
+//! ```
+//!
+//! `--expmap` renders the zoom to `--to` once as an exponential map and
+//! resamples the frames from it, much faster for long zooms.
+//!
+//! Frames go to numbered PNGs, `-o frames/zoom-#####.png`, or as raw RGBA to
+//! stdout with `-o -`, e.g. piped into
+//! `ffmpeg -f rawvideo -pix_fmt rgba -s 1280x720 -r 60 -i - zoom.mp4`.
+
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Cursor, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result, anyhow, bail};
+use clap::{Args, Parser};
+use rust_fractal::animation::{Animation, Easing, FrameRenderer, Keyframe};
+use rust_fractal::colormap::ColormapMode;
+use rust_fractal::estimate::suggest_max_iterations;
+use rust_fractal::expmap::{ExpMap, reconstruct_values, render_expmap};
+use rust_fractal::export::{read_png_view, write_png};
+use rust_fractal::import::{parse_kfr, parse_par};
+use rust_fractal::kernel::{BailoutNorm, Formula};
+use rust_fractal::location::Location;
+use rust_fractal::pixel::{PixelFormat, PixelTarget};
+use rust_fractal::poster::{self, DEFAULT_BAND_HEIGHT, Poster, PosterError, PosterFormat, Stage};
+use rust_fractal::render::{Coloring, RenderParams, render_into};
+use rust_fractal::view::ViewDescription;
+use serde::Deserialize;
+
+#[derive(Debug, Parser)]
+#[command(name = "fractal-render")]
+#[command(about = "Render a fractal view to an image file", long_about = None)]
+struct Cli {
+    #[command(flatten)]
+    view: ViewArgs,
+
+    /// Image width in pixels
+    #[arg(long, default_value_t = 1280)]
+    width: u32,
+
+    /// Image height in pixels
+    #[arg(long, default_value_t = 720)]
+    height: u32,
+
+    /// Output .png, or .tif/.tiff file. Animations need a '#' run for the
+    /// frame number or '-' for raw RGBA frames on stdout
+    #[arg(short, long)]
+    output: PathBuf,
+
+    /// Render in bands of rows, resuming an interrupted render of the same image
+    #[arg(long)]
+    tiled: bool,
+
+    /// Rows per band of a tiled render
+    #[arg(long, default_value_t = DEFAULT_BAND_HEIGHT)]
+    band_height: u32,
+
+    /// Discard the leftovers of an interrupted tiled render to this output
+    #[arg(long)]
+    restart: bool,
+
+    /// Animate from the view to this location (same formats as --location)
+    #[arg(long, conflicts_with = "tiled")]
+    to: Option<PathBuf>,
+
+    /// Animate along the keyframes of a TOML file
+    #[arg(long, conflicts_with_all = [
+        "tiled", "to", "location", "par_entry", "center_x", "center_y", "zoom", "rotation",
+        "max_iter", "formula", "palette", "coloring", "escape_radius", "bailout",
+    ])]
+    keyframes: Option<PathBuf>,
+
+    /// Frames from the view to --to
+    #[arg(long, default_value_t = 240, requires = "to")]
+    frames: u32,
+
+    /// Easing from the view to --to
+    #[arg(long, value_enum, default_value_t, requires = "to")]
+    easing: Easing,
+
+    /// Render one exponential map (log-polar strip) for the whole zoom to
+    /// --to and resample every frame from it instead of iterating each one
+    #[arg(long, requires = "to")]
+    expmap: bool,
+
+    /// Share of the previous frames' histogram kept per frame by histogram
+    /// colorings, 0 equalizes every frame on its own
+    #[arg(long, default_value_t = 0.8)]
+    color_inertia: f64,
+}
+
+#[derive(Debug, Deserialize)]
+struct KeyframeFile {
+    keyframe: Vec<KeyframeEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+struct KeyframeEntry {
+    location: PathBuf,
+    par_entry: Option<String>,
+    #[serde(default)]
+    frames: u32,
+    #[serde(default)]
+    easing: Easing,
+}
+
+/// View options, each one overrides the value from `--location`.
+#[derive(Debug, Args)]
+struct ViewArgs {
+    /// Start from a saved view: .toml/.json location file, Kalles Fraktaler
+    /// .kfr, Fractint .par or a PNG written by this tool
+    #[arg(long)]
+    location: Option<PathBuf>,
+
+    /// Entry of a .par file, defaults to the first one
+    #[arg(long, requires = "location")]
+    par_entry: Option<String>,
+
+    /// Real part of the view center [default: -0.5]
+    #[arg(long, allow_hyphen_values = true)]
+    center_x: Option<f64>,
+
+    /// Imaginary part of the view center [default: 0]
+    #[arg(long, allow_hyphen_values = true)]
+    center_y: Option<f64>,
+
+    /// Scale in pixels per world unit [default: 300]
+    #[arg(long)]
+    zoom: Option<u64>,
+
+    /// Counter-clockwise view rotation in degrees [default: 0]
+    #[arg(long, allow_hyphen_values = true)]
+    rotation: Option<f64>,
+
+    /// Iteration limit, suggested from the zoom when neither this nor a location is given
+    #[arg(long)]
+    max_iter: Option<u32>,
+
+    #[arg(long, value_enum)]
+    formula: Option<Formula>,
+
+    #[arg(long, value_enum)]
+    palette: Option<ColormapMode>,
+
+    #[arg(long, value_enum)]
+    coloring: Option<Coloring>,
+
+    /// Escape radius [default: 2]
+    #[arg(long)]
+    escape_radius: Option<f64>,
+
+    #[arg(long, value_enum)]
+    bailout: Option<BailoutNorm>,
+}
+
+impl ViewArgs {
+    /// Location file (or defaults) with the command line overrides applied.
+    /// `height` is needed to convert the relative zoom of .kfr and .par files.
+    fn resolve(&self, height: u32) -> Result<ViewDescription> {
+        let base = match &self.location {
+            Some(path) => load_location(path, self.par_entry.as_deref(), height)
+                .with_context(|| format!("failed to load location {}", path.display()))?,
+            None => ViewDescription {
+                center_x: -0.5,
+                center_y: 0.,
+                zoom: 300,
+                rotation: 0.,
+                params: RenderParams::default(),
+            },
+        };
+
+        let zoom = self.zoom.unwrap_or(base.zoom);
+        let max_iter = match (self.max_iter, &self.location) {
+            (Some(max_iter), _) => max_iter,
+            (None, Some(_)) => base.params.max_iter(),
+            (None, None) => suggest_max_iterations(zoom),
+        };
+        let p = base.params;
+        Ok(ViewDescription {
+            center_x: self.center_x.unwrap_or(base.center_x),
+            center_y: self.center_y.unwrap_or(base.center_y),
+            zoom,
+            rotation: self.rotation.unwrap_or(base.rotation),
+            params: RenderParams {
+                formula: self.formula.unwrap_or(p.formula),
+                max_iter: i32::try_from(max_iter).context("--max-iter is too large")?,
+                escape_radius: self.escape_radius.unwrap_or(p.escape_radius),
+                bailout: self.bailout.unwrap_or(p.bailout),
+                colormap: self.palette.unwrap_or(p.colormap),
+                coloring: self.coloring.unwrap_or(p.coloring),
+            },
+        })
+    }
+}
+
+fn load_location(path: &Path, par_entry: Option<&str>, height: u32) -> Result<ViewDescription> {
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+
+    if extension == "png" {
+        let data = fs::read(path)?;
+        return Ok(read_png_view(Cursor::new(data))?);
+    }
+
+    let text = fs::read_to_string(path)?;
+    let location = match extension.as_str() {
+        "kfr" => parse_kfr(&text, height)?,
+        "par" => {
+            let mut entries = parse_par(&text, height)?.into_iter();
+            let entry = match par_entry {
+                Some(name) => entries
+                    .find(|e| e.name == name)
+                    .ok_or_else(|| anyhow!("no entry named '{name}'"))?,
+                None => entries.next().ok_or_else(|| anyhow!("no entries"))?,
+            };
+            entry.location?
+        }
+        _ => Location::parse(&text)?,
+    };
+    Ok(location.to_view()?)
+}
+
+fn main() -> Result<()> {
+    let cli = Cli::parse();
+
+    if let Some(path) = &cli.keyframes {
+        let animation = load_keyframes(path, cli.height)
+            .with_context(|| format!("failed to load keyframes {}", path.display()))?;
+        return render_animation(&cli, &animation, None);
+    }
+
+    let description = cli.view.resolve(cli.height)?;
+    if let Some(path) = &cli.to {
+        let to = load_location(path, None, cli.height)
+            .with_context(|| format!("failed to load location {}", path.display()))?;
+        let keyframes = vec![
+            Keyframe {
+                view: description,
+                frames: cli.frames,
+                easing: cli.easing,
+            },
+            Keyframe {
+                view: to,
+                frames: 0,
+                easing: Easing::Linear,
+            },
+        ];
+        let animation =
+            Animation::new(keyframes).ok_or_else(|| anyhow!("zoom must be positive"))?;
+        let strip = if cli.expmap {
+            Some(render_strip(&cli, &description, &to)?)
+        } else {
+            None
+        };
+        return render_animation(&cli, &animation, strip);
+    }
+    let params = description.params;
+
+    let format = PosterFormat::from_path(&cli.output)
+        .ok_or_else(|| anyhow!("output must be a .png, .tif or .tiff file"))?;
+    if cli.tiled || format == PosterFormat::Tiff {
+        return render_tiled(&cli, description, format);
+    }
+
+    let (width, height) = (cli.width as usize, cli.height as usize);
+    let view = description
+        .viewport(width, height)
+        .ok_or_else(|| anyhow!("width, height and zoom must be positive"))?;
+
+    let stride = width * PixelFormat::Rgba8.bytes_per_pixel();
+    let mut rgba = vec![0u8; stride * height];
+    let mut target = PixelTarget::new(&mut rgba, width, height, stride, PixelFormat::Rgba8)
+        .ok_or_else(|| anyhow!("{width}x{height} does not fit into memory"))?;
+    render_into(&view, &params, &mut target);
+
+    let file = File::create(&cli.output)
+        .with_context(|| format!("create failed: {}", cli.output.display()))?;
+    write_png(
+        BufWriter::new(file),
+        cli.width,
+        cli.height,
+        &rgba,
+        Some(&description),
+    )
+    .with_context(|| format!("png encoding failed: {}", cli.output.display()))?;
+
+    eprintln!(
+        "rendered {}x{} ({} iterations) -> {}",
+        cli.width,
+        cli.height,
+        params.max_iter,
+        cli.output.display()
+    );
+    Ok(())
+}
+
+fn load_keyframes(path: &Path, height: u32) -> Result<Animation> {
+    let file: KeyframeFile = toml::from_str(&fs::read_to_string(path)?)?;
+    let dir = path.parent().unwrap_or(Path::new(""));
+    let keyframes = file
+        .keyframe
+        .iter()
+        .map(|entry| {
+            let location = dir.join(&entry.location);
+            let view = load_location(&location, entry.par_entry.as_deref(), height)
+                .with_context(|| format!("failed to load location {}", location.display()))?;
+            Ok(Keyframe {
+                view,
+                frames: entry.frames,
+                easing: entry.easing,
+            })
+        })
+        .collect::<Result<_>>()?;
+    Animation::new(keyframes)
+        .ok_or_else(|| anyhow!("needs at least one keyframe, with positive zooms"))
+}
+
+/// Exponential map around the fixed point of the zoom from `from` to `to`,
+/// covering every frame in between.
+fn render_strip(
+    cli: &Cli,
+    from: &ViewDescription,
+    to: &ViewDescription,
+) -> Result<(ExpMap, Vec<f32>)> {
+    let (zoom_from, zoom_to) = (from.zoom as f64, to.zoom as f64);
+    if from.zoom == to.zoom {
+        bail!("--expmap needs a zoom change between the views");
+    }
+    // the point both views show at the same pixel, see animation::interpolate
+    let fixed = |a: f64, b: f64| (b * zoom_to - a * zoom_from) / (zoom_to - zoom_from);
+    let (center_x, center_y) = (
+        fixed(from.center_x, to.center_x),
+        fixed(from.center_y, to.center_y),
+    );
+    // its distance from the frame center in pixels stays the same in every frame
+    let distance = (center_x - from.center_x).hypot(center_y - from.center_y) * zoom_from;
+    let reach = distance + (cli.width as f64).hypot(cli.height as f64) / 2.;
+    let map = ExpMap::covering(center_x, center_y, reach, zoom_from, zoom_to)
+        .ok_or_else(|| anyhow!("cannot cover the zoom with an exponential map"))?;
+
+    let params = RenderParams {
+        max_iter: from.params.max_iter.max(to.params.max_iter),
+        ..to.params
+    };
+    eprintln!("rendering exponential map {}x{}", map.width, map.rows);
+    let mut values = vec![0f32; map.sample_count()];
+    render_expmap(&map, &params, &mut values);
+    Ok((map, values))
+}
+
+fn render_animation(
+    cli: &Cli,
+    animation: &Animation,
+    strip: Option<(ExpMap, Vec<f32>)>,
+) -> Result<()> {
+    let raw = cli.output.as_os_str() == "-";
+    if !raw && !cli.output.to_string_lossy().contains('#') {
+        bail!("animation output needs a '#' run for the frame number, e.g. zoom-#####.png, or '-'");
+    }
+
+    let (width, height) = (cli.width as usize, cli.height as usize);
+    let stride = width * PixelFormat::Rgba8.bytes_per_pixel();
+    let mut rgba = vec![0u8; stride * height];
+    let mut renderer = FrameRenderer::new(cli.color_inertia);
+    let mut frame_values = if strip.is_some() {
+        vec![0f32; width * height]
+    } else {
+        Vec::new()
+    };
+    let mut stdout = io::stdout().lock();
+
+    let count = animation.frame_count();
+    for index in 0..count {
+        let frame = animation.frame(index);
+        let view = frame
+            .viewport(width, height)
+            .ok_or_else(|| anyhow!("width, height and zoom must be positive"))?;
+        let mut target = PixelTarget::new(&mut rgba, width, height, stride, PixelFormat::Rgba8)
+            .ok_or_else(|| anyhow!("{width}x{height} does not fit into memory"))?;
+        match &strip {
+            Some((map, values)) => {
+                reconstruct_values(map, values, &view, &frame.view.params, &mut frame_values);
+                renderer.colorize(&frame_values, &frame.view.params, &mut target);
+            }
+            None => renderer.render(&view, &frame.view.params, &mut target),
+        }
+
+        if raw {
+            stdout
+                .write_all(&rgba)
+                .context("writing to stdout failed")?;
+        } else {
+            let path = frame_path(&cli.output, index);
+            let file = File::create(&path)
+                .with_context(|| format!("create failed: {}", path.display()))?;
+            write_png(
+                BufWriter::new(file),
+                cli.width,
+                cli.height,
+                &rgba,
+                Some(&frame.view),
+            )
+            .with_context(|| format!("png encoding failed: {}", path.display()))?;
+        }
+        eprint!("\rframe {}/{count}   ", index + 1);
+    }
+    eprintln!();
+    stdout.flush()?;
+
+    eprintln!(
+        "rendered {count} frames of {}x{} -> {}",
+        cli.width,
+        cli.height,
+        cli.output.display()
+    );
+    Ok(())
+}
+
+/// `pattern` with its first run of '#' replaced by `index`, zero padded to
+/// the length of the run.
+fn frame_path(pattern: &Path, index: u64) -> PathBuf {
+    let pattern = pattern.to_string_lossy();
+    let start = pattern.find('#').unwrap_or(pattern.len());
+    let digits = pattern[start..].bytes().take_while(|&b| b == b'#').count();
+    PathBuf::from(format!(
+        "{}{index:0digits$}{}",
+        &pattern[..start],
+        &pattern[start + digits..]
+    ))
+}
+
+fn render_tiled(cli: &Cli, description: ViewDescription, format: PosterFormat) -> Result<()> {
+    if cli.restart {
+        poster::discard(&cli.output).context("failed to discard the previous render")?;
+    }
+    let poster = Poster {
+        view: description,
+        width: cli.width,
+        height: cli.height,
+        band_height: cli.band_height,
+        format,
+    };
+
+    let mut resumed = None;
+    poster
+        .render(&cli.output, |progress| {
+            let stage = match progress.stage {
+                Stage::Rendering => "rendering",
+                Stage::Encoding => "encoding",
+            };
+            if resumed.is_none() {
+                resumed = Some(progress.done);
+                if progress.done > 0 {
+                    eprintln!("resuming after band {}", progress.done);
+                }
+            }
+            eprint!("\r{stage} band {}/{}   ", progress.done, progress.total);
+            if progress.done == progress.total {
+                eprintln!();
+            }
+        })
+        .map_err(|e| match e {
+            PosterError::OtherJob(_) => anyhow!("{e}, pass --restart to discard it"),
+            e => e.into(),
+        })
+        .with_context(|| format!("tiled render failed: {}", cli.output.display()))?;
+
+    eprintln!(
+        "rendered {}x{} ({} iterations) -> {}",
+        cli.width,
+        cli.height,
+        description.params.max_iter,
+        cli.output.display()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_fractal::location::LocationFormat;
+
+    fn cli(args: &[&str]) -> Cli {
+        Cli::try_parse_from(["fractal-render", "-o", "out.png"].iter().chain(args)).unwrap()
+    }
+
+    /// A scratch directory removed when dropped.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let dir =
+                std::env::temp_dir().join(format!("fractal-render-{name}-{}", std::process::id()));
+            fs::create_dir_all(&dir).unwrap();
+            Self(dir)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn saved_view() -> ViewDescription {
+        ViewDescription {
+            center_x: -0.75,
+            center_y: 0.1,
+            zoom: 5000,
+            rotation: 30.,
+            params: RenderParams {
+                formula: Formula::Tricorn,
+                max_iter: 1234,
+                escape_radius: 4.,
+                bailout: BailoutNorm::Max,
+                colormap: ColormapMode::Classic,
+                coloring: Coloring::Smooth,
+            },
+        }
+    }
+
+    fn write_location(dir: &TempDir) -> String {
+        let path = dir.0.join("view.toml");
+        let text = Location::from_view(&saved_view())
+            .serialize(LocationFormat::Toml)
+            .unwrap();
+        fs::write(&path, text).unwrap();
+        path.to_str().unwrap().to_string()
+    }
+
+    fn same(a: &ViewDescription, b: &ViewDescription) -> bool {
+        Location::from_view(a) == Location::from_view(b)
+    }
+
+    #[test]
+    fn frame_paths_pad_the_first_hash_run() {
+        let path = |pattern: &str, index| frame_path(Path::new(pattern), index);
+        assert_eq!(path("zoom-#####.png", 42), Path::new("zoom-00042.png"));
+        assert_eq!(path("frames/#.png", 7), Path::new("frames/7.png"));
+        // longer numbers are not cut off
+        assert_eq!(path("f-##.png", 1234), Path::new("f-1234.png"));
+        // only the first run is replaced
+        assert_eq!(path("a##-b##.png", 3), Path::new("a03-b##.png"));
+        assert_eq!(path("##/x.png", 5), Path::new("05/x.png"));
+    }
+
+    #[test]
+    fn defaults_suggest_the_iteration_limit() {
+        let view = cli(&[]).view.resolve(720).unwrap();
+        assert_eq!((view.center_x, view.center_y, view.zoom), (-0.5, 0., 300));
+        assert_eq!(view.params.max_iter(), suggest_max_iterations(300));
+
+        let deep = cli(&["--zoom", "1000000000"]).view.resolve(720).unwrap();
+        assert_eq!(
+            deep.params.max_iter(),
+            suggest_max_iterations(1_000_000_000)
+        );
+        let fixed = cli(&["--zoom", "1000000000", "--max-iter", "77"])
+            .view
+            .resolve(720)
+            .unwrap();
+        assert_eq!(fixed.params.max_iter, 77);
+    }
+
+    #[test]
+    fn a_location_file_is_used_as_saved() {
+        let dir = TempDir::new("location");
+        let location = write_location(&dir);
+        let view = cli(&["--location", &location]).view.resolve(720).unwrap();
+        assert!(same(&view, &saved_view()), "{view:?}");
+
+        // a new zoom keeps the saved limit instead of suggesting one
+        let zoomed = cli(&["--location", &location, "--zoom", "1000000000"])
+            .view
+            .resolve(720)
+            .unwrap();
+        assert_eq!(zoomed.zoom, 1_000_000_000);
+        assert_eq!(zoomed.params.max_iter, 1234);
+    }
+
+    #[test]
+    fn flags_override_the_location_file() {
+        let dir = TempDir::new("override");
+        let location = write_location(&dir);
+        let view = cli(&[
+            "--location",
+            &location,
+            "--center-x",
+            "-1.25",
+            "--center-y",
+            "-0.5",
+            "--zoom",
+            "900",
+            "--rotation",
+            "-45",
+            "--max-iter",
+            "99",
+            "--formula",
+            "burning-ship",
+            "--palette",
+            "cyclic",
+            "--coloring",
+            "histogram",
+            "--escape-radius",
+            "8",
+            "--bailout",
+            "manhattan",
+        ])
+        .view
+        .resolve(720)
+        .unwrap();
+        let expected = ViewDescription {
+            center_x: -1.25,
+            center_y: -0.5,
+            zoom: 900,
+            rotation: -45.,
+            params: RenderParams {
+                formula: Formula::BurningShip,
+                max_iter: 99,
+                escape_radius: 8.,
+                bailout: BailoutNorm::Manhattan,
+                colormap: ColormapMode::Cyclic,
+                coloring: Coloring::Histogram,
+            },
+        };
+        assert!(same(&view, &expected), "{view:?}");
+
+        // unset flags keep the saved values
+        let partial = cli(&["--location", &location, "--center-x", "0.25"])
+            .view
+            .resolve(720)
+            .unwrap();
+        assert!(same(
+            &partial,
+            &ViewDescription {
+                center_x: 0.25,
+                ..saved_view()
+            }
+        ));
+    }
+
+    #[test]
+    fn reports_bad_locations_and_limits() {
+        let dir = TempDir::new("errors");
+        let missing = dir.0.join("missing.toml");
+        let err = cli(&["--location", missing.to_str().unwrap()])
+            .view
+            .resolve(720)
+            .unwrap_err();
+        assert!(
+            format!("{err:#}").contains("failed to load location"),
+            "{err:#}"
+        );
+
+        let err = cli(&["--max-iter", "3000000000"])
+            .view
+            .resolve(720)
+            .unwrap_err();
+        assert!(err.to_string().contains("--max-iter"), "{err}");
+    }
+
+    #[test]
+    fn picks_par_entries_by_name() {
+        let dir = TempDir::new("par");
+        let par = dir.0.join("views.par");
+        fs::write(
+            &par,
+            "first {\n  type=mandel center-mag=-0.5/0/1 maxiter=300\n}\n\
+             second {\n  type=mandel center-mag=-0.75/0.1/4 maxiter=500\n}\n",
+        )
+        .unwrap();
+        let par = par.to_str().unwrap();
+        let first = cli(&["--location", par]).view.resolve(720).unwrap();
+        let second = cli(&["--location", par, "--par-entry", "second"])
+            .view
+            .resolve(720)
+            .unwrap();
+        assert_eq!((first.center_x, first.params.max_iter), (-0.5, 300));
+        assert_eq!((second.center_x, second.params.max_iter), (-0.75, 500));
+        // the magnification is relative to the image height
+        assert_eq!(second.zoom, 4 * 720 / 2);
+        assert!(
+            cli(&["--location", par, "--par-entry", "third"])
+                .view
+                .resolve(720)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn keyframes_conflict_with_view_flags() {
+        let parse = |args: &[&str]| {
+            Cli::try_parse_from(["fractal-render", "-o", "f-#.png"].iter().chain(args))
+        };
+        assert!(parse(&["--keyframes", "k.toml"]).is_ok());
+        assert!(parse(&["--keyframes", "k.toml", "--zoom", "5"]).is_err());
+        assert!(parse(&["--frames", "10"]).is_err());
+        assert!(parse(&["--par-entry", "x"]).is_err());
+    }
+}
@@ -1,3 +1,5 @@
+use serde::{Deserialize, Serialize};
+
 const RED: &[u8] = &[0, 0, 0, 0, 128, 255, 255, 255];
 const GREEN: &[u8] = &[0, 0, 128, 255, 128, 128, 255, 255];
 const BLUE: &[u8] = &[0, 255, 255, 128, 0, 0, 128, 255];
@@ -9,21 +11,93 @@ fn pack_argb(r: u8, g: u8, b: u8) -> u32 {
 }
 const COLOR_WIDTH: f64 = 50.;
 
-const BLACK: u32 = 0xff << 24;
+pub const BLACK: u32 = 0xff << 24;
+
+/// Segment logic used to walk the palette.
+#[repr(u32)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum ColormapMode {
+    /// Cycles over all segments, truncating interpolated channels.
+    #[default]
+    Cyclic = 0,
+    /// Matches `ClassicColormapProvider` in C#: the first segment is a
+    /// non-repeating ramp, the rest cycle over `MAP_LEN - 1` segments and
+    /// channels are rounded half to even.
+    Classic = 1,
+}
+
+enum_names!(ColormapMode {
+    Cyclic => "cyclic",
+    Classic => "classic",
+});
+
+#[inline]
+pub fn get_color(iteration: u32, max_iteration: u32, mode: ColormapMode) -> u32 {
+    get_color_with(iteration, max_iteration, mode, COLOR_WIDTH)
+}
 
+/// `color_width` is the number of iterations per palette segment.
 #[inline]
-pub fn get_color(iteration: u32, max_iteration: u32) -> u32 {
+pub fn get_color_with(
+    iteration: u32,
+    max_iteration: u32,
+    mode: ColormapMode,
+    color_width: f64,
+) -> u32 {
     if max_iteration == 0 || iteration >= max_iteration {
         return BLACK;
     }
 
-    let mut f_iteration: f64 = iteration as f64;
+    match mode {
+        ColormapMode::Cyclic => palette_color(iteration as f64, color_width),
+        ColormapMode::Classic => classic_color(iteration as f64, color_width),
+    }
+}
+
+/// Same as [`get_color`] for continuous iteration values.
+#[inline]
+pub fn get_value_color(value: f64, max_iteration: u32, mode: ColormapMode) -> u32 {
+    if max_iteration == 0 || value.is_nan() || value >= max_iteration as f64 {
+        return BLACK;
+    }
+
+    let value = value.max(0.);
+    match mode {
+        ColormapMode::Cyclic => palette_color(value, COLOR_WIDTH),
+        ColormapMode::Classic => classic_color(value, COLOR_WIDTH),
+    }
+}
+
+/// Fills `lut[i]` with the color of iteration `i` for `max_iteration`.
+/// Entries at or past `max_iteration` are black.
+pub fn fill_lut(lut: &mut [u32], max_iteration: u32, mode: ColormapMode, color_width: f64) {
+    for (i, out) in lut.iter_mut().enumerate() {
+        let iteration = u32::try_from(i).unwrap_or(u32::MAX);
+        *out = get_color_with(iteration, max_iteration, mode, color_width);
+    }
+}
+
+/// Maps a cumulative rank in `0..=1` onto one pass over the palette.
+#[inline]
+pub fn get_rank_color(rank: f64) -> u32 {
+    palette_color(
+        rank.clamp(0., 1.) * COLOR_WIDTH * (MAP_LEN - 1) as f64,
+        COLOR_WIDTH,
+    )
+}
+
+/// continuous palette lookup, one palette unit per iteration
+#[inline]
+fn palette_color(value: f64, color_width: f64) -> u32 {
+    let mut f_iteration: f64 = value;
     let mut start = 0usize;
-    while f_iteration >= COLOR_WIDTH {
-        f_iteration -= COLOR_WIDTH;
+    while f_iteration >= color_width {
+        f_iteration -= color_width;
         start += 1;
     }
-    let t = f_iteration / COLOR_WIDTH;
+    let t = f_iteration / color_width;
 
     let end = (start + 1) % MAP_LEN;
     let start = start % MAP_LEN;
@@ -34,6 +108,49 @@ pub fn get_color(iteration: u32, max_iteration: u32) -> u32 {
     )
 }
 
+/// the "non-FLAT_CMAP" branch of the original C code, see `ClassicColormapProvider`
+#[inline]
+fn classic_color(value: f64, color_width: f64) -> u32 {
+    let lerp = rounded_interpolation;
+
+    // first ramp segment, never repeated
+    if value < color_width {
+        let t = value / color_width;
+        return pack_argb(
+            lerp(RED[0], RED[1], t),
+            lerp(GREEN[0], GREEN[1], t),
+            lerp(BLUE[0], BLUE[1], t),
+        );
+    }
+
+    // cyclic segments
+    let val = value - color_width;
+    let whole = (val / color_width) as i64;
+    let start = (whole % (MAP_LEN as i64 - 1)) as usize + 1;
+    let t = (val - color_width * whole as f64) / color_width;
+
+    let end = if start + 1 >= MAP_LEN { 1 } else { start + 1 };
+    pack_argb(
+        lerp(RED[start], RED[end], t),
+        lerp(GREEN[start], GREEN[end], t),
+        lerp(BLUE[start], BLUE[end], t),
+    )
+}
+
+/// same as [`clamped_interpolation`] but rounds like C#'s `Math.Round`
+#[inline]
+fn rounded_interpolation(lower: u8, higher: u8, percentage: f64) -> u8 {
+    if percentage <= 0. {
+        return lower;
+    }
+    if percentage >= 1. {
+        return higher;
+    }
+
+    let t = (lower as f64 + (higher as f64 - lower as f64) * percentage).round_ties_even() as i64;
+    t.clamp(0, 255) as u8
+}
+
 #[inline]
 fn clamped_interpolation(lower: u8, higher: u8, percentage: f64) -> u8 {
     if percentage <= 0. {
@@ -46,3 +163,72 @@ fn clamped_interpolation(lower: u8, higher: u8, percentage: f64) -> u8 {
     let t = (lower as f64 + (higher as f64 - lower as f64) * percentage) as u32;
     t.clamp(0, 255) as u8
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `ClassicColormapProvider.GetColor(i, 1000)` of mandelbrot/Render/ColorProvider.cs.
+    const CLASSIC: [(usize, u32); 12] = [
+        (0, 0xff000000),
+        // 255 * 0.5 = 127.5, Math.Round ties to even
+        (25, 0xff000080),
+        (49, 0xff0000fa),
+        (50, 0xff0000ff),
+        (75, 0xff0040ff),
+        // 191.5 in green
+        (175, 0xff40c040),
+        // 191.5 in red
+        (225, 0xffc08000),
+        // 191.5 in blue
+        (325, 0xffffffc0),
+        (350, 0xffffffff),
+        // the last segment wraps to segment 1, not back to black; 127.5 in red and green
+        (375, 0xff8080ff),
+        (400, 0xff0000ff),
+        (425, 0xff0040ff),
+    ];
+
+    #[test]
+    fn classic_lut_matches_the_csharp_provider() {
+        let mut lut = vec![0; 1001];
+        fill_lut(&mut lut, 1000, ColormapMode::Classic, COLOR_WIDTH);
+        for (iteration, color) in CLASSIC {
+            assert_eq!(lut[iteration], color, "iteration {iteration}");
+        }
+        assert_eq!(lut[1000], BLACK);
+    }
+
+    #[test]
+    fn classic_lut_matches_the_csharp_provider_for_narrow_segments() {
+        // `new ClassicColormapProvider(3).GetColor(i, 1000)`
+        let expected = [
+            0xff000000, 0xff000055, 0xff0000aa, 0xff0000ff, 0xff002bff, 0xff0055ff, 0xff0080ff,
+            0xff00aad5, 0xff00d5aa, 0xff00ff80, 0xff2bd555, 0xff55aa2b,
+        ];
+        let mut lut = [0; 12];
+        fill_lut(&mut lut, 1000, ColormapMode::Classic, 3.);
+        assert_eq!(lut, expected);
+    }
+
+    #[test]
+    fn cyclic_truncates_and_repeats_the_first_segment() {
+        assert_eq!(get_color(25, 1000, ColormapMode::Cyclic), 0xff00007f);
+        let period = MAP_LEN as u32 * COLOR_WIDTH as u32;
+        assert_eq!(get_color(period, 1000, ColormapMode::Cyclic), BLACK);
+        assert_eq!(
+            get_color(period + 25, 1000, ColormapMode::Cyclic),
+            get_color(25, 1000, ColormapMode::Cyclic)
+        );
+    }
+
+    #[test]
+    fn interior_points_are_black() {
+        for mode in [ColormapMode::Cyclic, ColormapMode::Classic] {
+            assert_eq!(get_color(100, 100, mode), BLACK);
+            assert_eq!(get_color(5, 0, mode), BLACK);
+            assert_ne!(get_value_color(99.999, 100, mode), BLACK);
+            assert_eq!(get_value_color(f64::NAN, 100, mode), BLACK);
+        }
+    }
+}
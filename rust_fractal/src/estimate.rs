@@ -0,0 +1,221 @@
+//! Heuristics for picking `max_iter` from the zoom depth and frame content.
+
+use crate::kernel::Kernel;
+use crate::render::Viewport;
+
+/// Never suggest less than this, shallow views still need some detail.
+pub const MIN_ITERATIONS: u32 = 64;
+/// Cap for the adaptive search so a view full of interior points terminates.
+pub const MAX_ITERATIONS: u32 = 1 << 20;
+
+const ITERATIONS_PER_DECADE: f64 = 100.;
+/// Longest side of the preview grid used by [`adaptive_max_iterations`].
+const PREVIEW_SIDE: usize = 64;
+
+/// Suggests `max_iter` from the scale alone (`zoom` = pixels per world unit).
+/// Grows like `log10(zoom)^1.5`, which keeps the default view around a few
+/// hundred iterations and reaches thousands at deep zoom.
+pub fn suggest_max_iterations(zoom: u64) -> u32 {
+    let depth = (zoom.max(1) as f64).log10().max(1.);
+    let iterations = ITERATIONS_PER_DECADE * depth.powf(1.5);
+    (iterations as u32).clamp(MIN_ITERATIONS, MAX_ITERATIONS)
+}
+
+/// Orbits returning this close to an earlier point are taken as periodic.
+const PERIOD_EPSILON: f64 = 1e-10;
+
+/// Preview sample that can resume iterating where it stopped.
+#[derive(Clone, Copy)]
+struct Sample {
+    x0: f64,
+    y0: f64,
+    x: f64,
+    y: f64,
+    /// orbit point saved for the periodicity check, replaced at powers of two
+    saved: (f64, f64),
+    save_at: u32,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Outcome {
+    Escaped,
+    /// the orbit is periodic, the point never escapes
+    Interior,
+    ReachedLimit,
+}
+
+impl Sample {
+    /// Continues iterating from iteration `from` up to `max_iter`.
+    #[inline]
+    fn advance(&mut self, from: u32, max_iter: u32, kernel: &Kernel) -> Outcome {
+        for i in from..max_iter {
+            (self.x, self.y) = kernel.step(self.x, self.y, self.x0, self.y0);
+            if kernel.bailout.escaped(self.x, self.y) {
+                return Outcome::Escaped;
+            }
+            if (self.x - self.saved.0).abs() < PERIOD_EPSILON
+                && (self.y - self.saved.1).abs() < PERIOD_EPSILON
+            {
+                return Outcome::Interior;
+            }
+            if i == self.save_at {
+                self.saved = (self.x, self.y);
+                self.save_at = self.save_at.saturating_mul(2);
+            }
+        }
+        Outcome::ReachedLimit
+    }
+}
+
+/// Starts from `start_iter` and doubles the limit while more than
+/// `max_iter_fraction` of a cheap preview grid reaches it without escaping.
+///
+/// Points inside the set reach any limit. Samples whose orbits turn out to
+/// be periodic are interior for good, they stop iterating and count neither
+/// way, so a view that is mostly inside the set does not run into
+/// [`MAX_ITERATIONS`]. Interior orbits near a component boundary converge
+/// slowly and count as reaching the limit until their period shows, which
+/// can add a few doublings. Only the formula and bailout of `kernel` are
+/// used, not its `max_iter`.
+pub fn adaptive_max_iterations(
+    view: &Viewport,
+    start_iter: u32,
+    max_iter_fraction: f64,
+    kernel: &Kernel,
+) -> u32 {
+    let mut max_iter = start_iter.clamp(MIN_ITERATIONS, MAX_ITERATIONS);
+    let mut pending = preview_samples(view);
+    let total = pending.len();
+
+    let mut from = 0;
+    let mut interior = 0;
+    loop {
+        pending.retain_mut(|s| match s.advance(from, max_iter, kernel) {
+            Outcome::Escaped => false,
+            Outcome::Interior => {
+                interior += 1;
+                false
+            }
+            Outcome::ReachedLimit => true,
+        });
+        let candidates = total - interior;
+        if max_iter >= MAX_ITERATIONS
+            || pending.len() as f64 <= max_iter_fraction * candidates as f64
+        {
+            return max_iter;
+        }
+        from = max_iter;
+        max_iter = max_iter.saturating_mul(2).min(MAX_ITERATIONS);
+    }
+}
+
+/// The viewport sampled on a coarse grid spanning the same world area.
+fn preview_samples(view: &Viewport) -> Vec<Sample> {
+    let spacing = (view.width.max(view.height) as f64 / PREVIEW_SIDE as f64).max(1.);
+    let cols = ((view.width as f64 / spacing) as usize).max(1);
+    let rows = ((view.height as f64 / spacing) as usize).max(1);
+
+    (0..rows)
+        .flat_map(|r| (0..cols).map(move |c| (r, c)))
+        .map(|(r, c)| {
+            let (x0, y0) = view.world((c as f64 + 0.5) * spacing, (r as f64 + 0.5) * spacing);
+            Sample {
+                x0,
+                y0,
+                x: 0.,
+                y: 0.,
+                saved: (0., 0.),
+                save_at: 1,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kernel() -> Kernel {
+        Kernel::default()
+    }
+
+    /// Fraction of the preview grid reaching `max_iter`.
+    fn at_limit(view: &Viewport, max_iter: u32) -> f64 {
+        let kernel = Kernel {
+            max_iter,
+            ..kernel()
+        };
+        let samples = preview_samples(view);
+        let inside = samples
+            .iter()
+            .filter(|s| kernel.iterate(s.x0, s.y0) >= max_iter)
+            .count();
+        inside as f64 / samples.len() as f64
+    }
+
+    #[test]
+    fn suggestions_grow_with_depth() {
+        assert_eq!(suggest_max_iterations(0), 100);
+        assert_eq!(suggest_max_iterations(300), 389);
+        assert!(suggest_max_iterations(1 << 40) > 1000);
+        assert!(suggest_max_iterations(u64::MAX) <= MAX_ITERATIONS);
+    }
+
+    #[test]
+    fn raises_the_limit_until_few_samples_reach_it() {
+        // on the boundary in the seahorse valley, about half the view is interior
+        let view = Viewport::new(-0.743643887, 0.131825904, 100_000_000, 320, 240).unwrap();
+        let fraction = 0.1;
+        let interior = at_limit(&view, 1 << 16);
+        let escaping_later = |max_iter| (at_limit(&view, max_iter) - interior) / (1. - interior);
+        // the smallest limit meeting the criterion on the exact interior
+        let needed = (6..16)
+            .map(|bits| 1u32 << bits)
+            .find(|&max_iter| escaping_later(max_iter) <= fraction)
+            .unwrap();
+        assert!(needed > 1000);
+
+        let max_iter = adaptive_max_iterations(&view, MIN_ITERATIONS, fraction, &kernel());
+        assert!(max_iter >= needed, "{max_iter} < {needed}");
+        // interior orbits near the boundary take a while to show their period
+        assert!(max_iter <= 16 * needed, "{max_iter} > 16 * {needed}");
+    }
+
+    #[test]
+    fn periodic_samples_do_not_raise_the_limit() {
+        // inside the main cardioid, every sample reaches every limit
+        let view = Viewport::new(-0.2, 0., 10_000, 64, 64).unwrap();
+        assert_eq!(at_limit(&view, 1000), 1.);
+        assert_eq!(adaptive_max_iterations(&view, 200, 0.1, &kernel()), 200);
+    }
+
+    #[test]
+    fn keeps_the_start_when_enough_samples_escape() {
+        let view = Viewport::new(-0.5, 0., 300, 640, 480).unwrap();
+        let max_iter = adaptive_max_iterations(&view, 256, 0.5, &kernel());
+        assert_eq!(max_iter, 256);
+        assert!(at_limit(&view, 256) <= 0.5);
+    }
+
+    #[test]
+    fn clamps_the_start() {
+        let outside = Viewport::new(3., 3., 1000, 16, 16).unwrap();
+        assert_eq!(
+            adaptive_max_iterations(&outside, 1, 0.1, &kernel()),
+            MIN_ITERATIONS
+        );
+        assert_eq!(
+            adaptive_max_iterations(&outside, u32::MAX, 0.1, &kernel()),
+            MAX_ITERATIONS
+        );
+        // the exported function clamps invalid views and negative starts the same way
+        assert_eq!(
+            crate::adaptive_max_iterations(0., 0., 300, 0, 16, -500, 0.1),
+            MIN_ITERATIONS as i32
+        );
+        assert_eq!(
+            crate::adaptive_max_iterations(0., 0., 300, 16, 16, 100, 0.1),
+            100
+        );
+    }
+}
@@ -0,0 +1,297 @@
+//! Exponential map (log-polar) strips for zoom videos.
+//!
+//! A strip samples the plane around a zoom center on circles whose radius
+//! shrinks by the same factor from row to row: column `c` is the angle
+//! `c / width` of a full turn, row `r` the radius `radius * exp(-r * 2π / width)`,
+//! so samples are square. One strip of iteration values covers every zoom
+//! between its outer radius and its innermost row, and any frame of a zoom
+//! into the center is resampled from it instead of being iterated again.
+//!
+//! A strip `width` of about `2π` times the frame's half diagonal in pixels
+//! matches the frame's resolution at its corners and oversamples everything
+//! closer to the center. Frame pixels outside the strip are iterated directly.
+
+use std::f64::consts::TAU;
+
+use rayon::prelude::*;
+
+use crate::pixel::PixelTarget;
+use crate::render::{RenderParams, Viewport, colorize_into};
+
+#[derive(Clone, Copy, Debug)]
+pub struct ExpMap {
+    pub center_x: f64,
+    pub center_y: f64,
+    /// world radius of row 0
+    pub radius: f64,
+    /// samples per full turn
+    pub width: usize,
+    pub rows: usize,
+}
+
+impl ExpMap {
+    /// `None` for empty sizes or a non-positive radius.
+    pub fn new(
+        center_x: f64,
+        center_y: f64,
+        radius: f64,
+        width: usize,
+        rows: usize,
+    ) -> Option<Self> {
+        if width == 0 || rows == 0 || !(radius.is_finite() && radius > 0.) {
+            return None;
+        }
+        width.checked_mul(rows)?;
+        Some(Self {
+            center_x,
+            center_y,
+            radius,
+            width,
+            rows,
+        })
+    }
+
+    /// Strip around a center for frames from `zoom_from` to `zoom_to` pixels
+    /// per world unit whose pixels lie within `reach_px` pixels of the center,
+    /// e.g. the half diagonal when the frames are centered on it.
+    pub fn covering(
+        center_x: f64,
+        center_y: f64,
+        reach_px: f64,
+        zoom_from: f64,
+        zoom_to: f64,
+    ) -> Option<Self> {
+        let width = (TAU * reach_px).ceil().max(1.);
+        let (zoom_min, zoom_max) = (zoom_from.min(zoom_to), zoom_from.max(zoom_to));
+        let radius = reach_px / zoom_min;
+        // down to half a pixel of the deepest frame
+        let rows = rows_between(radius, 0.5 / zoom_max, width as usize)?;
+        Self::new(center_x, center_y, radius, width as usize, rows)
+    }
+
+    pub fn sample_count(&self) -> usize {
+        self.width * self.rows
+    }
+
+    /// Log radius step between rows.
+    fn step(&self) -> f64 {
+        TAU / self.width as f64
+    }
+
+    /// World offset from the center of a (fractional) sample position.
+    #[inline]
+    fn offset(&self, column: f64, row: f64) -> (f64, f64) {
+        let radius = self.radius * (-row * self.step()).exp();
+        let (sin, cos) = (column * self.step()).sin_cos();
+        (radius * cos, radius * sin)
+    }
+
+    /// Fractional sample position of a world offset from the center, `None`
+    /// outside the strip.
+    #[inline]
+    fn position(&self, dx: f64, dy: f64) -> Option<(f64, f64)> {
+        let radius = dx.hypot(dy);
+        let row = (self.radius / radius).ln() / self.step();
+        if !(0. ..=(self.rows - 1) as f64).contains(&row) {
+            return None;
+        }
+        let column = dy.atan2(dx).rem_euclid(TAU) / self.step();
+        Some((column, row))
+    }
+}
+
+/// Rows needed to reach from `radius_max` down to `radius_min`.
+pub fn rows_between(radius_max: f64, radius_min: f64, width: usize) -> Option<usize> {
+    let rows = (radius_max / radius_min).ln() * width as f64 / TAU;
+    (rows.is_finite() && rows >= 0.).then(|| rows.ceil() as usize + 1)
+}
+
+/// Fills `values` (at least `map.sample_count()` long, row-major, row 0 at
+/// the outer radius) with iteration values as [`crate::render::iterate_values`] does.
+pub fn render_expmap(map: &ExpMap, params: &RenderParams, values: &mut [f32]) {
+    let kernel = params.kernel();
+    let smooth = params.coloring.is_smooth();
+
+    values[..map.sample_count()]
+        .par_chunks_mut(map.width)
+        .enumerate()
+        .for_each(|(row, out_row)| {
+            for (column, out) in out_row.iter_mut().enumerate() {
+                let (dx, dy) = map.offset(column as f64, row as f64);
+                let (x, y) = (map.center_x + dx, map.center_y + dy);
+                *out = kernel.iterate_value(x, y, smooth);
+            }
+        });
+}
+
+/// Resamples a frame from a strip and colors it with `params`.
+pub fn reconstruct_into(
+    map: &ExpMap,
+    values: &[f32],
+    view: &Viewport,
+    params: &RenderParams,
+    target: &mut PixelTarget,
+) {
+    let mut frame = vec![0f32; view.pixel_count()];
+    reconstruct_values(map, values, view, params, &mut frame);
+    colorize_into(&frame, params, target);
+}
+
+/// Resamples the iteration values of a frame (`frame` at least
+/// `view.pixel_count()` long) from a strip: bilinear between samples, wrapping
+/// around the angle, nearest next to interior points so the set's edge stays
+/// sharp. Pixels outside the strip are iterated with `params`.
+pub fn reconstruct_values(
+    map: &ExpMap,
+    values: &[f32],
+    view: &Viewport,
+    params: &RenderParams,
+    frame: &mut [f32],
+) {
+    let values = &values[..map.sample_count()];
+    let kernel = params.kernel();
+    let smooth = params.coloring.is_smooth();
+    let max_iter = params.max_iter() as f32;
+    // view center relative to the strip center
+    let (shift_x, shift_y) = (view.center_x - map.center_x, view.center_y - map.center_y);
+
+    frame[..view.pixel_count()]
+        .par_chunks_mut(view.width)
+        .enumerate()
+        .for_each(|(py, out_row)| {
+            for (px, out) in out_row.iter_mut().enumerate() {
+                let (dx, dy) = view.offset(px as f64, py as f64);
+                *out = match map.position(dx + shift_x, dy + shift_y) {
+                    Some((column, row)) => sample(map, values, column, row, max_iter),
+                    None => {
+                        let (x, y) = view.world(px as f64, py as f64);
+                        kernel.iterate_value(x, y, smooth)
+                    }
+                };
+            }
+        });
+}
+
+#[inline]
+fn sample(map: &ExpMap, values: &[f32], column: f64, row: f64, max_iter: f32) -> f32 {
+    let (c0, r0) = (column.floor(), row.floor());
+    let (fc, fr) = ((column - c0) as f32, (row - r0) as f32);
+    let c0 = c0 as usize % map.width;
+    let c1 = (c0 + 1) % map.width;
+    let r0 = r0 as usize;
+    let r1 = (r0 + 1).min(map.rows - 1);
+    let at = |c: usize, r: usize| values[r * map.width + c];
+    let corners = [at(c0, r0), at(c1, r0), at(c0, r1), at(c1, r1)];
+
+    if corners.iter().any(|&v| v >= max_iter) {
+        let c = if fc < 0.5 { 0 } else { 1 };
+        let r = if fr < 0.5 { 0 } else { 2 };
+        return corners[r + c];
+    }
+    let top = corners[0] + (corners[1] - corners[0]) * fc;
+    let bottom = corners[2] + (corners[3] - corners[2]) * fc;
+    top + (bottom - top) * fr
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::{Coloring, iterate_values};
+
+    #[test]
+    fn rows_between_reaches_the_inner_radius() {
+        // one row per e^(2π / width)
+        assert_eq!(rows_between(TAU.exp(), 1., 1), Some(2));
+        assert_eq!(rows_between(1., 1., 8), Some(1));
+        assert_eq!(rows_between(1., 2., 8), None);
+        assert_eq!(rows_between(1., 0., 8), None);
+
+        for (radius_max, radius_min, width) in [(2., 1e-3, 100), (1e-5, 1e-12, 1297)] {
+            let rows = rows_between(radius_max, radius_min, width).unwrap();
+            let map = ExpMap::new(0., 0., radius_max, width, rows).unwrap();
+            let radius = |row: usize| map.offset(0., row as f64).0;
+            assert!(radius(rows - 1) <= radius_min);
+            assert!(radius(rows - 2) > radius_min);
+        }
+    }
+
+    #[test]
+    fn covering_spans_every_frame() {
+        let map = ExpMap::covering(-0.5, 0.25, 400., 1e6, 100.).unwrap();
+        assert_eq!(map.width, (TAU * 400.).ceil() as usize);
+        // the shallowest frame's corners lie on row 0
+        assert_eq!(map.radius, 400. / 100.);
+        // the deepest frame is covered down to half a pixel
+        let inner = map.offset(0., (map.rows - 1) as f64).0;
+        assert!(inner <= 0.5 / 1e6);
+        assert!(map.offset(0., (map.rows - 2) as f64).0 > 0.5 / 1e6);
+
+        let reversed = ExpMap::covering(-0.5, 0.25, 400., 100., 1e6).unwrap();
+        assert_eq!((reversed.radius, reversed.rows), (map.radius, map.rows));
+        assert!(ExpMap::covering(0., 0., 400., 0., 1e6).is_none());
+    }
+
+    #[test]
+    fn reconstructed_frames_match_direct_renders() {
+        let params = RenderParams {
+            max_iter: 500,
+            coloring: Coloring::Smooth,
+            ..RenderParams::default()
+        };
+        let (cx, cy) = (-0.743643887, 0.131825904);
+        let (width, height) = (96, 64);
+        let reach = (width as f64).hypot(height as f64) / 2.;
+        let map = ExpMap::covering(cx, cy, reach, 2_000., 200_000.).unwrap();
+        let mut values = vec![0f32; map.sample_count()];
+        render_expmap(&map, &params, &mut values);
+
+        let render = |view: &Viewport| {
+            let mut direct = vec![0f32; view.pixel_count()];
+            iterate_values(view, &params, &mut direct);
+            let mut frame = vec![0f32; view.pixel_count()];
+            reconstruct_values(&map, &values, view, &params, &mut frame);
+            (direct, frame)
+        };
+
+        for zoom in [2_000, 30_000, 200_000] {
+            let view = Viewport::new(cx, cy, zoom, width, height).unwrap();
+            let (direct, frame) = render(&view);
+            // The strip samples lie within a pixel of every frame pixel, so
+            // resampled values stay within the range of the neighbouring
+            // direct ones except on filaments thinner than a pixel. A direct
+            // render shifted by half a pixel misses more than 5% here.
+            let at = |x: usize, y: usize| direct[y * width + x];
+            let mut outside = 0;
+            for y in 1..height - 1 {
+                for x in 1..width - 1 {
+                    let around =
+                        (y - 1..=y + 1).flat_map(|y| (x - 1..=x + 1).map(move |x| at(x, y)));
+                    let lo = around.clone().fold(f32::MAX, f32::min);
+                    let hi = around.fold(f32::MIN, f32::max);
+                    let value = frame[y * width + x];
+                    if value < lo - 0.5 || value > hi + 0.5 {
+                        outside += 1;
+                    }
+                }
+            }
+            let inner = (width - 2) * (height - 2);
+            assert!(outside * 20 < inner, "zoom {zoom}: {outside} of {inner}");
+        }
+
+        // pixels beyond the outer radius are iterated exactly
+        let view = Viewport::new(cx + 48. / 2_000., cy, 2_000, width, height).unwrap();
+        let (direct, frame) = render(&view);
+        let mut beyond = 0;
+        for (i, (a, b)) in direct.iter().zip(&frame).enumerate() {
+            let (dx, dy) = view.offset((i % width) as f64, (i / width) as f64);
+            if map
+                .position(dx + view.center_x - cx, dy + view.center_y - cy)
+                .is_none()
+            {
+                assert_eq!(a, b, "pixel {i}");
+                beyond += 1;
+            }
+        }
+        assert!(beyond > 0);
+    }
+}
@@ -0,0 +1,281 @@
+//! Image file export.
+//!
+//! PNGs carry the view they show in `tEXt` chunks (keywords prefixed with
+//! `fractal.`), so an exported image can be reopened at the same location.
+
+use std::fmt;
+use std::io::{BufRead, Seek, Write};
+
+use crate::colormap::ColormapMode;
+use crate::kernel::{BailoutNorm, Formula};
+use crate::render::{Coloring, RenderParams};
+use crate::view::ViewDescription;
+
+const KEY_CENTER_X: &str = "fractal.center-x";
+const KEY_CENTER_Y: &str = "fractal.center-y";
+const KEY_ZOOM: &str = "fractal.zoom";
+const KEY_ROTATION: &str = "fractal.rotation";
+const KEY_FORMULA: &str = "fractal.formula";
+const KEY_MAX_ITER: &str = "fractal.max-iter";
+const KEY_ESCAPE_RADIUS: &str = "fractal.escape-radius";
+const KEY_BAILOUT: &str = "fractal.bailout";
+const KEY_PALETTE: &str = "fractal.palette";
+const KEY_COLORING: &str = "fractal.coloring";
+
+/// Writes `width * height` tightly packed `R, G, B, A` bytes as an 8 bit RGBA PNG.
+/// With a `view` its parameters are stored in `tEXt` chunks before the image data.
+pub fn write_png<W: Write>(
+    writer: W,
+    width: u32,
+    height: u32,
+    rgba: &[u8],
+    view: Option<&ViewDescription>,
+) -> Result<(), png::EncodingError> {
+    let mut writer = png_writer(writer, width, height, view)?;
+    writer.write_image_data(rgba)?;
+    writer.finish()
+}
+
+/// Header and text chunks of [`write_png`], ready for the image data.
+pub(crate) fn png_writer<W: Write>(
+    writer: W,
+    width: u32,
+    height: u32,
+    view: Option<&ViewDescription>,
+) -> Result<png::Writer<W>, png::EncodingError> {
+    let mut encoder = png::Encoder::new(writer, width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    if let Some(view) = view {
+        encoder.add_text_chunk("Software".to_string(), env!("CARGO_PKG_NAME").to_string())?;
+        for (key, value) in view_entries(view) {
+            encoder.add_text_chunk(key.to_string(), value)?;
+        }
+    }
+    encoder.write_header()
+}
+
+/// Reads the view stored by [`write_png`] without decoding the image data.
+pub fn read_png_view<R: BufRead + Seek>(reader: R) -> Result<ViewDescription, MetadataError> {
+    let reader = png::Decoder::new(reader).read_info()?;
+    let info = reader.info();
+    let text = |key: &str| {
+        info.uncompressed_latin1_text
+            .iter()
+            .find(|c| c.keyword == key)
+            .map(|c| c.text.as_str())
+    };
+    parse_view(text)
+}
+
+/// `(keyword, value)` pairs describing `view`, floats in shortest round-trip form.
+pub fn view_entries(view: &ViewDescription) -> Vec<(&'static str, String)> {
+    let p = &view.params;
+    vec![
+        (KEY_CENTER_X, view.center_x.to_string()),
+        (KEY_CENTER_Y, view.center_y.to_string()),
+        (KEY_ZOOM, view.zoom.to_string()),
+        (KEY_ROTATION, view.rotation.to_string()),
+        (KEY_FORMULA, p.formula.name().to_string()),
+        (KEY_MAX_ITER, p.max_iter.to_string()),
+        (KEY_ESCAPE_RADIUS, p.escape_radius.to_string()),
+        (KEY_BAILOUT, p.bailout.name().to_string()),
+        (KEY_PALETTE, p.colormap.name().to_string()),
+        (KEY_COLORING, p.coloring.name().to_string()),
+    ]
+}
+
+/// Inverse of [`view_entries`]. Center and zoom are required, missing render
+/// parameters fall back to their defaults.
+pub fn parse_view<'a>(
+    text: impl Fn(&str) -> Option<&'a str>,
+) -> Result<ViewDescription, MetadataError> {
+    fn parse<T>(
+        key: &'static str,
+        value: &str,
+        f: impl FnOnce(&str) -> Option<T>,
+    ) -> Result<T, MetadataError> {
+        f(value.trim()).ok_or_else(|| MetadataError::Invalid {
+            key,
+            value: value.to_string(),
+        })
+    }
+    let required = |key: &'static str| text(key).ok_or(MetadataError::Missing(key));
+
+    let defaults = RenderParams::default();
+
+    let params = RenderParams {
+        formula: match text(KEY_FORMULA) {
+            Some(v) => parse(KEY_FORMULA, v, Formula::from_name)?,
+            None => defaults.formula,
+        },
+        max_iter: match text(KEY_MAX_ITER) {
+            Some(v) => parse(KEY_MAX_ITER, v, |s| s.parse().ok())?,
+            None => defaults.max_iter,
+        },
+        escape_radius: match text(KEY_ESCAPE_RADIUS) {
+            Some(v) => parse(KEY_ESCAPE_RADIUS, v, |s| s.parse().ok())?,
+            None => defaults.escape_radius,
+        },
+        bailout: match text(KEY_BAILOUT) {
+            Some(v) => parse(KEY_BAILOUT, v, BailoutNorm::from_name)?,
+            None => defaults.bailout,
+        },
+        colormap: match text(KEY_PALETTE) {
+            Some(v) => parse(KEY_PALETTE, v, ColormapMode::from_name)?,
+            None => defaults.colormap,
+        },
+        coloring: match text(KEY_COLORING) {
+            Some(v) => parse(KEY_COLORING, v, Coloring::from_name)?,
+            None => defaults.coloring,
+        },
+    };
+
+    Ok(ViewDescription {
+        center_x: parse(KEY_CENTER_X, required(KEY_CENTER_X)?, |s| s.parse().ok())?,
+        center_y: parse(KEY_CENTER_Y, required(KEY_CENTER_Y)?, |s| s.parse().ok())?,
+        zoom: parse(KEY_ZOOM, required(KEY_ZOOM)?, |s| s.parse().ok())?,
+        rotation: match text(KEY_ROTATION) {
+            Some(v) => parse(KEY_ROTATION, v, |s| s.parse().ok())?,
+            None => 0.,
+        },
+        params,
+    })
+}
+
+#[derive(Debug)]
+pub enum MetadataError {
+    Png(png::DecodingError),
+    Missing(&'static str),
+    Invalid { key: &'static str, value: String },
+}
+
+impl fmt::Display for MetadataError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Png(e) => write!(f, "png decoding failed: {e}"),
+            Self::Missing(key) => write!(f, "missing '{key}'"),
+            Self::Invalid { key, value } => write!(f, "invalid value for '{key}': {value:?}"),
+        }
+    }
+}
+
+impl std::error::Error for MetadataError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Png(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<png::DecodingError> for MetadataError {
+    fn from(e: png::DecodingError) -> Self {
+        Self::Png(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::io::Cursor;
+
+    fn view() -> ViewDescription {
+        ViewDescription {
+            center_x: -0.743643887037151,
+            center_y: 0.1 + 0.2,
+            zoom: 123_456_789_012,
+            rotation: -12.5,
+            params: RenderParams {
+                formula: Formula::BurningShip,
+                max_iter: 4321,
+                escape_radius: 1e3,
+                bailout: BailoutNorm::ImagOnly,
+                colormap: ColormapMode::Classic,
+                coloring: Coloring::SmoothHistogram,
+            },
+        }
+    }
+
+    fn png(view: Option<&ViewDescription>) -> Vec<u8> {
+        let rgba: Vec<u8> = (0..2 * 3 * 4).map(|i| i as u8).collect();
+        let mut out = Vec::new();
+        write_png(&mut out, 2, 3, &rgba, view).unwrap();
+        out
+    }
+
+    fn parse_entries(entries: &HashMap<&str, String>) -> Result<ViewDescription, MetadataError> {
+        parse_view(|key| entries.get(key).map(String::as_str))
+    }
+
+    #[test]
+    fn view_survives_a_png_round_trip() {
+        let data = png(Some(&view()));
+        let read = read_png_view(Cursor::new(&data)).unwrap();
+        assert_eq!(format!("{read:?}"), format!("{:?}", view()));
+
+        // the image data is untouched by the text chunks
+        let mut decoder = png::Decoder::new(Cursor::new(&data)).read_info().unwrap();
+        let mut pixels = vec![0; decoder.output_buffer_size().unwrap()];
+        decoder.next_frame(&mut pixels).unwrap();
+        assert_eq!(pixels, (0..24).collect::<Vec<u8>>());
+    }
+
+    #[test]
+    fn missing_keys_are_reported() {
+        let err = read_png_view(Cursor::new(png(None))).unwrap_err();
+        assert!(matches!(err, MetadataError::Missing(KEY_CENTER_X)), "{err}");
+
+        let mut entries: HashMap<_, _> = view_entries(&view()).into_iter().collect();
+        entries.remove(KEY_ZOOM);
+        let err = parse_entries(&entries).unwrap_err();
+        assert!(matches!(err, MetadataError::Missing(KEY_ZOOM)), "{err}");
+    }
+
+    #[test]
+    fn render_parameters_default_when_missing() {
+        let entries: HashMap<_, _> = view_entries(&view())
+            .into_iter()
+            .filter(|(key, _)| [KEY_CENTER_X, KEY_CENTER_Y, KEY_ZOOM].contains(key))
+            .collect();
+        let read = parse_entries(&entries).unwrap();
+        assert_eq!(read.zoom, view().zoom);
+        assert_eq!(read.rotation, 0.);
+        assert_eq!(
+            format!("{:?}", read.params),
+            format!("{:?}", RenderParams::default())
+        );
+    }
+
+    #[test]
+    fn malformed_values_are_reported() {
+        for (key, value) in [
+            (KEY_CENTER_X, "left"),
+            (KEY_ZOOM, "-5"),
+            (KEY_ZOOM, "1.5"),
+            (KEY_ROTATION, ""),
+            (KEY_FORMULA, "julia"),
+            (KEY_MAX_ITER, "many"),
+            (KEY_ESCAPE_RADIUS, "2,5"),
+            (KEY_BAILOUT, "taxicab"),
+            (KEY_PALETTE, "rainbow"),
+            (KEY_COLORING, "flat"),
+        ] {
+            let mut entries: HashMap<_, _> = view_entries(&view()).into_iter().collect();
+            entries.insert(key, value.to_string());
+            match parse_entries(&entries) {
+                Err(MetadataError::Invalid { key: k, value: v }) => {
+                    assert_eq!((k, v.as_str()), (key, value));
+                }
+                other => panic!("{key}={value:?}: {other:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn other_files_are_png_errors() {
+        let err = read_png_view(Cursor::new(b"GIF89a".to_vec())).unwrap_err();
+        assert!(matches!(err, MetadataError::Png(_)), "{err}");
+    }
+}
@@ -0,0 +1,218 @@
+//! Histogram equalization of escape-time values.
+//!
+//! Every rayon worker fills its own [`Histogram`], the partial histograms are
+//! merged once the pass is done and turned into a cumulative distribution.
+//! Pixels are then colored by their rank within that distribution instead of
+//! by their raw iteration count.
+
+/// Upper bound for the number of bins so huge `max_iter` values do not
+/// allocate one bin per iteration for every worker.
+const MAX_BINS: u32 = 1 << 16;
+
+#[derive(Clone, Debug)]
+pub struct Histogram {
+    counts: Vec<u64>,
+    max_iter: u32,
+}
+
+impl Histogram {
+    pub fn new(max_iter: u32) -> Self {
+        Self {
+            counts: vec![0; max_iter.min(MAX_BINS) as usize],
+            max_iter,
+        }
+    }
+
+    /// Records an integer or smooth iteration value.
+    /// Values `>= max_iter` are inside the set and are not counted.
+    #[inline]
+    pub fn add(&mut self, value: f64) {
+        if let Some(bin) = self.bin(value) {
+            self.counts[bin] += 1;
+        }
+    }
+
+    pub fn merge(mut self, other: Self) -> Self {
+        for (a, b) in self.counts.iter_mut().zip(other.counts) {
+            *a += b;
+        }
+        self
+    }
+
+    pub fn into_cumulative(self) -> CumulativeHistogram {
+        let total: u64 = self.counts.iter().sum();
+        let mut sum = 0u64;
+        let fractions = self
+            .counts
+            .into_iter()
+            .map(|c| {
+                sum += c;
+                if total == 0 {
+                    0.
+                } else {
+                    sum as f64 / total as f64
+                }
+            })
+            .collect();
+        CumulativeHistogram {
+            fractions,
+            max_iter: self.max_iter,
+        }
+    }
+
+    #[inline]
+    fn bin(&self, value: f64) -> Option<usize> {
+        bin_position(value, self.max_iter, self.counts.len()).map(|(bin, _)| bin)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct CumulativeHistogram {
+    /// fraction of escaped pixels up to and including each bin
+    fractions: Vec<f64>,
+    max_iter: u32,
+}
+
+impl CumulativeHistogram {
+    /// Fraction of escaped pixels that escaped before `value`, in `0..=1`.
+    /// Smooth values interpolate between the neighbouring bins.
+    #[inline]
+    pub fn rank(&self, value: f64) -> f64 {
+        let Some((bin, frac)) = bin_position(value, self.max_iter, self.fractions.len()) else {
+            return 1.;
+        };
+        let below = if bin == 0 {
+            0.
+        } else {
+            self.fractions[bin - 1]
+        };
+        below + (self.fractions[bin] - below) * frac
+    }
+
+    /// Mixes `previous` into this distribution, `keep` in `0..=1` being its
+    /// weight. Animations chain this from frame to frame so the equalization
+    /// follows the view gradually instead of flickering.
+    pub fn blend(&self, previous: &Self, keep: f64) -> Self {
+        let keep = keep.clamp(0., 1.);
+        let bins = self.fractions.len();
+        let fractions = self
+            .fractions
+            .iter()
+            .enumerate()
+            .map(|(bin, &fraction)| {
+                // upper edge of the bin
+                let value = (bin + 1) as f64 * self.max_iter as f64 / bins as f64;
+                fraction * (1. - keep) + previous.rank(value) * keep
+            })
+            .collect();
+        Self {
+            fractions,
+            max_iter: self.max_iter,
+        }
+    }
+}
+
+/// Bin index and fractional position inside it, `None` for interior values.
+#[inline]
+fn bin_position(value: f64, max_iter: u32, bins: usize) -> Option<(usize, f64)> {
+    if bins == 0 || value.is_nan() || value >= max_iter as f64 {
+        return None;
+    }
+    let pos = value.max(0.) * bins as f64 / max_iter as f64;
+    let bin = (pos as usize).min(bins - 1);
+    Some((bin, (pos - bin as f64).clamp(0., 1.)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::frame_histogram;
+
+    fn histogram(max_iter: u32, values: &[f64]) -> Histogram {
+        let mut hist = Histogram::new(max_iter);
+        for &value in values {
+            hist.add(value);
+        }
+        hist
+    }
+
+    #[test]
+    fn merging_worker_histograms_matches_one_pass() {
+        let values = [0., 1., 1., 3., 7., 7., 7., 9.5];
+        let whole = histogram(10, &values).into_cumulative();
+        let (left, right) = values.split_at(3);
+        let merged = histogram(10, left)
+            .merge(histogram(10, right))
+            .into_cumulative();
+        assert_eq!(merged.fractions, whole.fractions);
+        assert_eq!(whole.fractions[1], 3. / 8.);
+        assert_eq!(whole.fractions[9], 1.);
+    }
+
+    #[test]
+    fn frame_histogram_merges_rows_of_every_worker() {
+        let values: Vec<f32> = (0..64 * 64).map(|i| (i % 50) as f32).collect();
+        let frame = frame_histogram(&values, 64, 100);
+        let single =
+            histogram(100, &values.iter().map(|&v| v as f64).collect::<Vec<_>>()).into_cumulative();
+        assert_eq!(frame.fractions, single.fractions);
+    }
+
+    #[test]
+    fn interior_points_are_not_counted() {
+        let hist = histogram(10, &[2., 10., 10., 11., f64::NAN]);
+        assert_eq!(hist.counts.iter().sum::<u64>(), 1);
+        let cumulative = hist.into_cumulative();
+        assert_eq!(cumulative.rank(10.), 1.);
+        assert_eq!(cumulative.rank(1.), 0.);
+        assert_eq!(cumulative.rank(3.), 1.);
+    }
+
+    #[test]
+    fn rank_is_monotonic() {
+        let values: Vec<f64> = (0..500).map(|i| ((i * 37) % 200) as f64 * 0.47).collect();
+        let cumulative = histogram(100, &values).into_cumulative();
+        let mut previous = 0.;
+        for step in 0..=1000 {
+            let rank = cumulative.rank(step as f64 * 0.1);
+            assert!((previous..=1.).contains(&rank), "{rank} after {previous}");
+            previous = rank;
+        }
+        assert_eq!(previous, 1.);
+    }
+
+    #[test]
+    fn bins_are_capped_for_huge_iteration_limits() {
+        let max_iter = 10_000_000;
+        let hist = histogram(max_iter, &[0., (max_iter - 1) as f64]);
+        assert_eq!(hist.counts.len(), MAX_BINS as usize);
+        assert_eq!(hist.counts[0], 1);
+        assert_eq!(hist.counts[MAX_BINS as usize - 1], 1);
+        assert_eq!(
+            bin_position(-5., max_iter, MAX_BINS as usize),
+            Some((0, 0.))
+        );
+    }
+
+    #[test]
+    fn smooth_values_interpolate_within_a_bin() {
+        // half the pixels in bin 2, half in bin 3
+        let cumulative = histogram(10, &[2., 3.]).into_cumulative();
+        assert_eq!(cumulative.rank(2.), 0.);
+        assert_eq!(cumulative.rank(2.25), 0.125);
+        assert_eq!(cumulative.rank(2.5), 0.25);
+        assert_eq!(cumulative.rank(3.), 0.5);
+        assert_eq!(cumulative.rank(3.5), 0.75);
+    }
+
+    #[test]
+    fn blending_moves_towards_the_previous_distribution() {
+        let current = histogram(10, &[1.]).into_cumulative();
+        let previous = histogram(10, &[5.]).into_cumulative();
+        assert_eq!(current.blend(&previous, 0.).fractions, current.fractions);
+        assert_eq!(current.blend(&previous, 1.).fractions, previous.fractions);
+        let half = current.blend(&previous, 0.5);
+        assert_eq!(half.rank(2.), 0.5);
+        assert_eq!(half.rank(6.), 1.);
+    }
+}
@@ -0,0 +1,501 @@
+//! Importers for location files of other fractal programs.
+//!
+//! Both formats describe the zoom relative to the image height, so the
+//! importers need the height of the output in pixels to compute this crate's
+//! pixels-per-unit zoom. Centers keep every digit of the source file.
+
+use std::fmt;
+
+use crate::colormap::ColormapMode;
+use crate::kernel::{BailoutNorm, DEFAULT_ESCAPE_RADIUS, Formula};
+use crate::location::{
+    Center, ColoringSettings, Decimal, FORMAT_VERSION, IterationSettings, Location,
+};
+use crate::render::Coloring;
+
+/// Kalles Fraktaler `.kfr`: `Key: value` lines.
+///
+/// `Zoom` is a magnification where 1 shows a radius of 2 around the center,
+/// measured over half the image height. `FractalType` 0, 1 and 4 with
+/// `Power: 2` map to Mandelbrot, Burning Ship and Tricorn; `Smooth: 1` selects
+/// smooth coloring. The KF palette (`Colors`) is not imported.
+pub fn parse_kfr(text: &str, height_px: u32) -> Result<Location, ImportError> {
+    let value = |key: &'static str| {
+        text.lines()
+            .filter_map(|line| line.split_once(':'))
+            .find(|(k, _)| k.trim().eq_ignore_ascii_case(key))
+            .map(|(_, v)| v.trim())
+    };
+    let required = |key: &'static str| value(key).ok_or(ImportError::Missing(key));
+
+    let fractal_type = match value("FractalType") {
+        Some(v) => parse_number::<u32>("FractalType", v)?,
+        None => 0,
+    };
+    let power = match value("Power") {
+        Some(v) => parse_number::<u32>("Power", v)?,
+        None => 2,
+    };
+    let formula = match (fractal_type, power) {
+        (0, 2) => Formula::Mandelbrot,
+        (1, 2) => Formula::BurningShip,
+        (4, 2) => Formula::Tricorn,
+        (t, p) => {
+            return Err(ImportError::UnsupportedFormula(format!(
+                "FractalType {t} with Power {p}"
+            )));
+        }
+    };
+
+    let magnification = parse_number::<f64>("Zoom", required("Zoom")?)?;
+    // radius 2 / magnification spans half the image height
+    let zoom = magnification * height_px as f64 / 4.;
+
+    let smooth = value("Smooth").is_some_and(|v| v == "1");
+
+    Ok(Location {
+        version: FORMAT_VERSION,
+        formula,
+        center: Center {
+            re: parse_decimal("Re", required("Re")?)?,
+            im: parse_decimal("Im", required("Im")?)?,
+        },
+        zoom: Decimal::from_f64(zoom),
+        rotation: match value("Rotate") {
+            Some(v) => parse_number("Rotate", v)?,
+            None => 0.,
+        },
+        iteration: IterationSettings {
+            max_iter: parse_number("Iterations", required("Iterations")?)?,
+            ..IterationSettings::default()
+        },
+        coloring: ColoringSettings {
+            palette: ColormapMode::default(),
+            method: if smooth {
+                Coloring::Smooth
+            } else {
+                Coloring::EscapeTime
+            },
+        },
+    })
+}
+
+/// Fractint `.par`: any number of `name { key=value ... }` entries,
+/// `;` starts a comment and a trailing `\` continues a line. Returns every
+/// entry in file order, each with its own result so one unsupported entry
+/// does not hide the others.
+///
+/// Supports `type=mandel`/`mandelfp` with zero `params`, `center-mag` or
+/// `corners`, `maxiter`, `bailout` and `bailoutest` (`mod`, `real`, `imag`,
+/// `manh`). Fractint's magnification shows `2 / mag` world units over the
+/// image height. Views stretched by an `Xmagfactor` other than 1 or skewed
+/// are rejected, pixels here are square. Colors are not imported.
+pub fn parse_par(text: &str, height_px: u32) -> Result<Vec<ParEntry>, ImportError> {
+    let mut stripped = String::new();
+    for line in text.lines() {
+        let code = line.split_once(';').map_or(line, |(code, _)| code);
+        // a continued value goes on at the first non-blank of the next line
+        match code.trim_end().strip_suffix('\\') {
+            Some(continued) => stripped.push_str(continued.trim_start()),
+            None => {
+                stripped.push_str(code.trim_start());
+                stripped.push('\n');
+            }
+        }
+    }
+
+    let mut entries = Vec::new();
+    let mut rest = stripped.as_str();
+    while let Some(open) = rest.find('{') {
+        let name = rest[..open].trim().to_string();
+        let close = rest[open..].find('}').ok_or(ImportError::Invalid {
+            key: "entry",
+            value: format!("'{name}' has no closing brace"),
+        })? + open;
+        let location =
+            parse_par_entry(&rest[open + 1..close], height_px).map_err(|e| e.in_entry(&name));
+        entries.push(ParEntry { name, location });
+        rest = &rest[close + 1..];
+    }
+
+    if entries.is_empty() {
+        return Err(ImportError::Missing("par entry"));
+    }
+    Ok(entries)
+}
+
+/// One named entry of a `.par` file.
+#[derive(Debug)]
+pub struct ParEntry {
+    pub name: String,
+    pub location: Result<Location, ImportError>,
+}
+
+fn parse_par_entry(body: &str, height_px: u32) -> Result<Location, ImportError> {
+    let pairs: Vec<(&str, &str)> = body
+        .split_whitespace()
+        .filter_map(|token| token.split_once('='))
+        .collect();
+    let value = |key: &str| {
+        pairs
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(key))
+            .map(|(_, v)| *v)
+    };
+
+    let formula = match value("type").unwrap_or("mandel") {
+        t if t.eq_ignore_ascii_case("mandel") || t.eq_ignore_ascii_case("mandelfp") => {
+            Formula::Mandelbrot
+        }
+        t => return Err(ImportError::UnsupportedFormula(format!("type={t}"))),
+    };
+    if let Some(params) = value("params")
+        && params
+            .split('/')
+            .any(|p| !p.parse::<f64>().is_ok_and(|v| v == 0.))
+    {
+        return Err(ImportError::UnsupportedFormula(format!(
+            "mandel with params={params}"
+        )));
+    }
+
+    let (re, im, zoom, rotation) = if let Some(center_mag) = value("center-mag") {
+        let parts: Vec<&str> = center_mag.split('/').collect();
+        let [re, im, mag, rest @ ..] = parts.as_slice() else {
+            return Err(ImportError::Invalid {
+                key: "center-mag",
+                value: center_mag.to_string(),
+            });
+        };
+        let mag = parse_number::<f64>("center-mag", mag)?;
+        let [x_mag_factor, rotation, skew] =
+            [(rest.first(), 1.), (rest.get(1), 0.), (rest.get(2), 0.)].map(|(part, default)| {
+                part.map_or(Ok(default), |p| parse_number::<f64>("center-mag", p))
+            });
+        let (x_mag_factor, rotation, skew) = (x_mag_factor?, rotation?, skew?);
+        if x_mag_factor != 1. || skew != 0. || rest.len() > 3 {
+            // stretched or sheared views need non-square pixels
+            return Err(ImportError::Unsupported(format!("center-mag={center_mag}")));
+        }
+        (
+            parse_decimal("center-mag", re)?,
+            parse_decimal("center-mag", im)?,
+            mag * height_px as f64 / 2.,
+            rotation,
+        )
+    } else if let Some(corners) = value("corners") {
+        let parts: Vec<f64> = corners
+            .split('/')
+            .map(|p| parse_number("corners", p))
+            .collect::<Result<_, _>>()?;
+        let [xmin, xmax, ymin, ymax] = parts[..] else {
+            // rotated/skewed corners have a third corner pair
+            return Err(ImportError::Unsupported(format!("corners={corners}")));
+        };
+        (
+            Decimal::from_f64((xmin + xmax) / 2.),
+            Decimal::from_f64((ymin + ymax) / 2.),
+            height_px as f64 / (ymax - ymin).abs(),
+            0.,
+        )
+    } else {
+        return Err(ImportError::Missing("center-mag or corners"));
+    };
+
+    let bailout_value = match value("bailout") {
+        Some(v) => parse_number::<f64>("bailout", v)?,
+        None => DEFAULT_ESCAPE_RADIUS * DEFAULT_ESCAPE_RADIUS,
+    };
+    let bailout = match value("bailoutest").unwrap_or("mod") {
+        "mod" => BailoutNorm::Euclidean,
+        "real" => BailoutNorm::RealOnly,
+        "imag" => BailoutNorm::ImagOnly,
+        "manh" => BailoutNorm::Manhattan,
+        other => return Err(ImportError::Unsupported(format!("bailoutest={other}"))),
+    };
+
+    Ok(Location {
+        version: FORMAT_VERSION,
+        formula,
+        center: Center { re, im },
+        zoom: Decimal::from_f64(zoom),
+        rotation,
+        iteration: IterationSettings {
+            max_iter: match value("maxiter") {
+                Some(v) => parse_number("maxiter", v)?,
+                None => 150,
+            },
+            // fractint compares squared values against bailout
+            escape_radius: bailout_value.sqrt(),
+            bailout,
+        },
+        coloring: ColoringSettings::default(),
+    })
+}
+
+fn parse_number<T: std::str::FromStr>(key: &'static str, value: &str) -> Result<T, ImportError> {
+    value.trim().parse().map_err(|_| ImportError::Invalid {
+        key,
+        value: value.to_string(),
+    })
+}
+
+fn parse_decimal(key: &'static str, value: &str) -> Result<Decimal, ImportError> {
+    Decimal::try_from(value.to_string()).map_err(|_| ImportError::Invalid {
+        key,
+        value: value.to_string(),
+    })
+}
+
+#[derive(Debug)]
+pub enum ImportError {
+    Missing(&'static str),
+    Invalid {
+        key: &'static str,
+        value: String,
+    },
+    UnsupportedFormula(String),
+    Unsupported(String),
+    InEntry {
+        entry: String,
+        source: Box<ImportError>,
+    },
+}
+
+impl ImportError {
+    fn in_entry(self, entry: &str) -> Self {
+        Self::InEntry {
+            entry: entry.to_string(),
+            source: Box::new(self),
+        }
+    }
+}
+
+impl fmt::Display for ImportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Missing(key) => write!(f, "missing {key}"),
+            Self::Invalid { key, value } => write!(f, "invalid value for {key}: {value:?}"),
+            Self::UnsupportedFormula(formula) => write!(f, "unsupported formula: {formula}"),
+            Self::Unsupported(what) => write!(f, "unsupported setting {what}"),
+            Self::InEntry { entry, source } => write!(f, "entry '{entry}': {source}"),
+        }
+    }
+}
+
+impl std::error::Error for ImportError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Trimmed from a file saved by Kalles Fraktaler 2.
+    const KFR: &str = "\
+Re: -0.743643887037158704752191506114774
+Im: 0.131825904205311970493132056385139
+Zoom: 2.5E7
+Iterations: 12000
+IterDiv: 1.000000
+SmoothMethod: 0
+ColorMethod: 7
+ColorOffset: 0
+Rotate: 15.000000
+Ratio: 360.000000
+Colors: 255,255,255,128,0,64,160,0,0,192,128,0,
+InteriorColor: 0,0,0,
+Smooth: 1
+MultiColor: 0
+FractalType: 1
+Power: 2
+";
+
+    /// A Fractint 20 entry with a continued `center-mag` and a second,
+    /// unsupported entry.
+    const PAR: &str = "\
+Seahorse           { ; valley between the bulbs
+                     ; saved by Fractint
+  reset=2004 type=mandel center-mag=-0.74364388703715870475/\\
+  0.13182590420531197049/4000/1/30/0 params=0/0 float=y
+  maxiter=1500 bailout=16 bailoutest=manh inside=0
+  colors=000fff<3>zzz
+  }
+
+Julia_Dragon       {
+  reset=2004 type=julia center-mag=0/0/0.8 params=-0.8/0.156
+  }
+";
+
+    #[test]
+    fn parses_a_kfr_file() {
+        let location = parse_kfr(KFR, 1000).unwrap();
+        assert_eq!(location.formula, Formula::BurningShip);
+        assert_eq!(
+            location.center.re.as_str(),
+            "-0.743643887037158704752191506114774"
+        );
+        assert_eq!(
+            location.center.im.as_str(),
+            "0.131825904205311970493132056385139"
+        );
+        // radius 2 / 2.5e7 over half of 1000 pixels
+        assert_eq!(location.zoom.to_f64(), 2.5e7 * 1000. / 4.);
+        assert_eq!(location.rotation, 15.);
+        assert_eq!(location.iteration.max_iter, 12000);
+        assert_eq!(location.coloring.method, Coloring::Smooth);
+        assert!(location.to_view().is_ok());
+    }
+
+    #[test]
+    fn kfr_defaults_to_mandelbrot_with_escape_time_coloring() {
+        let text = "Re: 0\r\nIm: 0\r\nZoom: 1\r\nIterations: 200\r\n";
+        let location = parse_kfr(text, 400).unwrap();
+        assert_eq!(location.formula, Formula::Mandelbrot);
+        assert_eq!(location.rotation, 0.);
+        assert_eq!(location.coloring.method, Coloring::EscapeTime);
+        assert_eq!(location.zoom.to_f64(), 100.);
+    }
+
+    #[test]
+    fn rejects_malformed_kfr_files() {
+        let without = |key: &str| {
+            KFR.lines()
+                .filter(|line| !line.starts_with(&format!("{key}:")))
+                .collect::<Vec<_>>()
+                .join("\n")
+        };
+        for key in ["Re", "Im", "Zoom", "Iterations"] {
+            assert!(
+                matches!(parse_kfr(&without(key), 1000), Err(ImportError::Missing(k)) if k == key),
+                "{key}"
+            );
+        }
+        for (from, to, key) in [
+            ("Zoom: 2.5E7", "Zoom: lots", "Zoom"),
+            ("Iterations: 12000", "Iterations: -1", "Iterations"),
+            ("Re: -0.7436", "Re: 0x7436", "Re"),
+            ("Rotate: 15.000000", "Rotate: left", "Rotate"),
+        ] {
+            assert!(
+                matches!(
+                    parse_kfr(&KFR.replace(from, to), 1000),
+                    Err(ImportError::Invalid { key: k, .. }) if k == key
+                ),
+                "{to}"
+            );
+        }
+        for (from, to) in [
+            ("FractalType: 1", "FractalType: 2"),
+            ("Power: 2", "Power: 3"),
+        ] {
+            assert!(matches!(
+                parse_kfr(&KFR.replace(from, to), 1000),
+                Err(ImportError::UnsupportedFormula(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn parses_par_entries_in_file_order() {
+        let entries = parse_par(PAR, 1000).unwrap();
+        let names: Vec<_> = entries.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, ["Seahorse", "Julia_Dragon"]);
+
+        let location = entries[0].location.as_ref().unwrap();
+        assert_eq!(location.formula, Formula::Mandelbrot);
+        assert_eq!(location.center.re.as_str(), "-0.74364388703715870475");
+        assert_eq!(location.center.im.as_str(), "0.13182590420531197049");
+        // 2 / 4000 world units over 1000 pixels
+        assert_eq!(location.zoom.to_f64(), 2_000_000.);
+        assert_eq!(location.rotation, 30.);
+        assert_eq!(location.iteration.max_iter, 1500);
+        assert_eq!(location.iteration.escape_radius, 4.);
+        assert_eq!(location.iteration.bailout, BailoutNorm::Manhattan);
+
+        match &entries[1].location {
+            Err(ImportError::InEntry { entry, source }) => {
+                assert_eq!(entry, "Julia_Dragon");
+                assert!(matches!(**source, ImportError::UnsupportedFormula(_)));
+            }
+            other => panic!("expected an unsupported formula, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn par_corners_and_defaults() {
+        let entry = "Whole { corners=-2.5/1.5/-1.5/1.5 }";
+        let location = parse_par(entry, 600).unwrap().remove(0).location.unwrap();
+        assert_eq!(location.center.re.to_f64(), -0.5);
+        assert_eq!(location.center.im.to_f64(), 0.);
+        assert_eq!(location.zoom.to_f64(), 200.);
+        assert_eq!(location.iteration.max_iter, 150);
+        assert_eq!(location.iteration.escape_radius, DEFAULT_ESCAPE_RADIUS);
+        assert_eq!(location.iteration.bailout, BailoutNorm::Euclidean);
+    }
+
+    #[test]
+    fn rejects_par_views_with_non_square_pixels() {
+        for view in [
+            "center-mag=0/0/1/2/0/0",
+            "center-mag=0/0/1/1/0/10",
+            "center-mag=0/0/1/1/0/0/5",
+            "corners=-2/1/-1.5/1.5/-2/-1.5",
+        ] {
+            let text = format!("Stretched {{ type=mandel {view} }}");
+            let location = parse_par(&text, 600).unwrap().remove(0).location;
+            match location {
+                Err(ImportError::InEntry { source, .. }) => {
+                    assert!(matches!(*source, ImportError::Unsupported(_)), "{view}")
+                }
+                other => panic!("{view}: expected an unsupported view, got {other:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_par_files() {
+        assert!(matches!(
+            parse_par("; just a comment\n", 600),
+            Err(ImportError::Missing("par entry"))
+        ));
+        assert!(matches!(
+            parse_par("Open { type=mandel center-mag=0/0/1", 600),
+            Err(ImportError::Invalid { key: "entry", .. })
+        ));
+
+        let inner = |body: &str| match parse_par(&format!("E {{ {body} }}"), 600)
+            .unwrap()
+            .remove(0)
+            .location
+        {
+            Err(ImportError::InEntry { source, .. }) => *source,
+            other => panic!("{body}: expected an error, got {other:?}"),
+        };
+        assert!(matches!(inner("type=mandel"), ImportError::Missing(_)));
+        assert!(matches!(
+            inner("center-mag=0/0"),
+            ImportError::Invalid {
+                key: "center-mag",
+                ..
+            }
+        ));
+        assert!(matches!(
+            inner("center-mag=0/zero/1"),
+            ImportError::Invalid {
+                key: "center-mag",
+                ..
+            }
+        ));
+        assert!(matches!(
+            inner("center-mag=0/0/1 maxiter=many"),
+            ImportError::Invalid { key: "maxiter", .. }
+        ));
+        assert!(matches!(
+            inner("center-mag=0/0/1 bailoutest=and"),
+            ImportError::Unsupported(_)
+        ));
+        assert!(matches!(
+            inner("center-mag=0/0/1 params=0.3/0"),
+            ImportError::UnsupportedFormula(_)
+        ));
+    }
+}
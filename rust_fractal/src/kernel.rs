@@ -0,0 +1,222 @@
+//! The escape-time iteration shared by every renderer.
+
+use std::f64::consts::LN_2;
+
+use serde::{Deserialize, Serialize};
+
+/// How `z` is measured against the escape radius.
+///
+/// Only `Euclidean` with a radius of at least 2 yields the exact Mandelbrot
+/// set; the other norms move the band boundaries for artistic effects.
+/// `RealOnly` and `ImagOnly` ignore one axis, so points that diverge along
+/// the other one may reach `max_iter` and are drawn as interior.
+#[repr(u32)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum BailoutNorm {
+    /// `re^2 + im^2 > radius^2`, the classic `|z| > radius` test.
+    #[default]
+    Euclidean = 0,
+    /// `|re| + |im| > radius`.
+    Manhattan = 1,
+    /// `max(|re|, |im|) > radius`.
+    Max = 2,
+    /// `|re| > radius`, the imaginary part is ignored.
+    RealOnly = 3,
+    /// `|im| > radius`, the real part is ignored.
+    ImagOnly = 4,
+}
+
+enum_names!(BailoutNorm {
+    Euclidean => "euclidean",
+    Manhattan => "manhattan",
+    Max => "max",
+    RealOnly => "real-only",
+    ImagOnly => "imag-only",
+});
+
+/// Default escape radius, `|z| > 2` is the smallest radius that is exact for
+/// the Mandelbrot set.
+pub const DEFAULT_ESCAPE_RADIUS: f64 = 2.0;
+
+/// Precomputed bailout test.
+#[derive(Clone, Copy, Debug)]
+pub struct Bailout {
+    norm: BailoutNorm,
+    radius: f64,
+    radius_sqr: f64,
+    ln_radius: f64,
+}
+
+impl Default for Bailout {
+    fn default() -> Self {
+        Self::new(BailoutNorm::Euclidean, DEFAULT_ESCAPE_RADIUS)
+    }
+}
+
+impl Bailout {
+    /// Non-finite or non-positive radii fall back to [`DEFAULT_ESCAPE_RADIUS`].
+    pub fn new(norm: BailoutNorm, radius: f64) -> Self {
+        let radius = if radius.is_finite() && radius > 0. {
+            radius
+        } else {
+            DEFAULT_ESCAPE_RADIUS
+        };
+        Self {
+            norm,
+            radius,
+            radius_sqr: radius * radius,
+            ln_radius: radius.ln(),
+        }
+    }
+
+    #[inline]
+    pub fn escaped(&self, x: f64, y: f64) -> bool {
+        match self.norm {
+            BailoutNorm::Euclidean => (x * x + y * y) > self.radius_sqr,
+            BailoutNorm::Manhattan => (x.abs() + y.abs()) > self.radius,
+            BailoutNorm::Max => x.abs().max(y.abs()) > self.radius,
+            BailoutNorm::RealOnly => x.abs() > self.radius,
+            BailoutNorm::ImagOnly => y.abs() > self.radius,
+        }
+    }
+}
+
+/// Iterated function `z -> f(z) + c`.
+#[repr(u32)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum Formula {
+    /// `z^2 + c`
+    #[default]
+    Mandelbrot = 0,
+    /// `conj(z)^2 + c`, also known as Mandelbar.
+    Tricorn = 1,
+    /// `(|re z| + i|im z|)^2 + c`, drawn with world Y up so the ship stands upside down.
+    BurningShip = 2,
+}
+
+enum_names!(Formula {
+    Mandelbrot => "mandelbrot",
+    Tricorn => "tricorn",
+    BurningShip => "burning-ship",
+});
+
+/// Formula, bailout and iteration limit of one render.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Kernel {
+    pub formula: Formula,
+    pub bailout: Bailout,
+    pub max_iter: u32,
+}
+
+impl Kernel {
+    /// One step of `z = f(z) + c`.
+    #[inline]
+    pub fn step(&self, x: f64, y: f64, x0: f64, y0: f64) -> (f64, f64) {
+        match self.formula {
+            Formula::Mandelbrot => (x * x - y * y + x0, 2.0 * x * y + y0),
+            Formula::Tricorn => (x * x - y * y + x0, -2.0 * x * y + y0),
+            Formula::BurningShip => (x * x - y * y + x0, 2.0 * (x * y).abs() + y0),
+        }
+    }
+
+    #[inline]
+    pub fn iterate(&self, x0: f64, y0: f64) -> u32 {
+        self.escape_time(x0, y0).0
+    }
+
+    /// Continuous iteration count `n + 1 - log2(ln|z| / ln(radius))`.
+    /// Escaped points stay below `max_iter`, interior points return `max_iter`.
+    #[inline]
+    pub fn iterate_smooth(&self, x0: f64, y0: f64) -> f64 {
+        self.smooth(self.escape_time(x0, y0))
+    }
+
+    /// [`Self::iterate_smooth`] from the result of [`Self::escape_time`].
+    #[inline]
+    pub fn smooth(&self, (i, norm_sqr): (u32, f64)) -> f64 {
+        let max_iter = self.max_iter;
+        if i >= max_iter {
+            return max_iter as f64;
+        }
+        let log_zn = 0.5 * norm_sqr.ln();
+        // ln(radius) is <= 0 for radii <= 1, fall back to the radius 2 constant
+        let ln_radius = if self.bailout.ln_radius > 0. {
+            self.bailout.ln_radius
+        } else {
+            LN_2
+        };
+        let nu = (log_zn / ln_radius).log2();
+        if !nu.is_finite() {
+            return i as f64;
+        }
+        (i as f64 + 1.0 - nu).clamp(0.0, (max_iter as f64).next_down())
+    }
+
+    /// [`Self::iterate_smooth`] or [`Self::iterate`] as stored in the `f32`
+    /// iteration buffers, see [`buffer_value`].
+    #[inline]
+    pub fn iterate_value(&self, x0: f64, y0: f64, smooth: bool) -> f32 {
+        self.buffer_value(self.escape_time(x0, y0), smooth)
+    }
+
+    /// [`Self::iterate_value`] from the result of [`Self::escape_time`].
+    #[inline]
+    pub fn buffer_value(&self, escape: (u32, f64), smooth: bool) -> f32 {
+        let value = if smooth {
+            self.smooth(escape)
+        } else {
+            escape.0 as f64
+        };
+        buffer_value(value, self.max_iter)
+    }
+
+    /// Iteration count and `|z|^2` at the point the loop stopped.
+    #[inline]
+    pub fn escape_time(&self, x0: f64, y0: f64) -> (u32, f64) {
+        let mut x = 0.0f64;
+        let mut y = 0.0f64;
+        let mut i = 0u32;
+
+        while i < self.max_iter {
+            (x, y) = self.step(x, y, x0, y0);
+
+            if self.bailout.escaped(x, y) {
+                break;
+            }
+            i += 1;
+        }
+        (i, x * x + y * y)
+    }
+}
+
+/// Narrows an iteration value to `f32`. Escaped values close to a large
+/// `max_iter` would round up to it and count as interior points, so they are
+/// clamped below it after rounding.
+#[inline]
+fn buffer_value(value: f64, max_iter: u32) -> f32 {
+    if value >= max_iter as f64 {
+        max_iter as f32
+    } else {
+        (value as f32).min((max_iter as f32).next_down())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escaped_values_stay_below_max_iter_in_f32() {
+        let max_iter = 100_000_000;
+        let escaped = (max_iter as f64).next_down();
+        assert_eq!(escaped as f32, max_iter as f32);
+        assert!(buffer_value(escaped, max_iter) < max_iter as f32);
+        assert!(buffer_value((max_iter - 1) as f64, max_iter) < max_iter as f32);
+        assert_eq!(buffer_value(max_iter as f64, max_iter), max_iter as f32);
+        assert_eq!(buffer_value(12.5, 256), 12.5);
+    }
+}
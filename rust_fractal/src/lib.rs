@@ -1,10 +1,54 @@
 // lib.rs, simple FFI code
 
-pub(crate) mod colormap;
+/// `name()` / `from_name()` with the kebab-case names shared by the CLI and
+/// the metadata written into exported files.
+macro_rules! enum_names {
+    ($ty:ident { $($variant:ident => $name:literal),+ $(,)? }) => {
+        impl $ty {
+            pub fn name(self) -> &'static str {
+                match self {
+                    $(Self::$variant => $name,)+
+                }
+            }
+
+            pub fn from_name(name: &str) -> Option<Self> {
+                match name {
+                    $($name => Some(Self::$variant),)+
+                    _ => None,
+                }
+            }
+        }
+    };
+}
 
-use rayon::prelude::*;
+pub mod animation;
+pub mod colormap;
+pub mod estimate;
+pub mod expmap;
+pub mod export;
+pub mod histogram;
+pub mod import;
+pub mod kernel;
+pub mod location;
+pub mod perturbation;
+pub mod pixel;
+pub mod poster;
+pub mod render;
+pub mod resample;
+pub mod tiles;
+pub mod view;
 
-use crate::colormap::get_color;
+use std::sync::LazyLock;
+
+use crate::colormap::ColormapMode;
+use crate::expmap::ExpMap;
+use crate::kernel::Kernel;
+use crate::location::{Location, LocationFormat};
+use crate::pixel::{PixelFormat, PixelTarget};
+use crate::render::{Coloring, RenderParams, Viewport, render_argb};
+use crate::resample::Resampling;
+use crate::tiles::{SharedTileCache, TileKey};
+use crate::view::ViewDescription;
 #[unsafe(no_mangle)]
 pub extern "C" fn test() -> u32 {
     6
@@ -25,74 +69,940 @@ pub unsafe extern "C" fn mandelbrot_baseline_render_u32(
     dst: *mut u32,
     dst_len: usize,
 ) {
+    let params = RenderParams {
+        max_iter,
+        ..RenderParams::default()
+    };
+    // SAFETY: forwarded caller guarantees.
+    unsafe {
+        mandelbrot_render_u32(
+            center_x, center_y, zoom, width_px, height_px, params, dst, dst_len,
+        )
+    }
+}
+
+/// Same as `mandelbrot_baseline_render_u32` with a selectable colormap.
+/// `ColormapMode.Classic` produces the same image as the C# baseline renderer.
+///
+/// # Safety
+///
+/// len must be width * height
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn mandelbrot_colormap_render_u32(
+    center_x: f32,
+    center_y: f32,
+    zoom: u64,
+    width_px: i32,
+    height_px: i32,
+    max_iter: i32,
+    colormap: ColormapMode,
+    dst: *mut u32,
+    dst_len: usize,
+) {
+    let params = RenderParams {
+        max_iter,
+        colormap,
+        ..RenderParams::default()
+    };
+    // SAFETY: forwarded caller guarantees.
+    unsafe {
+        mandelbrot_render_u32(
+            center_x, center_y, zoom, width_px, height_px, params, dst, dst_len,
+        )
+    }
+}
+
+/// Default render parameters: 256 iterations, `|z| > 2`, cyclic colormap,
+/// integer escape-time coloring.
+#[unsafe(no_mangle)]
+pub extern "C" fn render_params_default() -> RenderParams {
+    RenderParams::default()
+}
+
+/// Renders with every parameter of `RenderParams`, see its fields for the
+/// exact bailout semantics.
+///
+/// # Safety
+///
+/// len must be width * height
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn mandelbrot_render_u32(
+    center_x: f32,
+    center_y: f32,
+    zoom: u64,
+    width_px: i32,
+    height_px: i32,
+    params: RenderParams,
+    dst: *mut u32,
+    dst_len: usize,
+) {
+    let Some(view) = viewport(center_x, center_y, zoom, width_px, height_px) else {
+        return;
+    };
+    // SAFETY: forwarded caller guarantees.
+    let Some(pixels) = (unsafe { output_buffer(dst, dst_len, view.pixel_count()) }) else {
+        return;
+    };
+    render_argb(&view, &params, pixels);
+}
+
+/// Renders into a caller-owned buffer of any `PixelFormat`, e.g. a locked
+/// platform bitmap. Rows are `stride_bytes` apart; `dst_len` is in bytes and
+/// must cover `stride_bytes * (height - 1) + width * bytes_per_pixel`.
+///
+/// # Safety
+///
+/// dst must point to dst_len valid bytes
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn mandelbrot_render_into(
+    center_x: f32,
+    center_y: f32,
+    zoom: u64,
+    width_px: i32,
+    height_px: i32,
+    params: RenderParams,
+    format: PixelFormat,
+    dst: *mut u8,
+    dst_len: usize,
+    stride_bytes: usize,
+) {
+    let Some(view) = viewport(center_x, center_y, zoom, width_px, height_px) else {
+        return;
+    };
+    // SAFETY: forwarded caller guarantees.
+    let Some(data) = (unsafe { output_buffer(dst, dst_len, dst_len) }) else {
+        return;
+    };
+    let Some(mut target) = PixelTarget::new(data, view.width, view.height, stride_bytes, format)
+    else {
+        return;
+    };
+    render::render_into(&view, &params, &mut target);
+}
+
+/// Renders with histogram-equalized coloring.
+///
+/// The escape-time distribution of the whole frame is collected first and
+/// every pixel is colored by its cumulative rank, so clustered iteration
+/// counts at high `max_iter` still span the palette.
+/// `smooth` switches from integer to continuous iteration counts.
+///
+/// # Safety
+///
+/// len must be width * height
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn mandelbrot_histogram_render_u32(
+    center_x: f32,
+    center_y: f32,
+    zoom: u64,
+    width_px: i32,
+    height_px: i32,
+    max_iter: i32,
+    smooth: bool,
+    dst: *mut u32,
+    dst_len: usize,
+) {
+    let params = RenderParams {
+        max_iter,
+        coloring: if smooth {
+            Coloring::SmoothHistogram
+        } else {
+            Coloring::Histogram
+        },
+        ..RenderParams::default()
+    };
+    // SAFETY: forwarded caller guarantees.
+    unsafe {
+        mandelbrot_render_u32(
+            center_x, center_y, zoom, width_px, height_px, params, dst, dst_len,
+        )
+    }
+}
+
+/// Writes raw iteration values instead of colors, `width * height` floats.
+/// Smooth colorings produce continuous values, interior points hold `max_iter`.
+/// Feed the buffer to `colorize_into` to turn it into pixels.
+///
+/// # Safety
+///
+/// len must be width * height
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn mandelbrot_iterations_f32(
+    center_x: f32,
+    center_y: f32,
+    zoom: u64,
+    width_px: i32,
+    height_px: i32,
+    params: RenderParams,
+    dst: *mut f32,
+    dst_len: usize,
+) {
+    let Some(view) = viewport(center_x, center_y, zoom, width_px, height_px) else {
+        return;
+    };
+    // SAFETY: forwarded caller guarantees.
+    let Some(values) = (unsafe { output_buffer(dst, dst_len, view.pixel_count()) }) else {
+        return;
+    };
+    render::iterate_values(&view, &params, values);
+}
+
+/// Updates the iteration values of `mandelbrot_iterations_f32` after a pan
+/// instead of recomputing them. `dst` holds the previous view at `prev_zoom`;
+/// the content moved `dx_px` pixels right and `dy_px` down, and `center_x`,
+/// `center_y`, `zoom` describe the new view. The reusable region is shifted in
+/// place and only the exposed strips are iterated. A different zoom or a
+/// shift as large as the view renders everything. Returns the number of
+/// pixels iterated.
+///
+/// # Safety
+///
+/// dst must point to dst_len valid floats, at least width * height
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn mandelbrot_scroll_iterations_f32(
+    center_x: f64,
+    center_y: f64,
+    zoom: u64,
+    width_px: i32,
+    height_px: i32,
+    params: RenderParams,
+    prev_zoom: u64,
+    dx_px: i32,
+    dy_px: i32,
+    dst: *mut f32,
+    dst_len: usize,
+) -> usize {
     if width_px <= 0 || height_px <= 0 {
+        return 0;
+    }
+    let Some(view) = Viewport::new(
+        center_x,
+        center_y,
+        zoom,
+        width_px as usize,
+        height_px as usize,
+    ) else {
+        return 0;
+    };
+    // SAFETY: forwarded caller guarantees.
+    let Some(values) = (unsafe { output_buffer(dst, dst_len, view.pixel_count()) }) else {
+        return 0;
+    };
+    if zoom != prev_zoom {
+        render::iterate_values(&view, &params, values);
+        return values.len();
+    }
+    render::scroll_values(&view, &params, values, dx_px as isize, dy_px as isize)
+}
+
+/// Instant frame for a zoom gesture: resamples the previous ARGB frame `src`
+/// (view `prev_center_*`, `prev_zoom`) into `dst` (view `center_*`, `zoom`),
+/// both `width * height` pixels. Follow up with `zoom_refine_u32`.
+///
+/// # Safety
+///
+/// src and dst must each point to len valid u32s, at least width * height,
+/// and must not overlap
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn zoom_preview_u32(
+    prev_center_x: f64,
+    prev_center_y: f64,
+    prev_zoom: u64,
+    center_x: f64,
+    center_y: f64,
+    zoom: u64,
+    width_px: i32,
+    height_px: i32,
+    mode: Resampling,
+    src: *const u32,
+    dst: *mut u32,
+    len: usize,
+) {
+    let views = (
+        prev_center_x,
+        prev_center_y,
+        prev_zoom,
+        center_x,
+        center_y,
+        zoom,
+    );
+    // SAFETY: forwarded caller guarantees.
+    let Some((from, to, src, dst)) =
+        (unsafe { zoom_buffers(views, width_px, height_px, src, dst, len) })
+    else {
+        return;
+    };
+    resample::preview_argb(&from, src, &to, mode, dst);
+}
+
+/// Final frame after `zoom_preview_u32`: pixels that fall exactly on a pixel
+/// of the previous frame come from `src`, the rest are iterated, so the result
+/// matches a fresh render. Returns the number of pixels iterated.
+///
+/// # Safety
+///
+/// src and dst must each point to len valid u32s, at least width * height,
+/// and must not overlap
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn zoom_refine_u32(
+    prev_center_x: f64,
+    prev_center_y: f64,
+    prev_zoom: u64,
+    center_x: f64,
+    center_y: f64,
+    zoom: u64,
+    width_px: i32,
+    height_px: i32,
+    params: RenderParams,
+    src: *const u32,
+    dst: *mut u32,
+    len: usize,
+) -> usize {
+    let views = (
+        prev_center_x,
+        prev_center_y,
+        prev_zoom,
+        center_x,
+        center_y,
+        zoom,
+    );
+    // SAFETY: forwarded caller guarantees.
+    let Some((from, to, src, dst)) =
+        (unsafe { zoom_buffers(views, width_px, height_px, src, dst, len) })
+    else {
+        return 0;
+    };
+    resample::refine_argb(&from, src, &to, &params, dst)
+}
+
+/// `zoom_preview_u32` for iteration values of `mandelbrot_iterations_f32`.
+///
+/// # Safety
+///
+/// src and dst must each point to len valid floats, at least width * height,
+/// and must not overlap
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn zoom_preview_f32(
+    prev_center_x: f64,
+    prev_center_y: f64,
+    prev_zoom: u64,
+    center_x: f64,
+    center_y: f64,
+    zoom: u64,
+    width_px: i32,
+    height_px: i32,
+    mode: Resampling,
+    max_iter: i32,
+    src: *const f32,
+    dst: *mut f32,
+    len: usize,
+) {
+    let views = (
+        prev_center_x,
+        prev_center_y,
+        prev_zoom,
+        center_x,
+        center_y,
+        zoom,
+    );
+    // SAFETY: forwarded caller guarantees.
+    let Some((from, to, src, dst)) =
+        (unsafe { zoom_buffers(views, width_px, height_px, src, dst, len) })
+    else {
+        return;
+    };
+    resample::preview_values(&from, src, &to, mode, max_iter.unsigned_abs(), dst);
+}
+
+/// `zoom_refine_u32` for iteration values of `mandelbrot_iterations_f32`.
+///
+/// # Safety
+///
+/// src and dst must each point to len valid floats, at least width * height,
+/// and must not overlap
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn zoom_refine_f32(
+    prev_center_x: f64,
+    prev_center_y: f64,
+    prev_zoom: u64,
+    center_x: f64,
+    center_y: f64,
+    zoom: u64,
+    width_px: i32,
+    height_px: i32,
+    params: RenderParams,
+    src: *const f32,
+    dst: *mut f32,
+    len: usize,
+) -> usize {
+    let views = (
+        prev_center_x,
+        prev_center_y,
+        prev_zoom,
+        center_x,
+        center_y,
+        zoom,
+    );
+    // SAFETY: forwarded caller guarantees.
+    let Some((from, to, src, dst)) =
+        (unsafe { zoom_buffers(views, width_px, height_px, src, dst, len) })
+    else {
+        return 0;
+    };
+    resample::refine_values(&from, src, &to, &params, dst)
+}
+
+/// Colors an iteration buffer from `mandelbrot_iterations_f32` into any
+/// `PixelFormat`, see `mandelbrot_render_into` for the layout of `dst`.
+///
+/// # Safety
+///
+/// values must point to width * height floats, dst to dst_len valid bytes
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn colorize_into(
+    values: *const f32,
+    width_px: i32,
+    height_px: i32,
+    params: RenderParams,
+    format: PixelFormat,
+    dst: *mut u8,
+    dst_len: usize,
+    stride_bytes: usize,
+) {
+    if width_px <= 0 || height_px <= 0 || values.is_null() {
         return;
     }
-    if zoom == 0 {
+    let (width, height) = (width_px as usize, height_px as usize);
+    let Some(len) = width.checked_mul(height) else {
+        return;
+    };
+    // SAFETY: caller guarantees values points to width * height floats.
+    let values = unsafe { std::slice::from_raw_parts(values, len) };
+    // SAFETY: forwarded caller guarantees.
+    let Some(data) = (unsafe { output_buffer(dst, dst_len, dst_len) }) else {
         return;
+    };
+    let Some(mut target) = PixelTarget::new(data, width, height, stride_bytes, format) else {
+        return;
+    };
+    render::colorize_into(values, &params, &mut target);
+}
+
+/// Rows an exponential map strip `strip_width` samples wide needs to reach
+/// from `radius_max` in to `radius_min` (world units), 0 for invalid input.
+/// A zoom video from zoom `a` to `b` at `w * h` pixels needs about
+/// `strip_width = 2π * hypot(w, h) / 2`, `radius_max = hypot(w, h) / 2 / a`
+/// and `radius_min = 0.5 / b`.
+#[unsafe(no_mangle)]
+pub extern "C" fn expmap_rows(radius_max: f64, radius_min: f64, strip_width: i32) -> i32 {
+    if strip_width <= 0 {
+        return 0;
     }
-    if dst.is_null() {
+    expmap::rows_between(radius_max, radius_min, strip_width as usize)
+        .and_then(|rows| i32::try_from(rows).ok())
+        .unwrap_or(0)
+}
+
+/// Renders an exponential map strip around a zoom center with the same kernel
+/// as `mandelbrot_render_u32`: `strip_width * strip_rows` iteration values,
+/// column `c` at angle `2π * c / strip_width`, row `r` at radius
+/// `radius_max * exp(-2π * r / strip_width)`.
+///
+/// # Safety
+///
+/// dst must point to dst_len valid floats, at least strip_width * strip_rows
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn mandelbrot_expmap_f32(
+    center_x: f64,
+    center_y: f64,
+    radius_max: f64,
+    strip_width: i32,
+    strip_rows: i32,
+    params: RenderParams,
+    dst: *mut f32,
+    dst_len: usize,
+) {
+    let Some(map) = expmap(center_x, center_y, radius_max, strip_width, strip_rows) else {
+        return;
+    };
+    // SAFETY: forwarded caller guarantees.
+    let Some(values) = (unsafe { output_buffer(dst, dst_len, map.sample_count()) }) else {
+        return;
+    };
+    expmap::render_expmap(&map, &params, values);
+}
+
+/// Reconstructs a frame centered on the strip's center from a strip of
+/// `mandelbrot_expmap_f32`. `zoom` is fractional pixels per world unit,
+/// `rotation` counter-clockwise degrees; pixels outside the strip are
+/// iterated. See `mandelbrot_render_into` for the layout of `dst`.
+///
+/// # Safety
+///
+/// values must point to strip_width * strip_rows floats, dst to dst_len valid bytes
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn expmap_frame_render_into(
+    values: *const f32,
+    center_x: f64,
+    center_y: f64,
+    radius_max: f64,
+    strip_width: i32,
+    strip_rows: i32,
+    zoom: f64,
+    rotation: f64,
+    width_px: i32,
+    height_px: i32,
+    params: RenderParams,
+    format: PixelFormat,
+    dst: *mut u8,
+    dst_len: usize,
+    stride_bytes: usize,
+) {
+    if values.is_null() || width_px <= 0 || height_px <= 0 {
         return;
     }
+    let Some(map) = expmap(center_x, center_y, radius_max, strip_width, strip_rows) else {
+        return;
+    };
+    let (width, height) = (width_px as usize, height_px as usize);
+    let Some(view) = Viewport::new(center_x, center_y, 1, width, height) else {
+        return;
+    };
+    let view = view.with_zoom(zoom).rotated(rotation);
+    // SAFETY: caller guarantees values points to strip_width * strip_rows floats.
+    let values = unsafe { std::slice::from_raw_parts(values, map.sample_count()) };
+    // SAFETY: forwarded caller guarantees.
+    let Some(data) = (unsafe { output_buffer(dst, dst_len, dst_len) }) else {
+        return;
+    };
+    let Some(mut target) = PixelTarget::new(data, width, height, stride_bytes, format) else {
+        return;
+    };
+    expmap::reconstruct_into(&map, values, &view, &params, &mut target);
+}
 
-    let width = width_px as usize;
-    let height = height_px as usize;
-    let max_iter = max_iter.unsigned_abs();
+/// Width and height of the tiles of `tile_render_into` in pixels.
+#[unsafe(no_mangle)]
+pub extern "C" fn tile_size() -> i32 {
+    tiles::TILE_SIZE as i32
+}
 
-    let expected = match width.checked_mul(height) {
-        Some(v) => v,
-        None => return,
+/// Renders slippy-map tile (z, x, y), see the `tiles` module for the grid.
+/// Iteration values are kept in a process-wide LRU cache, so panning over
+/// tiles only computes the newly exposed ones and palette changes only
+/// recolor. See `mandelbrot_render_into` for the layout of `dst`, which must
+/// hold `tile_size()` square. False for a level past 40 or a short buffer.
+///
+/// # Safety
+///
+/// dst must point to dst_len valid bytes
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn tile_render_into(
+    z: u32,
+    x: i64,
+    y: i64,
+    params: RenderParams,
+    format: PixelFormat,
+    dst: *mut u8,
+    dst_len: usize,
+    stride_bytes: usize,
+) -> bool {
+    let Some(key) = TileKey::new(z, x, y) else {
+        return false;
+    };
+    // SAFETY: forwarded caller guarantees.
+    let Some(data) = (unsafe { output_buffer(dst, dst_len, dst_len) }) else {
+        return false;
+    };
+    let size = tiles::TILE_SIZE;
+    let Some(mut target) = PixelTarget::new(data, size, size, stride_bytes, format) else {
+        return false;
     };
-    if dst_len < expected {
+    tile_cache().render_into(key, &params, &mut target);
+    true
+}
+
+/// Bounds the tile cache, 64 MiB by default. Shrinks it right away.
+#[unsafe(no_mangle)]
+pub extern "C" fn tile_cache_set_capacity(capacity_bytes: usize) {
+    tile_cache().lock().set_capacity(capacity_bytes);
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn tile_cache_clear() {
+    tile_cache().lock().clear();
+}
+
+/// Writes the palette as a lookup table: `dst[i]` is the ARGB color of
+/// iteration `i` for the given `max_iter`, entries from `max_iter` on are black.
+/// `color_width` is the number of iterations per palette segment (50 by default).
+///
+/// # Safety
+///
+/// dst must point to dst_len valid u32s
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn colormap_lut_u32(
+    colormap: ColormapMode,
+    color_width: i32,
+    max_iter: i32,
+    dst: *mut u32,
+    dst_len: usize,
+) {
+    if color_width <= 0 {
+        return;
+    }
+    if dst.is_null() {
         return;
     }
 
     // SAFETY: caller guarantees dst points to dst_len valid u32s.
-    let pixels = unsafe { std::slice::from_raw_parts_mut(dst, expected) };
-
-    let inv_zoom = 1.0f64 / (zoom as f64); // world units per pixel
-    let half_w = (width_px as f64) * 0.5;
-    let half_h = (height_px as f64) * 0.5;
-
-    let cx = center_x as f64;
-    let cy = center_y as f64;
-
-    // parallelize by rows, like C# Parallel.For over py
-    pixels
-        .par_chunks_mut(width)
-        .enumerate()
-        .for_each(|(py, row)| {
-            // screen Y down, world Y up -> invert
-            let py_f = py as f64;
-            let y_world = cy + (-(py_f - half_h) * inv_zoom);
-
-            for (px, out) in row.iter_mut().enumerate() {
-                let px_f = px as f64;
-                let x_world = cx + ((px_f - half_w) * inv_zoom);
-
-                let iter = iterate_mandelbrot(x_world, y_world, max_iter);
-                *out = get_color(iter, max_iter);
-            }
-        });
+    let lut = unsafe { std::slice::from_raw_parts_mut(dst, dst_len) };
+    colormap::fill_lut(lut, max_iter.unsigned_abs(), colormap, color_width as f64);
 }
 
-#[inline]
-fn iterate_mandelbrot(x0: f64, y0: f64, max_iter: u32) -> u32 {
-    let mut x = 0.0f64;
-    let mut y = 0.0f64;
-    let mut i = 0u32;
+/// Encodes a packed ARGB frame as PNG at `path` (UTF-8, `path_len` bytes)
+/// and stores `view` in its tEXt chunks. Returns false on any failure.
+///
+/// # Safety
+///
+/// path must point to path_len bytes, argb to argb_len u32s
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn png_write_argb(
+    path: *const u8,
+    path_len: usize,
+    view: ViewDescription,
+    width_px: i32,
+    height_px: i32,
+    argb: *const u32,
+    argb_len: usize,
+) -> bool {
+    if path.is_null() || argb.is_null() || width_px <= 0 || height_px <= 0 {
+        return false;
+    }
+    let (width, height) = (width_px as usize, height_px as usize);
+    let Some(expected) = width.checked_mul(height) else {
+        return false;
+    };
+    if argb_len < expected {
+        return false;
+    }
+    // SAFETY: caller guarantees path points to path_len bytes.
+    let Ok(path) = std::str::from_utf8(unsafe { std::slice::from_raw_parts(path, path_len) })
+    else {
+        return false;
+    };
+    // SAFETY: caller guarantees argb points to argb_len u32s.
+    let argb = unsafe { std::slice::from_raw_parts(argb, expected) };
+
+    let mut rgba = vec![0u8; expected * 4];
+    for (out, &color) in rgba.chunks_exact_mut(4).zip(argb) {
+        PixelFormat::Rgba8.write(color, out);
+    }
 
-    while i < max_iter {
-        let xx = x * x - y * y + x0;
-        let yy = 2.0 * x * y + y0;
-        x = xx;
-        y = yy;
+    let Ok(file) = std::fs::File::create(path) else {
+        return false;
+    };
+    export::write_png(
+        std::io::BufWriter::new(file),
+        width_px as u32,
+        height_px as u32,
+        &rgba,
+        Some(&view),
+    )
+    .is_ok()
+}
+
+/// Reads the view stored by `png_write_argb` (or the `fractal-render` CLI)
+/// from an in-memory PNG file into `out`. Returns false if the data is not a
+/// PNG or the chunks are missing or malformed.
+///
+/// # Safety
+///
+/// data must point to data_len bytes, out to a writable ViewDescription
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn png_read_view(
+    data: *const u8,
+    data_len: usize,
+    out: *mut ViewDescription,
+) -> bool {
+    if data.is_null() || out.is_null() {
+        return false;
+    }
+    // SAFETY: caller guarantees data points to data_len bytes.
+    let data = unsafe { std::slice::from_raw_parts(data, data_len) };
+    match export::read_png_view(std::io::Cursor::new(data)) {
+        Ok(view) => {
+            // SAFETY: caller guarantees out is writable.
+            unsafe { out.write(view) };
+            true
+        }
+        Err(_) => false,
+    }
+}
 
-        // escape radius: |z|^2 > 4  <=> |z| > 2
-        if (x * x + y * y) > 4.0 {
-            break;
+/// Parses a location file (TOML, or JSON when it starts with `{`) into `out`.
+/// Returns false if the text is malformed, has an unsupported version or
+/// describes a view the renderers cannot represent.
+///
+/// # Safety
+///
+/// text must point to text_len UTF-8 bytes, out to a writable ViewDescription
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn location_parse(
+    text: *const u8,
+    text_len: usize,
+    out: *mut ViewDescription,
+) -> bool {
+    if text.is_null() || out.is_null() {
+        return false;
+    }
+    // SAFETY: caller guarantees text points to text_len bytes.
+    let Ok(text) = std::str::from_utf8(unsafe { std::slice::from_raw_parts(text, text_len) })
+    else {
+        return false;
+    };
+    match Location::parse(text).and_then(|l| l.to_view()) {
+        Ok(view) => {
+            // SAFETY: caller guarantees out is writable.
+            unsafe { out.write(view) };
+            true
         }
-        i += 1;
+        Err(_) => false,
+    }
+}
+
+/// Serializes `view` as a location file (UTF-8, no terminator).
+/// Returns the length in bytes and only writes if `dst_len` is large enough,
+/// so a first call with `dst_len = 0` queries the size. Returns 0 on failure.
+///
+/// # Safety
+///
+/// dst must be null or point to dst_len writable bytes
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn location_serialize(
+    view: ViewDescription,
+    format: LocationFormat,
+    dst: *mut u8,
+    dst_len: usize,
+) -> usize {
+    let Ok(text) = Location::from_view(&view).serialize(format) else {
+        return 0;
+    };
+    // SAFETY: forwarded caller guarantees.
+    if let Some(out) = unsafe { output_buffer(dst, dst_len, text.len()) } {
+        out.copy_from_slice(text.as_bytes());
+    }
+    text.len()
+}
+
+/// Imports a Kalles Fraktaler `.kfr` file into `out`. KF zooms are relative
+/// to the image height, so the height of the target view is required.
+/// Returns false for malformed files and unsupported formulas.
+///
+/// # Safety
+///
+/// text must point to text_len UTF-8 bytes, out to a writable ViewDescription
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn location_import_kfr(
+    text: *const u8,
+    text_len: usize,
+    height_px: i32,
+    out: *mut ViewDescription,
+) -> bool {
+    if height_px <= 0 {
+        return false;
+    }
+    // SAFETY: forwarded caller guarantees.
+    unsafe {
+        import_into(text, text_len, out, |text| {
+            import::parse_kfr(text, height_px as u32).map_err(|e| e.to_string())
+        })
+    }
+}
+
+/// Imports entry `entry_index` of a Fractint `.par` file into `out`.
+/// Fractint magnifications are relative to the image height, so the height of
+/// the target view is required. Returns false for malformed files, a missing
+/// entry and unsupported formulas.
+///
+/// # Safety
+///
+/// text must point to text_len UTF-8 bytes, out to a writable ViewDescription
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn location_import_par(
+    text: *const u8,
+    text_len: usize,
+    entry_index: i32,
+    height_px: i32,
+    out: *mut ViewDescription,
+) -> bool {
+    if height_px <= 0 || entry_index < 0 {
+        return false;
+    }
+    // SAFETY: forwarded caller guarantees.
+    unsafe {
+        import_into(text, text_len, out, |text| {
+            import::parse_par(text, height_px as u32)
+                .map_err(|e| e.to_string())?
+                .into_iter()
+                .nth(entry_index as usize)
+                .ok_or_else(|| format!("no entry {entry_index}"))?
+                .location
+                .map_err(|e| e.to_string())
+        })
+    }
+}
+
+/// Suggests a max iteration count from the scale alone
+/// (`zoom` = pixels per world unit). The result can be used directly as
+/// `RenderSettings.MaxIterations`.
+#[unsafe(no_mangle)]
+pub extern "C" fn suggest_max_iterations(zoom: u64) -> i32 {
+    estimate::suggest_max_iterations(zoom) as i32
+}
+
+/// Refines `start_iter` with a cheap low resolution preview of the view:
+/// the limit is doubled while more than `max_iter_fraction` (0..1) of the
+/// preview samples reach it, and as long as doubling lets more of them escape.
+/// Pass `suggest_max_iterations(zoom)` as `start_iter` for a sensible start.
+/// Invalid views return `start_iter` clamped like a valid result.
+#[unsafe(no_mangle)]
+pub extern "C" fn adaptive_max_iterations(
+    center_x: f32,
+    center_y: f32,
+    zoom: u64,
+    width_px: i32,
+    height_px: i32,
+    start_iter: i32,
+    max_iter_fraction: f32,
+) -> i32 {
+    let start_iter = start_iter.max(0) as u32;
+    let Some(view) = viewport(center_x, center_y, zoom, width_px, height_px) else {
+        return start_iter.clamp(estimate::MIN_ITERATIONS, estimate::MAX_ITERATIONS) as i32;
+    };
+    estimate::adaptive_max_iterations(
+        &view,
+        start_iter,
+        max_iter_fraction.clamp(0., 1.) as f64,
+        &Kernel::default(),
+    ) as i32
+}
+
+/// # Safety
+///
+/// text must be null or point to text_len bytes, out null or writable
+unsafe fn import_into(
+    text: *const u8,
+    text_len: usize,
+    out: *mut ViewDescription,
+    import: impl FnOnce(&str) -> Result<Location, String>,
+) -> bool {
+    if text.is_null() || out.is_null() {
+        return false;
+    }
+    // SAFETY: caller guarantees text points to text_len bytes.
+    let Ok(text) = std::str::from_utf8(unsafe { std::slice::from_raw_parts(text, text_len) })
+    else {
+        return false;
+    };
+    let Ok(view) = import(text).and_then(|l| l.to_view().map_err(|e| e.to_string())) else {
+        return false;
+    };
+    // SAFETY: caller guarantees out is writable.
+    unsafe { out.write(view) };
+    true
+}
+
+fn viewport(
+    center_x: f32,
+    center_y: f32,
+    zoom: u64,
+    width_px: i32,
+    height_px: i32,
+) -> Option<Viewport> {
+    if width_px <= 0 || height_px <= 0 {
+        return None;
+    }
+    Viewport::new(
+        center_x as f64,
+        center_y as f64,
+        zoom,
+        width_px as usize,
+        height_px as usize,
+    )
+}
+
+/// Previous and new view of the zoom FFI calls with their buffers.
+///
+/// # Safety
+///
+/// src and dst must be null or each point to len valid elements, not overlapping
+unsafe fn zoom_buffers<'a, T>(
+    (prev_center_x, prev_center_y, prev_zoom, center_x, center_y, zoom): (
+        f64,
+        f64,
+        u64,
+        f64,
+        f64,
+        u64,
+    ),
+    width_px: i32,
+    height_px: i32,
+    src: *const T,
+    dst: *mut T,
+    len: usize,
+) -> Option<(Viewport, Viewport, &'a [T], &'a mut [T])> {
+    if width_px <= 0 || height_px <= 0 || src.is_null() {
+        return None;
+    }
+    let (width, height) = (width_px as usize, height_px as usize);
+    let from = Viewport::new(prev_center_x, prev_center_y, prev_zoom, width, height)?;
+    let to = Viewport::new(center_x, center_y, zoom, width, height)?;
+    if len < from.pixel_count() {
+        return None;
+    }
+    // SAFETY: caller guarantees src points to len valid elements.
+    let src = unsafe { std::slice::from_raw_parts(src, from.pixel_count()) };
+    // SAFETY: forwarded caller guarantees.
+    let dst = unsafe { output_buffer(dst, len, to.pixel_count()) }?;
+    Some((from, to, src, dst))
+}
+
+fn tile_cache() -> &'static SharedTileCache {
+    static CACHE: LazyLock<SharedTileCache> = LazyLock::new(SharedTileCache::default);
+    &CACHE
+}
+
+fn expmap(
+    center_x: f64,
+    center_y: f64,
+    radius_max: f64,
+    strip_width: i32,
+    strip_rows: i32,
+) -> Option<ExpMap> {
+    if strip_width <= 0 || strip_rows <= 0 {
+        return None;
+    }
+    ExpMap::new(
+        center_x,
+        center_y,
+        radius_max,
+        strip_width as usize,
+        strip_rows as usize,
+    )
+}
+
+/// # Safety
+///
+/// dst must be null or point to dst_len valid u32s
+unsafe fn output_buffer<'a, T>(
+    dst: *mut T,
+    dst_len: usize,
+    expected: usize,
+) -> Option<&'a mut [T]> {
+    if dst.is_null() || dst_len < expected {
+        return None;
     }
-    i
+    // SAFETY: caller guarantees dst points to dst_len valid elements.
+    Some(unsafe { std::slice::from_raw_parts_mut(dst, expected) })
 }
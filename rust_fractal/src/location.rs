@@ -0,0 +1,426 @@
+//! Versioned location file for bookmarks and shared links.
+//!
+//! Stored as TOML or JSON with the same fields:
+//!
+//! ```toml
+//! version = 1
+//! formula = "mandelbrot"
+//! zoom = "2000000"        # pixels per world unit
+//! rotation = 0.0          # degrees, counter-clockwise
+//!
+//! [center]                # decimal strings, any number of digits
+//! re = "-0.743643887037158704752191506114774"
+//! im = "0.131825904205311970493132056385139"
+//!
+//! [iteration]
+//! max_iter = 1581
+//! escape_radius = 2.0
+//! bailout = "euclidean"
+//!
+//! [coloring]
+//! palette = "classic"
+//! method = "smooth-histogram"
+//! ```
+//!
+//! Center and zoom keep every digit they were written with, the renderers
+//! round them to `f64` / `u64` when the file is turned into a [`ViewDescription`].
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::colormap::ColormapMode;
+use crate::kernel::{BailoutNorm, DEFAULT_ESCAPE_RADIUS, Formula};
+use crate::render::{Coloring, RenderParams};
+use crate::view::ViewDescription;
+
+/// Version written by this build. Older versions are read, newer ones rejected.
+pub const FORMAT_VERSION: u32 = 1;
+
+/// Text encoding of a [`Location`].
+#[repr(u32)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LocationFormat {
+    #[default]
+    Toml = 0,
+    Json = 1,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Location {
+    pub version: u32,
+    #[serde(default)]
+    pub formula: Formula,
+    pub center: Center,
+    /// pixels per world unit
+    pub zoom: Decimal,
+    /// counter-clockwise rotation in degrees
+    #[serde(default)]
+    pub rotation: f64,
+    #[serde(default)]
+    pub iteration: IterationSettings,
+    #[serde(default)]
+    pub coloring: ColoringSettings,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Center {
+    pub re: Decimal,
+    pub im: Decimal,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IterationSettings {
+    pub max_iter: u32,
+    pub escape_radius: f64,
+    pub bailout: BailoutNorm,
+}
+
+impl Default for IterationSettings {
+    fn default() -> Self {
+        Self {
+            max_iter: RenderParams::default().max_iter(),
+            escape_radius: DEFAULT_ESCAPE_RADIUS,
+            bailout: BailoutNorm::default(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ColoringSettings {
+    pub palette: ColormapMode,
+    pub method: Coloring,
+}
+
+/// Decimal number kept as text so no digits are lost, e.g. `-1.25e-30`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Decimal(String);
+
+impl Decimal {
+    /// Shortest text that round-trips to `value`.
+    pub fn from_f64(value: f64) -> Self {
+        Self(value.to_string())
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        // validated on construction
+        self.0.parse().unwrap_or(f64::NAN)
+    }
+}
+
+impl TryFrom<String> for Decimal {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        if is_decimal(value.trim()) {
+            Ok(Self(value.trim().to_string()))
+        } else {
+            Err(format!("'{value}' is not a decimal number"))
+        }
+    }
+}
+
+impl From<Decimal> for String {
+    fn from(value: Decimal) -> Self {
+        value.0
+    }
+}
+
+impl fmt::Display for Decimal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// `[+-]digits[.digits][(e|E)[+-]digits]`, at least one mantissa digit.
+fn is_decimal(s: &str) -> bool {
+    let s = s.strip_prefix(['+', '-']).unwrap_or(s);
+    let (mantissa, exponent) = match s.split_once(['e', 'E']) {
+        Some((m, e)) => (m, Some(e)),
+        None => (s, None),
+    };
+    let (int, frac) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    let digits = |p: &str| p.bytes().all(|b| b.is_ascii_digit());
+    let mantissa_ok = digits(int) && digits(frac) && !(int.is_empty() && frac.is_empty());
+    let exponent_ok = exponent.is_none_or(|e| {
+        let e = e.strip_prefix(['+', '-']).unwrap_or(e);
+        !e.is_empty() && digits(e)
+    });
+    mantissa_ok && exponent_ok
+}
+
+impl Location {
+    pub fn from_view(view: &ViewDescription) -> Self {
+        let p = &view.params;
+        Self {
+            version: FORMAT_VERSION,
+            formula: p.formula,
+            center: Center {
+                re: Decimal::from_f64(view.center_x),
+                im: Decimal::from_f64(view.center_y),
+            },
+            zoom: Decimal(view.zoom.to_string()),
+            rotation: view.rotation,
+            iteration: IterationSettings {
+                max_iter: p.max_iter(),
+                escape_radius: p.escape_radius,
+                bailout: p.bailout,
+            },
+            coloring: ColoringSettings {
+                palette: p.colormap,
+                method: p.coloring,
+            },
+        }
+    }
+
+    /// Rounds the location to what the renderers support.
+    pub fn to_view(&self) -> Result<ViewDescription, LocationError> {
+        let zoom = self.zoom.to_f64();
+        if zoom.is_nan() || zoom < 1. || zoom >= u64::MAX as f64 {
+            return Err(LocationError::Unsupported(format!(
+                "zoom {} is outside 1..{}",
+                self.zoom,
+                u64::MAX
+            )));
+        }
+        let (center_x, center_y) = (self.center.re.to_f64(), self.center.im.to_f64());
+        if !center_x.is_finite() || !center_y.is_finite() {
+            return Err(LocationError::Unsupported(format!(
+                "center {} {} is outside the f64 range",
+                self.center.re, self.center.im
+            )));
+        }
+        let max_iter = i32::try_from(self.iteration.max_iter).map_err(|_| {
+            LocationError::Unsupported(format!("max_iter {} is too large", self.iteration.max_iter))
+        })?;
+
+        Ok(ViewDescription {
+            center_x,
+            center_y,
+            zoom: zoom.round() as u64,
+            rotation: self.rotation,
+            params: RenderParams {
+                formula: self.formula,
+                max_iter,
+                escape_radius: self.iteration.escape_radius,
+                bailout: self.iteration.bailout,
+                colormap: self.coloring.palette,
+                coloring: self.coloring.method,
+            },
+        })
+    }
+
+    /// Parses TOML or JSON, JSON is detected by a leading `{`.
+    pub fn parse(text: &str) -> Result<Self, LocationError> {
+        let location: Self = if text.trim_start().starts_with('{') {
+            serde_json::from_str(text).map_err(|e| LocationError::Parse(e.to_string()))?
+        } else {
+            toml::from_str(text).map_err(|e| LocationError::Parse(e.to_string()))?
+        };
+        if location.version == 0 || location.version > FORMAT_VERSION {
+            return Err(LocationError::UnsupportedVersion(location.version));
+        }
+        Ok(location)
+    }
+
+    pub fn serialize(&self, format: LocationFormat) -> Result<String, LocationError> {
+        match format {
+            LocationFormat::Toml => {
+                toml::to_string_pretty(self).map_err(|e| LocationError::Serialize(e.to_string()))
+            }
+            LocationFormat::Json => serde_json::to_string_pretty(self)
+                .map_err(|e| LocationError::Serialize(e.to_string())),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum LocationError {
+    Parse(String),
+    Serialize(String),
+    UnsupportedVersion(u32),
+    Unsupported(String),
+}
+
+impl fmt::Display for LocationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Parse(e) => write!(f, "invalid location file: {e}"),
+            Self::Serialize(e) => write!(f, "location serialization failed: {e}"),
+            Self::UnsupportedVersion(v) => write!(
+                f,
+                "location file version {v} is not supported (expected 1..={FORMAT_VERSION})"
+            ),
+            Self::Unsupported(e) => write!(f, "location not supported: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for LocationError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = r#"
+version = 1
+formula = "burning-ship"
+zoom = "2000000"
+rotation = 30.0
+
+[center]
+re = "-0.743643887037158704752191506114774"
+im = "0.131825904205311970493132056385139"
+
+[iteration]
+max_iter = 1581
+escape_radius = 4.0
+bailout = "manhattan"
+
+[coloring]
+palette = "classic"
+method = "smooth-histogram"
+"#;
+
+    fn sample_view() -> ViewDescription {
+        ViewDescription {
+            center_x: -0.743643887037158,
+            center_y: 0.131825904205312,
+            zoom: 2_000_000,
+            rotation: 30.,
+            params: RenderParams {
+                formula: Formula::BurningShip,
+                max_iter: 1581,
+                escape_radius: 4.,
+                bailout: BailoutNorm::Manhattan,
+                colormap: ColormapMode::Classic,
+                coloring: Coloring::SmoothHistogram,
+            },
+        }
+    }
+
+    #[test]
+    fn parses_every_field_and_keeps_center_digits() {
+        let location = Location::parse(SAMPLE).unwrap();
+        assert_eq!(location.formula, Formula::BurningShip);
+        assert_eq!(
+            location.center.re.as_str(),
+            "-0.743643887037158704752191506114774"
+        );
+        assert_eq!(location.zoom.as_str(), "2000000");
+        assert_eq!(location.rotation, 30.);
+        assert_eq!(location.iteration.max_iter, 1581);
+        assert_eq!(location.iteration.escape_radius, 4.);
+        assert_eq!(location.iteration.bailout, BailoutNorm::Manhattan);
+        assert_eq!(location.coloring.palette, ColormapMode::Classic);
+        assert_eq!(location.coloring.method, Coloring::SmoothHistogram);
+    }
+
+    #[test]
+    fn round_trips_through_toml_and_json() {
+        let location = Location::parse(SAMPLE).unwrap();
+        for format in [LocationFormat::Toml, LocationFormat::Json] {
+            let text = location.serialize(format).unwrap();
+            assert_eq!(
+                Location::parse(&text).unwrap(),
+                location,
+                "{format:?}:\n{text}"
+            );
+        }
+    }
+
+    #[test]
+    fn round_trips_a_view() {
+        let view = sample_view();
+        let text = Location::from_view(&view)
+            .serialize(LocationFormat::Toml)
+            .unwrap();
+        let back = Location::parse(&text).unwrap().to_view().unwrap();
+        assert_eq!(back.center_x, view.center_x);
+        assert_eq!(back.center_y, view.center_y);
+        assert_eq!(back.zoom, view.zoom);
+        assert_eq!(back.rotation, view.rotation);
+        assert_eq!(back.params.formula, view.params.formula);
+        assert_eq!(back.params.max_iter, view.params.max_iter);
+        assert_eq!(back.params.escape_radius, view.params.escape_radius);
+        assert_eq!(back.params.bailout, view.params.bailout);
+        assert_eq!(back.params.colormap, view.params.colormap);
+        assert_eq!(back.params.coloring, view.params.coloring);
+    }
+
+    #[test]
+    fn optional_sections_default() {
+        let location =
+            Location::parse(r#"{"version": 1, "center": {"re": "0", "im": "-1e-3"}, "zoom": "1"}"#)
+                .unwrap();
+        assert_eq!(location.formula, Formula::default());
+        assert_eq!(location.rotation, 0.);
+        assert_eq!(location.iteration, IterationSettings::default());
+        assert_eq!(location.coloring, ColoringSettings::default());
+        assert_eq!(location.to_view().unwrap().center_y, -1e-3);
+    }
+
+    #[test]
+    fn rejects_unsupported_versions() {
+        for version in [0, FORMAT_VERSION + 1] {
+            let text = SAMPLE.replace("version = 1", &format!("version = {version}"));
+            assert!(matches!(
+                Location::parse(&text),
+                Err(LocationError::UnsupportedVersion(v)) if v == version
+            ));
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_files() {
+        for text in [
+            "",
+            "version = 1",
+            "{ not json",
+            &SAMPLE.replace("\"2000000\"", "\"2e\""),
+            &SAMPLE.replace("-0.7436", "--0.7436"),
+            &SAMPLE.replace("\"burning-ship\"", "\"julia\""),
+        ] {
+            assert!(
+                matches!(Location::parse(text), Err(LocationError::Parse(_))),
+                "{text}"
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_views_the_renderers_cannot_show() {
+        for (from, to) in [
+            ("\"2000000\"", "\"0.5\""),
+            ("\"2000000\"", "\"1e20\""),
+            ("-0.743643887037158704752191506114774", "1e400"),
+            ("0.131825904205311970493132056385139", "-1e400"),
+            ("max_iter = 1581", "max_iter = 4000000000"),
+        ] {
+            let location = Location::parse(&SAMPLE.replace(from, to)).unwrap();
+            assert!(
+                matches!(location.to_view(), Err(LocationError::Unsupported(_))),
+                "{to}"
+            );
+        }
+    }
+
+    #[test]
+    fn decimals_follow_the_documented_grammar() {
+        for valid in ["0", "-1", "+.5", "1.", "1e5", "-1.25E-30", " 3.5 "] {
+            assert!(Decimal::try_from(valid.to_string()).is_ok(), "{valid}");
+        }
+        for invalid in [
+            "", ".", "-", "1e", "1e+", "0x10", "1.2.3", "inf", "NaN", "1_000",
+        ] {
+            assert!(Decimal::try_from(invalid.to_string()).is_err(), "{invalid}");
+        }
+    }
+}
@@ -0,0 +1,322 @@
+//! Perturbation iteration for zooms past `f64` precision.
+//!
+//! Deep in a zoom neighbouring pixels round to the same `f64` coordinate.
+//! Instead one reference point `C` is iterated directly, the [`ReferenceOrbit`]
+//! `Z_n`, and every pixel `C + dc` only iterates its small difference
+//! `d_n = z_n - Z_n`:
+//!
+//! `d_{n+1} = 2 Z_n d_n + d_n^2 + dc` (conjugated for the Tricorn)
+//!
+//! `dc` is the offset of the pixel from the reference, exact at any depth.
+//! The orbit itself is iterated in double-double precision and rounded to
+//! `f64` afterwards; iterated in `f64` its rounding errors would shift the
+//! image by about one `f64` step of `C`, many pixels deep in a zoom.
+//! When `|z_n| < |d_n|` or the orbit runs out, the pixel rebases onto the
+//! start of the orbit (`d = z`, `n = 0`), which avoids the glitches of a
+//! reference that escaped early or passes far from the pixel. Any reference
+//! near the view works, so consecutive animation frames share one
+//! ([`ReferenceOrbit::reusable_for`]).
+//!
+//! The Burning Ship's `abs` has no such expansion, it is always iterated
+//! directly.
+
+use rayon::prelude::*;
+
+use crate::kernel::{Formula, Kernel};
+use crate::render::{RenderParams, Viewport};
+
+/// Views with pixels smaller than this fraction of the center's magnitude
+/// lose detail to `f64` rounding when iterated directly.
+const DIRECT_PRECISION: f64 = 1. / (1u64 << 40) as f64;
+
+/// Whether `formula` can be iterated by perturbation.
+pub fn supports(formula: Formula) -> bool {
+    matches!(formula, Formula::Mandelbrot | Formula::Tricorn)
+}
+
+/// Whether `view` is too deep to iterate directly in `f64`.
+pub fn needs_perturbation(view: &Viewport) -> bool {
+    let magnitude = view.center_x.abs().max(view.center_y.abs()).max(1.);
+    view.inv_zoom < magnitude * DIRECT_PRECISION
+}
+
+/// `Z_0 = 0, Z_1, ...` of a reference point, until it escapes or reaches
+/// the iteration limit it was computed for.
+#[derive(Clone, Debug)]
+pub struct ReferenceOrbit {
+    x0: f64,
+    y0: f64,
+    formula: Formula,
+    max_iter: u32,
+    escaped: bool,
+    orbit: Vec<(f64, f64)>,
+}
+
+impl ReferenceOrbit {
+    pub fn compute(kernel: &Kernel, x0: f64, y0: f64) -> Self {
+        let conjugate = kernel.formula == Formula::Tricorn;
+        let (cx, cy) = (DoubleDouble::from(x0), DoubleDouble::from(y0));
+        let (mut x, mut y) = (DoubleDouble::from(0.), DoubleDouble::from(0.));
+        let mut orbit = vec![(0., 0.)];
+        let mut escaped = false;
+        while orbit.len() <= kernel.max_iter as usize {
+            let xy = x.mul(y);
+            let im = xy.add(xy);
+            (x, y) = (
+                x.mul(x).sub(y.mul(y)).add(cx),
+                if conjugate { im.neg() } else { im }.add(cy),
+            );
+            orbit.push((x.hi, y.hi));
+            if kernel.bailout.escaped(x.hi, y.hi) {
+                escaped = true;
+                break;
+            }
+        }
+        Self {
+            x0,
+            y0,
+            formula: kernel.formula,
+            max_iter: kernel.max_iter,
+            escaped,
+            orbit,
+        }
+    }
+
+    /// The reference point.
+    pub fn center(&self) -> (f64, f64) {
+        (self.x0, self.y0)
+    }
+
+    /// Whether `view` can be iterated against this orbit as well as against
+    /// a new one: same formula, the reference lies within the view, and the
+    /// orbit is not cut short by a lower iteration limit.
+    pub fn reusable_for(&self, view: &Viewport, kernel: &Kernel) -> bool {
+        let (px, py) = view.pixel_at_offset(self.x0 - view.center_x, self.y0 - view.center_y);
+        let inside =
+            (0. ..view.width as f64).contains(&px) && (0. ..view.height as f64).contains(&py);
+        self.formula == kernel.formula
+            && inside
+            && (self.escaped || self.max_iter >= kernel.max_iter)
+    }
+
+    /// [`Kernel::escape_time`] of the point `(dcx, dcy)` away from the reference.
+    #[inline]
+    pub fn escape_time(&self, kernel: &Kernel, dcx: f64, dcy: f64) -> (u32, f64) {
+        let conjugate = kernel.formula == Formula::Tricorn;
+        let last = self.orbit.len() - 1;
+        let (mut dx, mut dy) = (0f64, 0f64);
+        let (mut x, mut y) = (0f64, 0f64);
+        let mut m = 0;
+        let mut i = 0u32;
+
+        while i < kernel.max_iter {
+            let (zx, zy) = self.orbit[m];
+            let nx = 2. * (zx * dx - zy * dy) + dx * dx - dy * dy;
+            let ny = 2. * (zx * dy + zy * dx) + 2. * dx * dy;
+            dx = nx + dcx;
+            dy = if conjugate { -ny } else { ny } + dcy;
+            m += 1;
+
+            let (zx, zy) = self.orbit[m];
+            (x, y) = (zx + dx, zy + dy);
+            if kernel.bailout.escaped(x, y) {
+                break;
+            }
+            i += 1;
+
+            if m == last || x * x + y * y < dx * dx + dy * dy {
+                (dx, dy) = (x, y);
+                m = 0;
+            }
+        }
+        (i, x * x + y * y)
+    }
+}
+
+/// `hi + lo` with `|lo| <= ulp(hi) / 2`, about 106 significant bits.
+#[derive(Clone, Copy, Debug)]
+struct DoubleDouble {
+    hi: f64,
+    lo: f64,
+}
+
+impl From<f64> for DoubleDouble {
+    fn from(hi: f64) -> Self {
+        Self { hi, lo: 0. }
+    }
+}
+
+impl DoubleDouble {
+    /// `a + b` of two `f64`s that may differ in magnitude.
+    #[inline]
+    fn two_sum(a: f64, b: f64) -> Self {
+        let hi = a + b;
+        let b_part = hi - a;
+        let lo = (a - (hi - b_part)) + (b - b_part);
+        Self { hi, lo }
+    }
+
+    /// Renormalizes with `|lo|` known to be much smaller than `|hi|`.
+    #[inline]
+    fn quick_two_sum(hi: f64, lo: f64) -> Self {
+        let sum = hi + lo;
+        Self {
+            hi: sum,
+            lo: lo - (sum - hi),
+        }
+    }
+
+    #[inline]
+    fn add(self, other: Self) -> Self {
+        let sum = Self::two_sum(self.hi, other.hi);
+        Self::quick_two_sum(sum.hi, sum.lo + self.lo + other.lo)
+    }
+
+    #[inline]
+    fn neg(self) -> Self {
+        Self {
+            hi: -self.hi,
+            lo: -self.lo,
+        }
+    }
+
+    #[inline]
+    fn sub(self, other: Self) -> Self {
+        self.add(other.neg())
+    }
+
+    #[inline]
+    fn mul(self, other: Self) -> Self {
+        let hi = self.hi * other.hi;
+        let error = self.hi.mul_add(other.hi, -hi);
+        Self::quick_two_sum(hi, error + self.hi * other.lo + self.lo * other.hi)
+    }
+}
+
+/// [`crate::render::iterate_values`] against `orbit`.
+pub fn iterate_values_perturbed(
+    view: &Viewport,
+    params: &RenderParams,
+    orbit: &ReferenceOrbit,
+    values: &mut [f32],
+) {
+    let kernel = params.kernel();
+    let smooth = params.coloring.is_smooth();
+    // offset of the view's center from the reference, exact while they are close
+    let (rx, ry) = (view.center_x - orbit.x0, view.center_y - orbit.y0);
+
+    values[..view.pixel_count()]
+        .par_chunks_mut(view.width)
+        .enumerate()
+        .for_each(|(py, row)| {
+            for (px, out) in row.iter_mut().enumerate() {
+                let (dx, dy) = view.offset(px as f64, py as f64);
+                let escape = orbit.escape_time(&kernel, rx + dx, ry + dy);
+                *out = kernel.buffer_value(escape, smooth);
+            }
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::{Coloring, iterate_values};
+
+    /// Seahorse valley, a common deep zoom target.
+    const DEEP: (f64, f64) = (-0.7436438870371587, 0.13182590420531198);
+
+    fn params(formula: Formula, max_iter: i32) -> RenderParams {
+        RenderParams {
+            formula,
+            max_iter,
+            coloring: Coloring::EscapeTime,
+            ..RenderParams::default()
+        }
+    }
+
+    fn perturbed(view: &Viewport, params: &RenderParams, reference: (f64, f64)) -> Vec<f32> {
+        let orbit = ReferenceOrbit::compute(&params.kernel(), reference.0, reference.1);
+        let mut values = vec![0.; view.pixel_count()];
+        iterate_values_perturbed(view, params, &orbit, &mut values);
+        values
+    }
+
+    fn agreement(a: &[f32], b: &[f32]) -> f64 {
+        let same = a.iter().zip(b).filter(|(a, b)| a == b).count();
+        same as f64 / a.len() as f64
+    }
+
+    fn distinct(values: &[f32]) -> usize {
+        let mut values = values.to_vec();
+        values.sort_by(f32::total_cmp);
+        values.dedup();
+        values.len()
+    }
+
+    #[test]
+    fn matches_direct_iteration_at_shallow_zoom() {
+        for formula in [Formula::Mandelbrot, Formula::Tricorn] {
+            let view = Viewport::new(-0.75, 0.1, 300, 64, 48).unwrap();
+            let params = params(formula, 500);
+            let mut direct = vec![0.; view.pixel_count()];
+            iterate_values(&view, &params, &mut direct);
+            // a reference off center that escapes quickly exercises rebasing
+            let values = perturbed(&view, &params, (-0.7, 0.12));
+            assert!(agreement(&direct, &values) > 0.99, "{formula:?}");
+        }
+    }
+
+    #[test]
+    fn resolves_detail_direct_iteration_rounds_away() {
+        let view = Viewport::new(DEEP.0, DEEP.1, 1_000_000_000_000_000_000, 32, 32).unwrap();
+        assert!(needs_perturbation(&view));
+        let params = params(Formula::Mandelbrot, 20000);
+
+        let mut direct = vec![0.; view.pixel_count()];
+        iterate_values(&view, &params, &mut direct);
+        let values = perturbed(&view, &params, DEEP);
+        assert!(distinct(&values) > 4 * distinct(&direct));
+    }
+
+    #[test]
+    fn results_do_not_depend_on_the_reference() {
+        let view = Viewport::new(DEEP.0, DEEP.1, 10_000_000_000_000_000, 48, 48).unwrap();
+        let params = params(Formula::Mandelbrot, 5000);
+        let (dx, dy) = view.offset(5., 40.);
+        let centered = perturbed(&view, &params, DEEP);
+        let corner = perturbed(&view, &params, (DEEP.0 + dx, DEEP.1 + dy));
+        assert!(agreement(&centered, &corner) > 0.99);
+    }
+
+    #[test]
+    fn orbits_are_reused_while_the_reference_is_in_view() {
+        let kernel = params(Formula::Mandelbrot, 1000).kernel();
+        let orbit = ReferenceOrbit::compute(&kernel, DEEP.0, DEEP.1);
+        let view = Viewport::new(DEEP.0, DEEP.1, 1 << 50, 64, 64).unwrap();
+        assert!(orbit.reusable_for(&view, &kernel));
+
+        let (dx, dy) = view.offset(-10., 32.);
+        let moved = Viewport::new(DEEP.0 + dx, DEEP.1 + dy, 1 << 50, 64, 64).unwrap();
+        assert!(!orbit.reusable_for(&moved, &kernel));
+
+        let tricorn = Kernel {
+            formula: Formula::Tricorn,
+            ..kernel
+        };
+        assert!(!orbit.reusable_for(&view, &tricorn));
+        let longer = Kernel {
+            max_iter: 2000,
+            ..kernel
+        };
+        assert!(!orbit.reusable_for(&view, &longer));
+    }
+
+    #[test]
+    fn only_deep_views_need_perturbation() {
+        let shallow = Viewport::new(DEEP.0, DEEP.1, 1 << 30, 8, 8).unwrap();
+        let deep = Viewport::new(DEEP.0, DEEP.1, 1 << 45, 8, 8).unwrap();
+        assert!(!needs_perturbation(&shallow));
+        assert!(needs_perturbation(&deep));
+        assert!(!supports(Formula::BurningShip));
+    }
+}
@@ -0,0 +1,340 @@
+//! Output pixel layouts and strided destination buffers.
+
+use rayon::prelude::*;
+
+/// Memory layout of one output pixel.
+#[repr(u32)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum PixelFormat {
+    /// Packed `0xAARRGGBB` `u32` in native byte order, the layout of the `*_u32` APIs.
+    #[default]
+    Argb32 = 0,
+    /// Bytes `R, G, B, A`.
+    Rgba8 = 1,
+    /// Bytes `B, G, R, A`.
+    Bgra8 = 2,
+    /// Little-endian `u16`, `rrrrrggg gggbbbbb`, alpha dropped.
+    Rgb565 = 3,
+    /// Bytes `R, G, B, A` with color channels multiplied by alpha.
+    Rgba8Premultiplied = 4,
+    /// Bytes `B, G, R, A` with color channels multiplied by alpha.
+    Bgra8Premultiplied = 5,
+}
+
+impl PixelFormat {
+    pub fn bytes_per_pixel(self) -> usize {
+        match self {
+            Self::Rgb565 => 2,
+            Self::Argb32
+            | Self::Rgba8
+            | Self::Bgra8
+            | Self::Rgba8Premultiplied
+            | Self::Bgra8Premultiplied => 4,
+        }
+    }
+
+    /// Writes one `0xAARRGGBB` color into `out` (at least `bytes_per_pixel` long).
+    #[inline]
+    pub fn write(self, argb: u32, out: &mut [u8]) {
+        let [a, r, g, b] = argb.to_be_bytes();
+        match self {
+            Self::Argb32 => out[..4].copy_from_slice(&argb.to_ne_bytes()),
+            Self::Rgba8 => out[..4].copy_from_slice(&[r, g, b, a]),
+            Self::Bgra8 => out[..4].copy_from_slice(&[b, g, r, a]),
+            Self::Rgb565 => {
+                let v = (r as u16 >> 3) << 11 | (g as u16 >> 2) << 5 | b as u16 >> 3;
+                out[..2].copy_from_slice(&v.to_le_bytes());
+            }
+            Self::Rgba8Premultiplied => out[..4].copy_from_slice(&[
+                premultiply(r, a),
+                premultiply(g, a),
+                premultiply(b, a),
+                a,
+            ]),
+            Self::Bgra8Premultiplied => out[..4].copy_from_slice(&[
+                premultiply(b, a),
+                premultiply(g, a),
+                premultiply(r, a),
+                a,
+            ]),
+        }
+    }
+}
+
+#[inline]
+fn premultiply(c: u8, a: u8) -> u8 {
+    ((c as u32 * a as u32 + 127) / 255) as u8
+}
+
+/// Destination buffer of `height` rows, `stride` bytes apart.
+pub struct PixelTarget<'a> {
+    data: &'a mut [u8],
+    width: usize,
+    height: usize,
+    stride: usize,
+    format: PixelFormat,
+}
+
+impl<'a> PixelTarget<'a> {
+    /// `None` if the stride cannot hold a row or `data` is too short.
+    /// The last row only needs `width * bytes_per_pixel` bytes, not a full stride.
+    pub fn new(
+        data: &'a mut [u8],
+        width: usize,
+        height: usize,
+        stride: usize,
+        format: PixelFormat,
+    ) -> Option<Self> {
+        let row_bytes = width.checked_mul(format.bytes_per_pixel())?;
+        let required = required_len(width, height, stride, format)?;
+        if width == 0 || height == 0 || stride < row_bytes || data.len() < required {
+            return None;
+        }
+        Some(Self {
+            data: &mut data[..required],
+            width,
+            height,
+            stride,
+            format,
+        })
+    }
+
+    /// Packed `u32` buffer with an implicit stride of `width`.
+    pub fn from_argb(pixels: &'a mut [u32], width: usize, height: usize) -> Option<Self> {
+        // SAFETY: u8 has no alignment requirement and every bit pattern is valid,
+        // the byte view covers exactly the memory of `pixels`.
+        let data = unsafe {
+            std::slice::from_raw_parts_mut(pixels.as_mut_ptr() as *mut u8, size_of_val(pixels))
+        };
+        Self::new(data, width, height, width * 4, PixelFormat::Argb32)
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Rows in parallel, `(py, row)`.
+    pub fn par_rows(&mut self) -> impl IndexedParallelIterator<Item = (usize, PixelRow<'_>)> {
+        let format = self.format;
+        let width = self.width;
+        self.data
+            .par_chunks_mut(self.stride)
+            .take(self.height)
+            .map(move |data| PixelRow {
+                data,
+                width,
+                format,
+            })
+            .enumerate()
+    }
+}
+
+/// Bytes needed for `height` rows of `width` pixels, `stride` bytes apart.
+pub fn required_len(
+    width: usize,
+    height: usize,
+    stride: usize,
+    format: PixelFormat,
+) -> Option<usize> {
+    let row_bytes = width.checked_mul(format.bytes_per_pixel())?;
+    stride
+        .checked_mul(height.checked_sub(1)?)?
+        .checked_add(row_bytes)
+}
+
+pub struct PixelRow<'a> {
+    data: &'a mut [u8],
+    width: usize,
+    format: PixelFormat,
+}
+
+impl PixelRow<'_> {
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    #[inline]
+    pub fn set(&mut self, px: usize, argb: u32) {
+        let bpp = self.format.bytes_per_pixel();
+        self.format
+            .write(argb, &mut self.data[px * bpp..(px + 1) * bpp]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FORMATS: [PixelFormat; 6] = [
+        PixelFormat::Argb32,
+        PixelFormat::Rgba8,
+        PixelFormat::Bgra8,
+        PixelFormat::Rgb565,
+        PixelFormat::Rgba8Premultiplied,
+        PixelFormat::Bgra8Premultiplied,
+    ];
+
+    fn written(format: PixelFormat, argb: u32) -> Vec<u8> {
+        let mut out = vec![0; format.bytes_per_pixel()];
+        format.write(argb, &mut out);
+        out
+    }
+
+    #[test]
+    fn writes_every_format_byte_by_byte() {
+        // a = 0x80, r = 0xff, g = 0x80, b = 0x40
+        let argb = 0x80ff8040;
+        assert_eq!(written(PixelFormat::Argb32, argb), argb.to_ne_bytes());
+        assert_eq!(written(PixelFormat::Rgba8, argb), [0xff, 0x80, 0x40, 0x80]);
+        assert_eq!(written(PixelFormat::Bgra8, argb), [0x40, 0x80, 0xff, 0x80]);
+        // 11111 100000 01000
+        assert_eq!(written(PixelFormat::Rgb565, argb), [0x08, 0xfc]);
+        assert_eq!(
+            written(PixelFormat::Rgba8Premultiplied, argb),
+            [128, 64, 32, 0x80]
+        );
+        assert_eq!(
+            written(PixelFormat::Bgra8Premultiplied, argb),
+            [32, 64, 128, 0x80]
+        );
+    }
+
+    #[test]
+    fn rgb565_keeps_the_high_bits() {
+        assert_eq!(written(PixelFormat::Rgb565, 0xffffffff), [0xff, 0xff]);
+        assert_eq!(written(PixelFormat::Rgb565, 0xff000000), [0, 0]);
+        // the low bits of every channel are dropped
+        assert_eq!(written(PixelFormat::Rgb565, 0x00070307), [0, 0]);
+        assert_eq!(written(PixelFormat::Rgb565, 0x00ff0000), [0x00, 0xf8]);
+        assert_eq!(written(PixelFormat::Rgb565, 0x0000ff00), [0xe0, 0x07]);
+        assert_eq!(written(PixelFormat::Rgb565, 0x000000ff), [0x1f, 0x00]);
+    }
+
+    #[test]
+    fn premultiplying_rounds_to_nearest() {
+        for a in 0..=255u8 {
+            for c in 0..=255u8 {
+                let exact = (c as f64 * a as f64 / 255.).round() as u8;
+                assert_eq!(premultiply(c, a), exact, "{c} * {a}");
+            }
+        }
+        assert_eq!(premultiply(1, 128), 1);
+        assert_eq!(premultiply(1, 127), 0);
+        assert_eq!(
+            written(PixelFormat::Rgba8Premultiplied, 0x00ffffff),
+            [0, 0, 0, 0]
+        );
+    }
+
+    #[test]
+    fn required_len_leaves_out_the_last_row_padding() {
+        assert_eq!(required_len(3, 2, 16, PixelFormat::Argb32), Some(28));
+        assert_eq!(required_len(3, 2, 8, PixelFormat::Rgb565), Some(14));
+        assert_eq!(required_len(3, 1, 100, PixelFormat::Rgba8), Some(12));
+        assert_eq!(required_len(3, 0, 16, PixelFormat::Argb32), None);
+        assert_eq!(required_len(usize::MAX, 1, 16, PixelFormat::Argb32), None);
+        assert_eq!(
+            required_len(1, usize::MAX, usize::MAX, PixelFormat::Argb32),
+            None
+        );
+    }
+
+    #[test]
+    fn rejects_short_buffers_and_strides() {
+        for format in FORMATS {
+            let row = 3 * format.bytes_per_pixel();
+            let stride = row + 5;
+            let required = stride * 3 + row;
+            let mut data = vec![0; required];
+            assert!(PixelTarget::new(&mut data, 3, 4, stride, format).is_some());
+            assert!(PixelTarget::new(&mut data[..required - 1], 3, 4, stride, format).is_none());
+            assert!(PixelTarget::new(&mut data, 3, 4, row - 1, format).is_none());
+            assert!(PixelTarget::new(&mut data, 0, 4, stride, format).is_none());
+            assert!(PixelTarget::new(&mut data, 3, 0, stride, format).is_none());
+            assert!(PixelTarget::new(&mut data, usize::MAX, 1, usize::MAX, format).is_none());
+            assert!(PixelTarget::new(&mut data, 3, usize::MAX, stride, format).is_none());
+        }
+        let mut pixels = [0u32; 6];
+        assert!(PixelTarget::from_argb(&mut pixels, 3, 2).is_some());
+        assert!(PixelTarget::from_argb(&mut pixels, 3, 3).is_none());
+    }
+
+    #[test]
+    fn rows_write_inside_the_stride_only() {
+        for format in FORMATS {
+            let bpp = format.bytes_per_pixel();
+            let (width, height, stride) = (3, 3, 3 * bpp + 3);
+            // the last row has no padding
+            let mut data = vec![0xaa; stride * (height - 1) + width * bpp];
+            let mut target = PixelTarget::new(&mut data, width, height, stride, format).unwrap();
+            let rows: Vec<usize> = target
+                .par_rows()
+                .map(|(py, mut row)| {
+                    for px in 0..row.width() {
+                        row.set(px, 0xff000000);
+                    }
+                    py
+                })
+                .collect();
+            assert_eq!(rows, [0, 1, 2]);
+
+            let black = written(format, 0xff000000);
+            for (offset, byte) in data.iter().enumerate() {
+                let in_row = offset % stride;
+                if in_row < width * bpp {
+                    assert_eq!(*byte, black[in_row % bpp], "{format:?} byte {offset}");
+                } else {
+                    assert_eq!(*byte, 0xaa, "{format:?} padding byte {offset}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn render_into_leaves_short_buffers_alone() {
+        let params = crate::render::RenderParams::default();
+        let (width, height, stride) = (4, 3, 4 * 4 + 4);
+        let required = stride * 2 + 4 * 4;
+        let mut data = vec![0xaa; required];
+        for (len, stride) in [(required - 1, stride), (required, 4 * 4 - 1)] {
+            // SAFETY: data holds at least len bytes.
+            unsafe {
+                crate::mandelbrot_render_into(
+                    -0.5,
+                    0.,
+                    100,
+                    width,
+                    height,
+                    params,
+                    PixelFormat::Rgba8,
+                    data.as_mut_ptr(),
+                    len,
+                    stride,
+                )
+            };
+            assert!(data.iter().all(|&b| b == 0xaa), "len {len} stride {stride}");
+        }
+        // SAFETY: as above.
+        unsafe {
+            crate::mandelbrot_render_into(
+                -0.5,
+                0.,
+                100,
+                width,
+                height,
+                params,
+                PixelFormat::Rgba8,
+                data.as_mut_ptr(),
+                required,
+                stride,
+            )
+        };
+        assert!(data[..4 * 4].iter().any(|&b| b != 0xaa));
+        assert!(data[4 * 4..stride].iter().all(|&b| b == 0xaa));
+    }
+}
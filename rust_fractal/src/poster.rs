@@ -0,0 +1,505 @@
+//! Posters too large to render in one piece.
+//!
+//! The image is rendered in full-width bands of rows. Finished bands are
+//! appended as raw RGBA to a scratch file next to the output
+//! (`<output>.partial`), described by a job file (`<output>.job.toml`). Once
+//! every band is on disk the scratch file is streamed band by band into the
+//! PNG or TIFF and both helper files are removed. Memory use is bounded by one
+//! band, disk use by the raw image plus the encoded one.
+//!
+//! Rendering the same job to the same output again after an interruption
+//! continues after the last complete band. Histogram colorings are equalized
+//! over a preview of the whole image so the bands match.
+
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufReader, BufWriter, Read, Seek, Write};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use tiff::encoder::{TiffEncoder, TiffKind, colortype};
+use tiff::tags::Tag;
+
+use crate::export::png_writer;
+use crate::location::{Location, LocationFormat};
+use crate::pixel::{PixelFormat, PixelTarget};
+use crate::render::{colorize_with_histogram, iterate_values, preview_histogram, render_into};
+use crate::view::ViewDescription;
+
+pub const DEFAULT_BAND_HEIGHT: u32 = 256;
+
+/// Width of the preview histogram colorings are equalized over.
+const PREVIEW_WIDTH: usize = 1024;
+
+/// Above this many raw bytes TIFFs are written as BigTIFF, classic TIFF
+/// offsets are 32 bit.
+const BIG_TIFF_BYTES: u64 = 1 << 31;
+
+const BYTES_PER_PIXEL: usize = 4;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PosterFormat {
+    /// 8 bit RGBA PNG with the view in its text chunks.
+    Png,
+    /// Uncompressed 8 bit RGBA TIFF, one strip per band, with the location
+    /// file in `ImageDescription`. Readable in strips by most large-image tools.
+    Tiff,
+}
+
+impl PosterFormat {
+    /// From the extension of `path`: `.png`, `.tif` or `.tiff`.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "png" => Some(Self::Png),
+            "tif" | "tiff" => Some(Self::Tiff),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stage {
+    Rendering,
+    Encoding,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Progress {
+    pub stage: Stage,
+    /// Bands finished in this stage, including ones from an interrupted run.
+    pub done: u32,
+    pub total: u32,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Poster {
+    pub view: ViewDescription,
+    pub width: u32,
+    pub height: u32,
+    /// Rows rendered and written at once, clamped to `1..=height`.
+    pub band_height: u32,
+    pub format: PosterFormat,
+}
+
+/// Contents of the job file, a resumed render must match it exactly.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Job {
+    width: u32,
+    height: u32,
+    band_height: u32,
+    format: PosterFormat,
+    location: Location,
+}
+
+impl Poster {
+    /// Renders to `output`, continuing an interrupted render of the same poster.
+    /// Fails with [`PosterError::OtherJob`] if the helper files next to
+    /// `output` belong to a different poster, see [`discard`].
+    pub fn render(
+        &self,
+        output: &Path,
+        mut progress: impl FnMut(Progress),
+    ) -> Result<(), PosterError> {
+        let (width, height) = (self.width as usize, self.height as usize);
+        let view = self.view.viewport(width, height).ok_or(PosterError::Size)?;
+        let band_height = self.band_height.clamp(1, self.height.max(1));
+        let bands = self.height.div_ceil(band_height);
+        let band_bytes = width as u64 * band_height as u64 * BYTES_PER_PIXEL as u64;
+        let buffer_len = usize::try_from(band_bytes).map_err(|_| PosterError::Size)?;
+
+        let (job_path, partial_path) = helper_paths(output);
+        let job = Job {
+            width: self.width,
+            height: self.height,
+            band_height,
+            format: self.format,
+            location: Location::from_view(&self.view),
+        };
+        let mut done = resume(&job, &job_path, &partial_path, band_bytes)?;
+        progress(Progress {
+            stage: Stage::Rendering,
+            done,
+            total: bands,
+        });
+
+        let params = self.view.params;
+        let histogram = params
+            .coloring
+            .is_histogram()
+            .then(|| preview_histogram(&view, &params, PREVIEW_WIDTH));
+
+        let mut partial = OpenOptions::new().append(true).open(&partial_path)?;
+        let mut rgba = vec![0u8; buffer_len];
+        let mut values = Vec::new();
+        while done < bands {
+            let y = (done * band_height) as usize;
+            let rows = (band_height as usize).min(height - y);
+            let band_view = view.sub_view(0, y, width, rows);
+            let rgba = &mut rgba[..width * rows * BYTES_PER_PIXEL];
+            let mut target = PixelTarget::new(
+                rgba,
+                width,
+                rows,
+                width * BYTES_PER_PIXEL,
+                PixelFormat::Rgba8,
+            )
+            .ok_or(PosterError::Size)?;
+
+            match &histogram {
+                Some(histogram) => {
+                    values.resize(band_view.pixel_count(), 0f32);
+                    iterate_values(&band_view, &params, &mut values);
+                    colorize_with_histogram(&values, &params, &mut target, histogram);
+                }
+                None => render_into(&band_view, &params, &mut target),
+            }
+
+            partial.write_all(rgba)?;
+            partial.sync_data()?;
+            done += 1;
+            progress(Progress {
+                stage: Stage::Rendering,
+                done,
+                total: bands,
+            });
+        }
+        drop(partial);
+
+        let file = File::create(output)?;
+        let mut bands_in = BufReader::new(File::open(&partial_path)?);
+        let mut next_band = |index: u32, buf: &mut Vec<u8>| -> io::Result<()> {
+            let rows = (band_height as usize).min(height - (index * band_height) as usize);
+            buf.resize(width * rows * BYTES_PER_PIXEL, 0);
+            bands_in.read_exact(buf)?;
+            progress(Progress {
+                stage: Stage::Encoding,
+                done: index + 1,
+                total: bands,
+            });
+            Ok(())
+        };
+        match self.format {
+            PosterFormat::Png => {
+                let mut writer = png_writer(
+                    BufWriter::new(file),
+                    self.width,
+                    self.height,
+                    Some(&self.view),
+                )?;
+                let mut stream = writer.stream_writer()?;
+                for index in 0..bands {
+                    next_band(index, &mut rgba)?;
+                    stream.write_all(&rgba)?;
+                }
+                stream.finish()?;
+                writer.finish()?;
+            }
+            PosterFormat::Tiff => {
+                let description = job.location.serialize(LocationFormat::Toml)?;
+                let writer = BufWriter::new(file);
+                let tiff = TiffStrips {
+                    width: self.width,
+                    height: self.height,
+                    band_height,
+                    description: &description,
+                };
+                if width as u64 * height as u64 * BYTES_PER_PIXEL as u64 > BIG_TIFF_BYTES {
+                    tiff.write(TiffEncoder::new_big(writer)?, bands, &mut rgba, next_band)?;
+                } else {
+                    tiff.write(TiffEncoder::new(writer)?, bands, &mut rgba, next_band)?;
+                }
+            }
+        }
+
+        fs::remove_file(&partial_path)?;
+        fs::remove_file(&job_path)?;
+        Ok(())
+    }
+}
+
+struct TiffStrips<'a> {
+    width: u32,
+    height: u32,
+    band_height: u32,
+    description: &'a str,
+}
+
+impl TiffStrips<'_> {
+    fn write<W: Write + Seek, K: TiffKind>(
+        &self,
+        mut encoder: TiffEncoder<W, K>,
+        bands: u32,
+        buf: &mut Vec<u8>,
+        mut next_band: impl FnMut(u32, &mut Vec<u8>) -> io::Result<()>,
+    ) -> Result<(), PosterError> {
+        let mut image = encoder.new_image::<colortype::RGBA8>(self.width, self.height)?;
+        image.rows_per_strip(self.band_height)?;
+        image
+            .encoder()
+            .write_tag(Tag::Software, env!("CARGO_PKG_NAME"))?;
+        image
+            .encoder()
+            .write_tag(Tag::ImageDescription, self.description)?;
+        for index in 0..bands {
+            next_band(index, buf)?;
+            image.write_strip(buf)?;
+        }
+        image.finish()?;
+        Ok(())
+    }
+}
+
+/// Removes the job and scratch files of an interrupted render to `output`.
+pub fn discard(output: &Path) -> io::Result<()> {
+    let (job_path, partial_path) = helper_paths(output);
+    for path in [partial_path, job_path] {
+        match fs::remove_file(path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn helper_paths(output: &Path) -> (PathBuf, PathBuf) {
+    let with_suffix = |suffix: &str| {
+        let mut name = output.as_os_str().to_owned();
+        name.push(suffix);
+        PathBuf::from(name)
+    };
+    (with_suffix(".job.toml"), with_suffix(".partial"))
+}
+
+/// Writes the job file for a new render or checks it against an existing one.
+/// Returns the number of complete bands in the scratch file, which is cut
+/// back to them.
+fn resume(
+    job: &Job,
+    job_path: &Path,
+    partial_path: &Path,
+    band_bytes: u64,
+) -> Result<u32, PosterError> {
+    let fresh = match fs::read_to_string(job_path) {
+        Ok(text) => {
+            let existing: Option<Job> = toml::from_str(&text).ok();
+            if existing.as_ref() != Some(job) {
+                return Err(PosterError::OtherJob(job_path.to_path_buf()));
+            }
+            false
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            let text = toml::to_string_pretty(job).map_err(|e| PosterError::Job(e.to_string()))?;
+            fs::write(job_path, text)?;
+            true
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    let partial = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .truncate(fresh)
+        .open(partial_path)?;
+    let total_bytes = job.width as u64 * job.height as u64 * BYTES_PER_PIXEL as u64;
+    let bands = job.height.div_ceil(job.band_height);
+    let len = partial.metadata()?.len();
+    let done = if len >= total_bytes {
+        bands
+    } else {
+        (len / band_bytes) as u32
+    };
+    partial.set_len(total_bytes.min(done as u64 * band_bytes))?;
+    Ok(done)
+}
+
+#[derive(Debug)]
+pub enum PosterError {
+    /// Empty or too large image, or a zero zoom.
+    Size,
+    /// The job file next to the output describes a different poster.
+    OtherJob(PathBuf),
+    Job(String),
+    Io(io::Error),
+    Png(png::EncodingError),
+    Tiff(tiff::TiffError),
+}
+
+impl fmt::Display for PosterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Size => write!(
+                f,
+                "width, height and zoom must be positive and fit into memory"
+            ),
+            Self::OtherJob(path) => write!(
+                f,
+                "{} belongs to a different render of this output",
+                path.display()
+            ),
+            Self::Job(e) => write!(f, "job file: {e}"),
+            Self::Io(e) => write!(f, "{e}"),
+            Self::Png(e) => write!(f, "png encoding failed: {e}"),
+            Self::Tiff(e) => write!(f, "tiff encoding failed: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for PosterError {}
+
+impl From<io::Error> for PosterError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<png::EncodingError> for PosterError {
+    fn from(e: png::EncodingError) -> Self {
+        Self::Png(e)
+    }
+}
+
+impl From<tiff::TiffError> for PosterError {
+    fn from(e: tiff::TiffError) -> Self {
+        Self::Tiff(e)
+    }
+}
+
+impl From<crate::location::LocationError> for PosterError {
+    fn from(e: crate::location::LocationError) -> Self {
+        Self::Job(e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::{Coloring, RenderParams};
+    use std::panic::{AssertUnwindSafe, catch_unwind};
+
+    /// A scratch directory removed when dropped.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!("poster-{name}-{}", std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            Self(dir)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn poster(format: PosterFormat, coloring: Coloring) -> Poster {
+        Poster {
+            view: ViewDescription {
+                center_x: -0.75,
+                center_y: 0.1,
+                zoom: 40,
+                rotation: 0.,
+                params: RenderParams {
+                    max_iter: 200,
+                    coloring,
+                    ..RenderParams::default()
+                },
+            },
+            width: 61,
+            height: 47,
+            band_height: 10,
+            format,
+        }
+    }
+
+    /// Renders `poster` to `output`, stopping after `bands` bands are on disk
+    /// and cutting the last one in half.
+    fn interrupt(poster: &Poster, output: &Path, bands: u32) {
+        let interrupted = catch_unwind(AssertUnwindSafe(|| {
+            poster.render(output, |p| {
+                if p.stage == Stage::Rendering && p.done == bands {
+                    panic!("interrupted");
+                }
+            })
+        }));
+        assert!(interrupted.is_err());
+
+        let (_, partial_path) = helper_paths(output);
+        let partial = OpenOptions::new().write(true).open(&partial_path).unwrap();
+        let band_bytes = poster.width as u64 * poster.band_height as u64 * 4;
+        assert_eq!(partial.metadata().unwrap().len(), bands as u64 * band_bytes);
+        partial
+            .set_len(bands as u64 * band_bytes - band_bytes / 2)
+            .unwrap();
+    }
+
+    fn resumes_identically(format: PosterFormat, coloring: Coloring, name: &str) {
+        let dir = TempDir::new(name);
+        let poster = poster(format, coloring);
+        let complete = dir.0.join(format!("complete.{name}"));
+        let resumed = dir.0.join(format!("resumed.{name}"));
+
+        poster.render(&complete, |_| {}).unwrap();
+        interrupt(&poster, &resumed, 3);
+
+        let mut first = None;
+        let mut last = None;
+        poster
+            .render(&resumed, |p| {
+                first.get_or_insert((p.stage, p.done));
+                last = Some((p.stage, p.done, p.total));
+            })
+            .unwrap();
+        // the half-written band is rendered again
+        assert_eq!(first, Some((Stage::Rendering, 2)));
+        assert_eq!(last, Some((Stage::Encoding, 5, 5)));
+
+        assert!(fs::read(&complete).unwrap() == fs::read(&resumed).unwrap());
+        let (job_path, partial_path) = helper_paths(&resumed);
+        assert!(!job_path.exists() && !partial_path.exists());
+    }
+
+    #[test]
+    fn resumed_png_matches_an_uninterrupted_render() {
+        resumes_identically(PosterFormat::Png, Coloring::SmoothHistogram, "png");
+    }
+
+    #[test]
+    fn resumed_tiff_matches_an_uninterrupted_render() {
+        resumes_identically(PosterFormat::Tiff, Coloring::EscapeTime, "tiff");
+    }
+
+    #[test]
+    fn rejects_the_helper_files_of_another_poster() {
+        let dir = TempDir::new("other-job");
+        let output = dir.0.join("poster.png");
+        let poster = poster(PosterFormat::Png, Coloring::Smooth);
+        interrupt(&poster, &output, 2);
+        let (job_path, partial_path) = helper_paths(&output);
+        let partial = fs::read(&partial_path).unwrap();
+
+        let mut other = poster;
+        other.view.params.max_iter += 1;
+        match other.render(&output, |_| {}) {
+            Err(PosterError::OtherJob(path)) => assert_eq!(path, job_path),
+            other => panic!("{other:?}"),
+        }
+        // nothing of the interrupted render is touched
+        assert_eq!(fs::read(&partial_path).unwrap(), partial);
+        assert!(!output.exists());
+
+        fs::write(&job_path, "not a job").unwrap();
+        assert!(matches!(
+            poster.render(&output, |_| {}),
+            Err(PosterError::OtherJob(_))
+        ));
+
+        discard(&output).unwrap();
+        poster.render(&output, |_| {}).unwrap();
+        assert!(output.exists());
+    }
+}
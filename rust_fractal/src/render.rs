@@ -0,0 +1,476 @@
+//! Parameterized escape-time rendering into packed ARGB buffers.
+
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::colormap::{BLACK, ColormapMode, get_color, get_rank_color, get_value_color};
+use crate::histogram::{CumulativeHistogram, Histogram};
+use crate::kernel::{Bailout, BailoutNorm, DEFAULT_ESCAPE_RADIUS, Formula, Kernel};
+use crate::pixel::PixelTarget;
+
+/// How iteration counts are turned into palette positions.
+#[repr(u32)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum Coloring {
+    /// Integer iteration count, banded.
+    #[default]
+    EscapeTime = 0,
+    /// Continuous iteration count, no bands. Looks best with a large escape radius.
+    Smooth = 1,
+    /// Integer counts equalized over the frame's histogram.
+    Histogram = 2,
+    /// Continuous counts equalized over the frame's histogram.
+    SmoothHistogram = 3,
+}
+
+enum_names!(Coloring {
+    EscapeTime => "escape-time",
+    Smooth => "smooth",
+    Histogram => "histogram",
+    SmoothHistogram => "smooth-histogram",
+});
+
+impl Coloring {
+    pub fn is_smooth(self) -> bool {
+        matches!(self, Self::Smooth | Self::SmoothHistogram)
+    }
+
+    pub fn is_histogram(self) -> bool {
+        matches!(self, Self::Histogram | Self::SmoothHistogram)
+    }
+}
+
+/// Everything besides the view that affects the rendered image.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct RenderParams {
+    pub formula: Formula,
+    /// Iteration limit, points reaching it are drawn black.
+    pub max_iter: i32,
+    /// Escape radius compared against `bailout`. Radii of 2 or more are exact
+    /// for the Euclidean norm; smooth coloring gets smoother with larger radii.
+    /// Non-finite or non-positive values fall back to 2.
+    pub escape_radius: f64,
+    /// Norm of `z` tested against `escape_radius`.
+    pub bailout: BailoutNorm,
+    pub colormap: ColormapMode,
+    pub coloring: Coloring,
+}
+
+impl Default for RenderParams {
+    fn default() -> Self {
+        Self {
+            formula: Formula::default(),
+            max_iter: 256,
+            escape_radius: DEFAULT_ESCAPE_RADIUS,
+            bailout: BailoutNorm::default(),
+            colormap: ColormapMode::default(),
+            coloring: Coloring::default(),
+        }
+    }
+}
+
+impl RenderParams {
+    pub fn max_iter(&self) -> u32 {
+        self.max_iter.unsigned_abs()
+    }
+
+    pub fn kernel(&self) -> Kernel {
+        Kernel {
+            formula: self.formula,
+            bailout: Bailout::new(self.bailout, self.escape_radius),
+            max_iter: self.max_iter(),
+        }
+    }
+}
+
+/// Maps pixels to world coordinates. Screen Y points down, world Y up.
+#[derive(Clone, Copy, Debug)]
+pub struct Viewport {
+    pub center_x: f64,
+    pub center_y: f64,
+    /// world units per pixel
+    pub inv_zoom: f64,
+    pub width: usize,
+    pub height: usize,
+    /// pixel position of `center_*`, the middle of the view unless it is a `sub_view`
+    origin_x: f64,
+    origin_y: f64,
+    /// cosine and sine of the counter-clockwise view rotation
+    cos: f64,
+    sin: f64,
+}
+
+impl Viewport {
+    /// `None` for empty sizes or a zero zoom.
+    pub fn new(
+        center_x: f64,
+        center_y: f64,
+        zoom: u64,
+        width: usize,
+        height: usize,
+    ) -> Option<Self> {
+        if width == 0 || height == 0 || zoom == 0 {
+            return None;
+        }
+        width.checked_mul(height)?;
+        Some(Self {
+            center_x,
+            center_y,
+            inv_zoom: 1.0 / zoom as f64,
+            width,
+            height,
+            origin_x: width as f64 * 0.5,
+            origin_y: height as f64 * 0.5,
+            cos: 1.,
+            sin: 0.,
+        })
+    }
+
+    /// Rotates the view counter-clockwise around its center.
+    pub fn rotated(self, degrees: f64) -> Self {
+        let (sin, cos) = if degrees.is_finite() {
+            degrees.to_radians().sin_cos()
+        } else {
+            (0., 1.)
+        };
+        Self { cos, sin, ..self }
+    }
+
+    /// Sets a fractional scale in pixels per world unit, for views between
+    /// integer zooms such as animation frames. Ignores non-positive values.
+    pub fn with_zoom(self, zoom: f64) -> Self {
+        if zoom.is_finite() && zoom > 0. {
+            Self {
+                inv_zoom: 1. / zoom,
+                ..self
+            }
+        } else {
+            self
+        }
+    }
+
+    /// The `width x height` pixel region starting at `(x, y)` of this view,
+    /// as a view of its own. Pixel `(0, 0)` of the result is `(x, y)` here.
+    /// Pixels map to exactly the same world coordinates as here.
+    pub fn sub_view(&self, x: usize, y: usize, width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            origin_x: self.origin_x - x as f64,
+            origin_y: self.origin_y - y as f64,
+            ..*self
+        }
+    }
+
+    /// The same world area sampled with `width` pixels across.
+    /// The height keeps the aspect ratio, at least one pixel.
+    pub fn resampled(&self, width: usize) -> Self {
+        let width = width.max(1);
+        let scale = self.width as f64 / width as f64;
+        Self {
+            inv_zoom: self.inv_zoom * scale,
+            width,
+            height: ((self.height as f64 / scale).round() as usize).max(1),
+            origin_x: self.origin_x / scale,
+            origin_y: self.origin_y / scale,
+            ..*self
+        }
+    }
+
+    pub fn pixel_count(&self) -> usize {
+        self.width * self.height
+    }
+
+    /// World coordinates of a (sub)pixel position.
+    #[inline]
+    pub fn world(&self, px: f64, py: f64) -> (f64, f64) {
+        let (dx, dy) = self.offset(px, py);
+        (self.center_x + dx, self.center_y + dy)
+    }
+
+    /// Inverse of [`Viewport::offset`]: the (sub)pixel position of a world
+    /// offset from `center_*`.
+    #[inline]
+    pub fn pixel_at_offset(&self, dx: f64, dy: f64) -> (f64, f64) {
+        let rx = dx * self.cos + dy * self.sin;
+        let ry = -dx * self.sin + dy * self.cos;
+        (
+            self.origin_x + rx / self.inv_zoom,
+            self.origin_y - ry / self.inv_zoom,
+        )
+    }
+
+    /// World offset of a (sub)pixel position from `center_*`, exact even
+    /// where adding it to the center would round.
+    #[inline]
+    pub fn offset(&self, px: f64, py: f64) -> (f64, f64) {
+        let dx = (px - self.origin_x) * self.inv_zoom;
+        let dy = -(py - self.origin_y) * self.inv_zoom;
+        (dx * self.cos - dy * self.sin, dx * self.sin + dy * self.cos)
+    }
+}
+
+/// Renders `view` into `pixels` (at least `view.pixel_count()` long, row stride = width).
+pub fn render_argb(view: &Viewport, params: &RenderParams, pixels: &mut [u32]) {
+    if let Some(mut target) =
+        PixelTarget::from_argb(&mut pixels[..view.pixel_count()], view.width, view.height)
+    {
+        render_into(view, params, &mut target);
+    }
+}
+
+/// Renders `view` into a target of the same size in any pixel format.
+pub fn render_into(view: &Viewport, params: &RenderParams, target: &mut PixelTarget) {
+    debug_assert_eq!((target.width(), target.height()), (view.width, view.height));
+    if params.coloring.is_histogram() {
+        let mut values = vec![0f32; view.pixel_count()];
+        iterate_values(view, params, &mut values);
+        colorize_into(&values, params, target);
+        return;
+    }
+
+    let max_iter = params.max_iter();
+    let kernel = params.kernel();
+    let smooth = params.coloring.is_smooth();
+
+    // parallelize by rows, like C# Parallel.For over py
+    target.par_rows().for_each(|(py, mut row)| {
+        for px in 0..row.width() {
+            let (x_world, y_world) = view.world(px as f64, py as f64);
+
+            let color = if smooth {
+                let value = kernel.iterate_smooth(x_world, y_world);
+                get_value_color(value, max_iter, params.colormap)
+            } else {
+                let iter = kernel.iterate(x_world, y_world);
+                get_color(iter, max_iter, params.colormap)
+            };
+            row.set(px, color);
+        }
+    });
+}
+
+/// Fills `values` (at least `view.pixel_count()` long) with iteration values,
+/// continuous ones for smooth colorings. Interior points hold `max_iter`.
+pub fn iterate_values(view: &Viewport, params: &RenderParams, values: &mut [f32]) {
+    let kernel = params.kernel();
+    let smooth = params.coloring.is_smooth();
+
+    values[..view.pixel_count()]
+        .par_chunks_mut(view.width)
+        .enumerate()
+        .for_each(|(py, row)| {
+            for (px, out) in row.iter_mut().enumerate() {
+                let (x_world, y_world) = view.world(px as f64, py as f64);
+                *out = kernel.iterate_value(x_world, y_world, smooth);
+            }
+        });
+}
+
+/// Reuses the iteration values of the previous view after a pan: `values`
+/// (stride = width) held the view the content of which moved `dx` pixels right
+/// and `dy` down to become `view`. The overlap is shifted in place and only the
+/// newly exposed strips are iterated. Shifts as large as the view recompute
+/// everything. Returns the number of pixels iterated.
+pub fn scroll_values(
+    view: &Viewport,
+    params: &RenderParams,
+    values: &mut [f32],
+    dx: isize,
+    dy: isize,
+) -> usize {
+    let (width, height) = (view.width, view.height);
+    let values = &mut values[..view.pixel_count()];
+    if dx.unsigned_abs() >= width || dy.unsigned_abs() >= height {
+        iterate_values(view, params, values);
+        return values.len();
+    }
+
+    // destination columns that keep old values
+    let kept = dx.max(0) as usize..(width as isize + dx.min(0)) as usize;
+    let source_start = (kept.start as isize - dx) as usize;
+    let mut shift_row = |y: usize| {
+        let source_row = (y as isize - dy) as usize;
+        values.copy_within(
+            source_row * width + source_start..source_row * width + source_start + kept.len(),
+            y * width + kept.start,
+        );
+    };
+    // rows that keep old values, walked so no source row is overwritten first
+    let kept_rows = dy.max(0) as usize..(height as isize + dy.min(0)) as usize;
+    if dy > 0 {
+        kept_rows.clone().rev().for_each(&mut shift_row);
+    } else {
+        kept_rows.clone().for_each(&mut shift_row);
+    }
+
+    let kernel = params.kernel();
+    let smooth = params.coloring.is_smooth();
+    values
+        .par_chunks_mut(width)
+        .enumerate()
+        .map(|(py, row)| {
+            let mut computed = 0;
+            for (px, out) in row.iter_mut().enumerate() {
+                if kept_rows.contains(&py) && kept.contains(&px) {
+                    continue;
+                }
+                let (x_world, y_world) = view.world(px as f64, py as f64);
+                *out = kernel.iterate_value(x_world, y_world, smooth);
+                computed += 1;
+            }
+            computed
+        })
+        .sum()
+}
+
+/// Colors an iteration buffer laid out like the target (stride = width).
+/// Histogram colorings equalize over the histogram of `values` itself.
+pub fn colorize_into(values: &[f32], params: &RenderParams, target: &mut PixelTarget) {
+    if params.coloring.is_histogram() {
+        let width = target.width();
+        let values = &values[..width * target.height()];
+        let histogram = frame_histogram(values, width, params.max_iter());
+        colorize_with_histogram(values, params, target, &histogram);
+        return;
+    }
+
+    let width = target.width();
+    let max_iter = params.max_iter();
+    target.par_rows().for_each(|(py, mut row)| {
+        for (px, &value) in values[py * width..(py + 1) * width].iter().enumerate() {
+            row.set(px, get_value_color(value as f64, max_iter, params.colormap));
+        }
+    });
+}
+
+/// Collects one histogram per rayon worker over `width` wide rows of `values`
+/// and merges them once the pass is done.
+pub fn frame_histogram(values: &[f32], width: usize, max_iter: u32) -> CumulativeHistogram {
+    values
+        .par_chunks(width)
+        .fold(
+            || Histogram::new(max_iter),
+            |mut hist, row| {
+                for &value in row {
+                    hist.add(value as f64);
+                }
+                hist
+            },
+        )
+        .reduce(|| Histogram::new(max_iter), Histogram::merge)
+        .into_cumulative()
+}
+
+/// Histogram of `view` sampled `preview_width` pixels across. Images rendered
+/// in parts share it so every part is equalized the same way.
+pub fn preview_histogram(
+    view: &Viewport,
+    params: &RenderParams,
+    preview_width: usize,
+) -> CumulativeHistogram {
+    let preview = view.resampled(preview_width.min(view.width));
+    let mut values = vec![0f32; preview.pixel_count()];
+    iterate_values(&preview, params, &mut values);
+    frame_histogram(&values, preview.width, params.max_iter())
+}
+
+/// Colors every pixel by its rank within `histogram`, which may come from a
+/// different (e.g. preview or whole-image) buffer than `values`.
+pub fn colorize_with_histogram(
+    values: &[f32],
+    params: &RenderParams,
+    target: &mut PixelTarget,
+    histogram: &CumulativeHistogram,
+) {
+    let width = target.width();
+    let max_iter = params.max_iter() as f64;
+    target.par_rows().for_each(|(py, mut row)| {
+        for (px, &value) in values[py * width..(py + 1) * width].iter().enumerate() {
+            let value = value as f64;
+            let color = if value >= max_iter {
+                BLACK
+            } else {
+                get_rank_color(histogram.rank(value))
+            };
+            row.set(px, color);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ZOOM: u64 = 256;
+
+    fn params() -> RenderParams {
+        RenderParams {
+            max_iter: 300,
+            coloring: Coloring::Smooth,
+            ..RenderParams::default()
+        }
+    }
+
+    /// Values of the view centered on `(-0.5 - x / ZOOM, y / ZOOM)`. Dyadic
+    /// centers keep every world coordinate exact, so shifted views hit the
+    /// same points bit for bit.
+    fn values_at(x: isize, y: isize, width: usize, height: usize) -> (Viewport, Vec<f32>) {
+        let view = Viewport::new(
+            -0.5 - x as f64 / ZOOM as f64,
+            y as f64 / ZOOM as f64,
+            ZOOM,
+            width,
+            height,
+        )
+        .unwrap();
+        let mut values = vec![0f32; view.pixel_count()];
+        iterate_values(&view, &params(), &mut values);
+        (view, values)
+    }
+
+    #[test]
+    fn scrolled_values_match_a_fresh_render() {
+        let (width, height) = (37, 23);
+        for dx in [-9, -1, 0, 1, 9] {
+            for dy in [-7, -1, 0, 1, 7] {
+                let (_, mut values) = values_at(0, 0, width, height);
+                // content moving right and down means the view moved left and up
+                let (view, fresh) = values_at(dx, dy, width, height);
+                let iterated = scroll_values(&view, &params(), &mut values, dx, dy);
+                assert!(values == fresh, "dx {dx} dy {dy}");
+
+                let kept = (width - dx.unsigned_abs()) * (height - dy.unsigned_abs());
+                assert_eq!(iterated, width * height - kept, "dx {dx} dy {dy}");
+            }
+        }
+    }
+
+    #[test]
+    fn scrolling_a_whole_view_away_iterates_everything() {
+        let (width, height) = (16, 12);
+        for (dx, dy) in [(16, 0), (-16, 3), (2, 12), (0, -40)] {
+            let (_, mut values) = values_at(0, 0, width, height);
+            let (view, fresh) = values_at(dx, dy, width, height);
+            assert_eq!(
+                scroll_values(&view, &params(), &mut values, dx, dy),
+                width * height
+            );
+            assert!(values == fresh, "dx {dx} dy {dy}");
+        }
+    }
+
+    #[test]
+    fn scrolling_ignores_values_past_the_view() {
+        let (width, height) = (10, 8);
+        let (_, mut values) = values_at(0, 0, width, height);
+        values.extend([-1f32; 5]);
+        let (view, fresh) = values_at(3, -2, width, height);
+        scroll_values(&view, &params(), &mut values, 3, -2);
+        assert!(values[..width * height] == fresh[..]);
+        assert_eq!(values[width * height..], [-1f32; 5]);
+    }
+}
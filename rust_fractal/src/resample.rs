@@ -0,0 +1,395 @@
+//! Instant zoom previews from the previous frame.
+//!
+//! [`preview_argb`] / [`preview_values`] resample the previous frame into the
+//! new view, clamping to its edges, so a pinch gesture shows something right
+//! away. The matching refine pass then produces the final frame: pixels that
+//! fall on a source pixel, up to [`ALIGNED_EPSILON`], take its value, all
+//! others are iterated. The result matches a fresh render, so repeated zoom
+//! gestures do not drift, and a zoom by an integer factor around a pixel still
+//! reuses part of the frame.
+//!
+//! Both frames are expected to use the same render parameters.
+
+use rayon::prelude::*;
+
+use crate::colormap::{get_color, get_value_color};
+use crate::render::{RenderParams, Viewport, render_argb};
+
+#[repr(u32)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Resampling {
+    #[default]
+    Nearest = 0,
+    Bilinear = 1,
+}
+
+/// Position of pixel `(px, py)` of `to` in pixels of `from`.
+#[inline]
+fn source_position(from: &Viewport, to: &Viewport, px: usize, py: usize) -> (f64, f64) {
+    let (dx, dy) = to.offset(px as f64, py as f64);
+    from.pixel_at_offset(
+        dx + (to.center_x - from.center_x),
+        dy + (to.center_y - from.center_y),
+    )
+}
+
+/// Largest distance in source pixels at which a refined pixel still takes
+/// over a source pixel. Only covers the rounding of the view transforms, the
+/// world positions differ by less than `1e-6 / zoom`.
+pub const ALIGNED_EPSILON: f64 = 1e-6;
+
+/// Source pixel a refined pixel can take over, `None` where it must be iterated.
+#[inline]
+fn reusable(from: &Viewport, x: f64, y: f64) -> Option<usize> {
+    let (rx, ry) = (x.round(), y.round());
+    let aligned = (x - rx).abs() <= ALIGNED_EPSILON && (y - ry).abs() <= ALIGNED_EPSILON;
+    let inside = rx >= 0. && ry >= 0. && rx < from.width as f64 && ry < from.height as f64;
+    (aligned && inside).then(|| ry as usize * from.width + rx as usize)
+}
+
+/// Corner indices and weights of a bilinear sample, clamped to the frame.
+#[inline]
+fn bilinear(from: &Viewport, x: f64, y: f64) -> ([usize; 4], f32, f32) {
+    let x = x.clamp(0., (from.width - 1) as f64);
+    let y = y.clamp(0., (from.height - 1) as f64);
+    let (x0, y0) = (x.floor() as usize, y.floor() as usize);
+    let (x1, y1) = ((x0 + 1).min(from.width - 1), (y0 + 1).min(from.height - 1));
+    let at = |x: usize, y: usize| y * from.width + x;
+    (
+        [at(x0, y0), at(x1, y0), at(x0, y1), at(x1, y1)],
+        (x - x0 as f64) as f32,
+        (y - y0 as f64) as f32,
+    )
+}
+
+#[inline]
+fn nearest(from: &Viewport, x: f64, y: f64) -> usize {
+    let x = x.round().clamp(0., (from.width - 1) as f64) as usize;
+    let y = y.round().clamp(0., (from.height - 1) as f64) as usize;
+    y * from.width + x
+}
+
+/// Resamples the previous frame's ARGB pixels `src` (view `from`) into `dst`
+/// (view `to`), channel by channel for bilinear sampling.
+pub fn preview_argb(
+    from: &Viewport,
+    src: &[u32],
+    to: &Viewport,
+    mode: Resampling,
+    dst: &mut [u32],
+) {
+    let src = &src[..from.pixel_count()];
+    dst[..to.pixel_count()]
+        .par_chunks_mut(to.width)
+        .enumerate()
+        .for_each(|(py, row)| {
+            for (px, out) in row.iter_mut().enumerate() {
+                let (x, y) = source_position(from, to, px, py);
+                *out = match mode {
+                    Resampling::Nearest => src[nearest(from, x, y)],
+                    Resampling::Bilinear => {
+                        let (corners, fx, fy) = bilinear(from, x, y);
+                        blend_argb(corners.map(|i| src[i]), fx, fy)
+                    }
+                };
+            }
+        });
+}
+
+#[inline]
+fn blend_argb(corners: [u32; 4], fx: f32, fy: f32) -> u32 {
+    let mut out = 0;
+    for shift in [0, 8, 16, 24] {
+        let [a, b, c, d] = corners.map(|p| ((p >> shift) & 0xff) as f32);
+        let top = a + (b - a) * fx;
+        let bottom = c + (d - c) * fx;
+        let channel = (top + (bottom - top) * fy).round() as u32;
+        out |= channel.min(0xff) << shift;
+    }
+    out
+}
+
+/// Resamples the previous frame's iteration values `src` (view `from`) into
+/// `dst` (view `to`). Bilinear sampling falls back to nearest next to interior
+/// points, values `>= max_iter`, so the set's edge stays sharp.
+pub fn preview_values(
+    from: &Viewport,
+    src: &[f32],
+    to: &Viewport,
+    mode: Resampling,
+    max_iter: u32,
+    dst: &mut [f32],
+) {
+    let src = &src[..from.pixel_count()];
+    let max_iter = max_iter as f32;
+    dst[..to.pixel_count()]
+        .par_chunks_mut(to.width)
+        .enumerate()
+        .for_each(|(py, row)| {
+            for (px, out) in row.iter_mut().enumerate() {
+                let (x, y) = source_position(from, to, px, py);
+                *out = match mode {
+                    Resampling::Nearest => src[nearest(from, x, y)],
+                    Resampling::Bilinear => {
+                        let (corners, fx, fy) = bilinear(from, x, y);
+                        let [a, b, c, d] = corners.map(|i| src[i]);
+                        if [a, b, c, d].iter().any(|&v| v >= max_iter) {
+                            src[nearest(from, x, y)]
+                        } else {
+                            let top = a + (b - a) * fx;
+                            let bottom = c + (d - c) * fx;
+                            top + (bottom - top) * fy
+                        }
+                    }
+                };
+            }
+        });
+}
+
+/// Turns a preview in `dst` into the final ARGB frame, see the module docs.
+/// Histogram colorings depend on the whole frame and render it completely.
+/// Returns the number of pixels iterated.
+pub fn refine_argb(
+    from: &Viewport,
+    src: &[u32],
+    to: &Viewport,
+    params: &RenderParams,
+    dst: &mut [u32],
+) -> usize {
+    if params.coloring.is_histogram() {
+        render_argb(to, params, dst);
+        return to.pixel_count();
+    }
+
+    let kernel = params.kernel();
+    let max_iter = params.max_iter();
+    let smooth = params.coloring.is_smooth();
+    refine(
+        from,
+        to,
+        dst,
+        |index| src[index],
+        |x, y| {
+            if smooth {
+                get_value_color(kernel.iterate_smooth(x, y), max_iter, params.colormap)
+            } else {
+                get_color(kernel.iterate(x, y), max_iter, params.colormap)
+            }
+        },
+    )
+}
+
+/// Turns a preview in `dst` into the final iteration values, see the module
+/// docs. Returns the number of pixels iterated.
+pub fn refine_values(
+    from: &Viewport,
+    src: &[f32],
+    to: &Viewport,
+    params: &RenderParams,
+    dst: &mut [f32],
+) -> usize {
+    let kernel = params.kernel();
+    let smooth = params.coloring.is_smooth();
+    refine(
+        from,
+        to,
+        dst,
+        |index| src[index],
+        |x, y| kernel.iterate_value(x, y, smooth),
+    )
+}
+
+fn refine<T: Send>(
+    from: &Viewport,
+    to: &Viewport,
+    dst: &mut [T],
+    reuse: impl Fn(usize) -> T + Sync,
+    compute: impl Fn(f64, f64) -> T + Sync,
+) -> usize {
+    dst[..to.pixel_count()]
+        .par_chunks_mut(to.width)
+        .enumerate()
+        .map(|(py, row)| {
+            let mut computed = 0;
+            for (px, out) in row.iter_mut().enumerate() {
+                let (x, y) = source_position(from, to, px, py);
+                *out = match reusable(from, x, y) {
+                    Some(index) => reuse(index),
+                    None => {
+                        computed += 1;
+                        let (x_world, y_world) = to.world(px as f64, py as f64);
+                        compute(x_world, y_world)
+                    }
+                };
+            }
+            computed
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::{Coloring, iterate_values};
+
+    const WIDTH: usize = 48;
+    const HEIGHT: usize = 32;
+
+    fn params(coloring: Coloring) -> RenderParams {
+        RenderParams {
+            max_iter: 300,
+            coloring,
+            ..RenderParams::default()
+        }
+    }
+
+    /// Dyadic centers and zooms keep the world coordinates of aligned pixels
+    /// exact, so reused values equal freshly iterated ones bit for bit.
+    fn view(center_x: f64, zoom: u64) -> Viewport {
+        Viewport::new(center_x, 0.125, zoom, WIDTH, HEIGHT).unwrap()
+    }
+
+    fn values(view: &Viewport) -> Vec<f32> {
+        let mut values = vec![0f32; view.pixel_count()];
+        iterate_values(view, &params(Coloring::Smooth), &mut values);
+        values
+    }
+
+    fn preview(from: &Viewport, to: &Viewport, mode: Resampling) -> Vec<f32> {
+        let mut dst = vec![0f32; to.pixel_count()];
+        preview_values(from, &values(from), to, mode, 300, &mut dst);
+        dst
+    }
+
+    fn refined(from: &Viewport, to: &Viewport) -> (Vec<f32>, usize) {
+        let mut dst = preview(from, to, Resampling::Bilinear);
+        let iterated = refine_values(from, &values(from), to, &params(Coloring::Smooth), &mut dst);
+        (dst, iterated)
+    }
+
+    #[test]
+    fn previews_of_the_same_view_copy_it() {
+        let view = view(-0.75, 256);
+        for mode in [Resampling::Nearest, Resampling::Bilinear] {
+            assert!(preview(&view, &view, mode) == values(&view), "{mode:?}");
+        }
+    }
+
+    #[test]
+    fn nearest_previews_take_aligned_pixels_and_clamp_to_the_edges() {
+        // zooming out by two, the inner half of the new view lies on source pixels
+        let (from, to) = (view(-0.75, 512), view(-0.75, 256));
+        let (source, fresh) = (values(&from), values(&to));
+        let dst = preview(&from, &to, Resampling::Nearest);
+        for y in 0..HEIGHT {
+            for x in 0..WIDTH {
+                let (sx, sy) = (2 * x as isize - 24, 2 * y as isize - 16);
+                let clamped = sy.clamp(0, HEIGHT as isize - 1) as usize * WIDTH
+                    + sx.clamp(0, WIDTH as isize - 1) as usize;
+                assert_eq!(dst[y * WIDTH + x], source[clamped], "({x}, {y})");
+                if (12..36).contains(&x) && (8..24).contains(&y) {
+                    assert_eq!(dst[y * WIDTH + x], fresh[y * WIDTH + x], "({x}, {y})");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn bilinear_previews_blend_between_source_pixels() {
+        // zooming in by two, every other pixel lies halfway between two source pixels
+        let (from, to) = (view(-0.75, 256), view(-0.75, 512));
+        let source = values(&from);
+        let dst = preview(&from, &to, Resampling::Bilinear);
+        let mut blended = 0;
+        for y in (0..HEIGHT).step_by(2) {
+            for x in (1..WIDTH).step_by(2) {
+                let at = |x: usize, y: usize| source[(y / 2 + 8) * WIDTH + x / 2 + 12];
+                let (left, right) = (at(x - 1, y), at(x + 1, y));
+                // the corners below carry no weight but still count as neighbours
+                let corners = [left, right, at(x - 1, y + 2), at(x + 1, y + 2)];
+                let expected = if corners.iter().any(|&v| v >= 300.) {
+                    // next to interior points the nearest one, rounding up
+                    right
+                } else {
+                    blended += 1;
+                    (left + right) / 2.
+                };
+                assert!((dst[y * WIDTH + x] - expected).abs() < 1e-3, "({x}, {y})");
+            }
+        }
+        assert!(blended > 0);
+    }
+
+    #[test]
+    fn refined_frames_match_fresh_renders() {
+        for (from, to, reused) in [
+            // zooming in by two reuses every other pixel of every other row
+            (view(-0.75, 256), view(-0.75, 512), WIDTH * HEIGHT / 4),
+            // zooming out by two reuses the inner half
+            (view(-0.75, 512), view(-0.75, 256), WIDTH * HEIGHT / 4),
+            // panning by whole pixels reuses the overlap
+            (
+                view(-0.75, 256),
+                view(-0.75 + 5. / 256., 256),
+                (WIDTH - 5) * HEIGHT,
+            ),
+            // zooming in by 3 / 2 reuses every third pixel of every third row
+            (
+                view(-0.75, 256),
+                view(-0.75, 384),
+                (WIDTH / 3) * (HEIGHT / 3 + 1),
+            ),
+        ] {
+            let (dst, iterated) = refined(&from, &to);
+            assert!(dst == values(&to), "{} -> {}", from.inv_zoom, to.inv_zoom);
+            assert_eq!(iterated, WIDTH * HEIGHT - reused);
+        }
+    }
+
+    #[test]
+    fn repeated_refines_do_not_drift() {
+        // zooming in and out again by a factor that aligns few pixels
+        let views = [256, 320, 400, 320, 256, 200, 256].map(|zoom| view(-0.75, zoom));
+        let mut current = values(&views[0]);
+        for pair in views.windows(2) {
+            let mut dst = vec![0f32; pair[1].pixel_count()];
+            preview_values(
+                &pair[0],
+                &current,
+                &pair[1],
+                Resampling::Nearest,
+                300,
+                &mut dst,
+            );
+            refine_values(
+                &pair[0],
+                &current,
+                &pair[1],
+                &params(Coloring::Smooth),
+                &mut dst,
+            );
+            current = dst;
+        }
+        assert!(current == values(&views[0]));
+    }
+
+    #[test]
+    fn refined_colors_match_fresh_renders() {
+        let (from, to) = (view(-0.75, 512), view(-0.75, 256));
+        for coloring in [Coloring::EscapeTime, Coloring::Smooth, Coloring::Histogram] {
+            let params = params(coloring);
+            let mut src = vec![0u32; from.pixel_count()];
+            render_argb(&from, &params, &mut src);
+            let mut fresh = vec![0u32; to.pixel_count()];
+            render_argb(&to, &params, &mut fresh);
+
+            let mut dst = vec![0u32; to.pixel_count()];
+            preview_argb(&from, &src, &to, Resampling::Nearest, &mut dst);
+            let iterated = refine_argb(&from, &src, &to, &params, &mut dst);
+            assert!(dst == fresh, "{coloring:?}");
+            // histogram colorings depend on the whole frame
+            let expected = if coloring.is_histogram() { 4 } else { 3 };
+            assert_eq!(iterated, WIDTH * HEIGHT * expected / 4, "{coloring:?}");
+        }
+    }
+}
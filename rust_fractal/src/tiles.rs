@@ -0,0 +1,359 @@
+//! Slippy-map style tiles with a cache of their iteration values.
+//!
+//! Level 0 is one tile covering the square `-2.5..1.5 × -2..2`, every level
+//! halves the tile size. Tile `x` grows to the right and `y` downwards from
+//! the top left corner of that square; coordinates outside `0..2^z` continue
+//! the grid so the whole plane can be panned over.
+//!
+//! The cache keeps iteration values rather than colors, so palette changes
+//! only recolor. Histogram colorings need the whole view and are colored
+//! like their non-histogram counterparts here. [`SharedTileCache`] only
+//! locks the cache to look tiles up and to insert them, tiles missing from
+//! it are iterated in parallel.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use crate::kernel::{BailoutNorm, Formula};
+use crate::pixel::PixelTarget;
+use crate::render::{Coloring, RenderParams, Viewport, colorize_into, iterate_values};
+
+/// Width and height of a tile in pixels.
+pub const TILE_SIZE: usize = 256;
+
+/// Deepest level. A pixel spans `2^(-6-z)` world units and shrinks to
+/// `ulp(1.0) = 2^-52` at `z = 46`; at 40 it still spans 64 `f64` steps near
+/// `|c| = 1`, and 32 near the level 0 edge at `re = -2.5`.
+pub const MAX_LEVEL: u32 = 40;
+
+/// Default cache budget, 256 tiles.
+pub const DEFAULT_CACHE_BYTES: usize = 64 << 20;
+
+const LEVEL0_LEFT: f64 = -2.5;
+const LEVEL0_TOP: f64 = 2.;
+const LEVEL0_SPAN: f64 = 4.;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TileKey {
+    pub z: u32,
+    pub x: i64,
+    pub y: i64,
+}
+
+impl TileKey {
+    /// `None` past [`MAX_LEVEL`].
+    pub fn new(z: u32, x: i64, y: i64) -> Option<Self> {
+        (z <= MAX_LEVEL).then_some(Self { z, x, y })
+    }
+
+    /// Pixels per world unit at this level.
+    pub fn zoom(&self) -> f64 {
+        TILE_SIZE as f64 * (1u64 << self.z) as f64 / LEVEL0_SPAN
+    }
+
+    pub fn viewport(&self) -> Viewport {
+        let span = LEVEL0_SPAN / (1u64 << self.z) as f64;
+        let center_x = LEVEL0_LEFT + (self.x as f64 + 0.5) * span;
+        let center_y = LEVEL0_TOP - (self.y as f64 + 0.5) * span;
+        Viewport::new(center_x, center_y, 1, TILE_SIZE, TILE_SIZE)
+            .expect("tile size is positive")
+            .with_zoom(self.zoom())
+    }
+}
+
+/// The render parameters iteration values depend on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+struct ValuesKey {
+    formula: Formula,
+    max_iter: u32,
+    escape_radius: u64,
+    bailout: BailoutNorm,
+    smooth: bool,
+}
+
+impl ValuesKey {
+    fn new(params: &RenderParams) -> Self {
+        Self {
+            formula: params.formula,
+            max_iter: params.max_iter(),
+            escape_radius: params.escape_radius.to_bits(),
+            bailout: params.bailout,
+            smooth: params.coloring.is_smooth(),
+        }
+    }
+}
+
+struct Entry {
+    values: Arc<[f32]>,
+    last_used: u64,
+}
+
+/// Least recently used iteration tiles, bounded by the bytes of their values.
+pub struct TileCache {
+    capacity_bytes: usize,
+    used_bytes: usize,
+    entries: HashMap<(TileKey, ValuesKey), Entry>,
+    /// `last_used` -> key, oldest first
+    by_age: BTreeMap<u64, (TileKey, ValuesKey)>,
+    clock: u64,
+}
+
+impl TileCache {
+    pub fn new(capacity_bytes: usize) -> Self {
+        Self {
+            capacity_bytes,
+            used_bytes: 0,
+            entries: HashMap::new(),
+            by_age: BTreeMap::new(),
+            clock: 0,
+        }
+    }
+
+    pub fn used_bytes(&self) -> usize {
+        self.used_bytes
+    }
+
+    /// Shrinks the cache right away if it holds more than `capacity_bytes`.
+    pub fn set_capacity(&mut self, capacity_bytes: usize) {
+        self.capacity_bytes = capacity_bytes;
+        self.evict(0);
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.by_age.clear();
+        self.used_bytes = 0;
+    }
+
+    /// The cached values of a tile, marking them as most recently used.
+    pub fn get(&mut self, key: TileKey, params: &RenderParams) -> Option<Arc<[f32]>> {
+        let cache_key = (key, ValuesKey::new(params));
+        let entry = self.entries.get_mut(&cache_key)?;
+        self.clock += 1;
+        self.by_age.remove(&entry.last_used);
+        entry.last_used = self.clock;
+        self.by_age.insert(self.clock, cache_key);
+        Some(entry.values.clone())
+    }
+
+    /// Caches the values of a tile as most recently used, evicting the
+    /// oldest tiles to make room. Values larger than the whole budget are
+    /// not cached.
+    pub fn insert(&mut self, key: TileKey, params: &RenderParams, values: Arc<[f32]>) {
+        let cache_key = (key, ValuesKey::new(params));
+        // another thread may have computed the same tile meanwhile
+        if let Some(entry) = self.entries.remove(&cache_key) {
+            self.by_age.remove(&entry.last_used);
+            self.used_bytes -= size_of_val(&*entry.values);
+        }
+
+        let bytes = size_of_val(&*values);
+        if bytes > self.capacity_bytes {
+            return;
+        }
+        self.evict(bytes);
+        self.clock += 1;
+        self.used_bytes += bytes;
+        self.by_age.insert(self.clock, cache_key);
+        self.entries.insert(
+            cache_key,
+            Entry {
+                values,
+                last_used: self.clock,
+            },
+        );
+    }
+
+    /// `TILE_SIZE * TILE_SIZE` iteration values of a tile, computed on a miss.
+    pub fn values(&mut self, key: TileKey, params: &RenderParams) -> Arc<[f32]> {
+        if let Some(values) = self.get(key, params) {
+            return values;
+        }
+        let values = compute_values(key, params);
+        self.insert(key, params, values.clone());
+        values
+    }
+
+    /// Drops the oldest tiles until `incoming` more bytes fit.
+    fn evict(&mut self, incoming: usize) {
+        while self.used_bytes + incoming > self.capacity_bytes {
+            let Some((_, cache_key)) = self.by_age.pop_first() else {
+                break;
+            };
+            if let Some(entry) = self.entries.remove(&cache_key) {
+                self.used_bytes -= size_of_val(&*entry.values);
+            }
+        }
+    }
+}
+
+impl Default for TileCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_CACHE_BYTES)
+    }
+}
+
+/// A [`TileCache`] shared between threads that iterates missing tiles
+/// without holding its lock. Two threads missing the same tile both iterate
+/// it, the second insert replaces the first.
+#[derive(Default)]
+pub struct SharedTileCache {
+    cache: Mutex<TileCache>,
+}
+
+impl SharedTileCache {
+    pub fn new(capacity_bytes: usize) -> Self {
+        Self {
+            cache: Mutex::new(TileCache::new(capacity_bytes)),
+        }
+    }
+
+    pub fn lock(&self) -> MutexGuard<'_, TileCache> {
+        // entries are only inserted complete, a panic elsewhere leaves none half done
+        self.cache.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// [`TileCache::values`] with the lock released while iterating.
+    pub fn values(&self, key: TileKey, params: &RenderParams) -> Arc<[f32]> {
+        if let Some(values) = self.lock().get(key, params) {
+            return values;
+        }
+        let values = compute_values(key, params);
+        self.lock().insert(key, params, values.clone());
+        values
+    }
+
+    /// Renders a tile into a `TILE_SIZE` square target.
+    pub fn render_into(&self, key: TileKey, params: &RenderParams, target: &mut PixelTarget) {
+        let values = self.values(key, params);
+        let params = RenderParams {
+            coloring: if params.coloring.is_smooth() {
+                Coloring::Smooth
+            } else {
+                Coloring::EscapeTime
+            },
+            ..*params
+        };
+        colorize_into(&values, &params, target);
+    }
+}
+
+fn compute_values(key: TileKey, params: &RenderParams) -> Arc<[f32]> {
+    let view = key.viewport();
+    let mut values = vec![0f32; view.pixel_count()];
+    iterate_values(&view, params, &mut values);
+    values.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `len` values, `4 * len` bytes.
+    fn values(len: usize) -> Arc<[f32]> {
+        vec![len as f32; len].into()
+    }
+
+    fn key(x: i64) -> TileKey {
+        TileKey::new(3, x, 0).unwrap()
+    }
+
+    fn cached(cache: &mut TileCache, params: &RenderParams) -> Vec<i64> {
+        (0..8)
+            .filter(|&x| cache.get(key(x), params).is_some())
+            .collect()
+    }
+
+    #[test]
+    fn evicts_least_recently_used_tiles_to_stay_in_budget() {
+        let params = RenderParams::default();
+        let mut cache = TileCache::new(3 * 400);
+        for x in 0..3 {
+            cache.insert(key(x), &params, values(100));
+        }
+        assert_eq!(cache.used_bytes(), 1200);
+
+        // touching 0 leaves 1 as the oldest
+        assert!(cache.get(key(0), &params).is_some());
+        cache.insert(key(3), &params, values(100));
+        assert_eq!(cache.used_bytes(), 1200);
+        assert!(cache.get(key(1), &params).is_none());
+
+        // a tile twice the size pushes out the two oldest, 2 then 0
+        cache.insert(key(4), &params, values(200));
+        assert_eq!(cache.used_bytes(), 1200);
+        assert!(cache.get(key(2), &params).is_none());
+        assert!(cache.get(key(0), &params).is_none());
+        assert_eq!(cached(&mut cache, &params), [3, 4]);
+    }
+
+    #[test]
+    fn keeps_the_budget_on_reinserts_and_oversized_tiles() {
+        let params = RenderParams::default();
+        let mut cache = TileCache::new(1000);
+        cache.insert(key(0), &params, values(100));
+        cache.insert(key(0), &params, values(150));
+        assert_eq!(cache.used_bytes(), 600);
+        assert_eq!(cache.get(key(0), &params).unwrap().len(), 150);
+
+        cache.insert(key(1), &params, values(300));
+        assert_eq!(cache.used_bytes(), 600);
+        assert_eq!(cached(&mut cache, &params), [0]);
+    }
+
+    #[test]
+    fn shrinking_keeps_the_newest_tiles() {
+        let params = RenderParams::default();
+        let mut cache = TileCache::new(1600);
+        for x in 0..4 {
+            cache.insert(key(x), &params, values(100));
+        }
+        cache.set_capacity(800);
+        assert_eq!(cache.used_bytes(), 800);
+        assert_eq!(cached(&mut cache, &params), [2, 3]);
+
+        cache.clear();
+        assert_eq!(cache.used_bytes(), 0);
+        assert!(cached(&mut cache, &params).is_empty());
+    }
+
+    #[test]
+    fn deepest_tiles_keep_pixels_apart() {
+        assert!(TileKey::new(MAX_LEVEL + 1, 0, 0).is_none());
+        // the leftmost tile of the deepest level, where f64 steps are largest
+        let tile = TileKey::new(MAX_LEVEL, 0, 1 << (MAX_LEVEL - 1)).unwrap();
+        let view = tile.viewport();
+        let xs: Vec<f64> = (0..TILE_SIZE)
+            .map(|px| view.center_x + view.offset(px as f64, 0.).0)
+            .collect();
+        let step = xs[1] - xs[0];
+        assert!(step > 0.);
+        assert!(xs.windows(2).all(|w| w[1] - w[0] == step));
+    }
+
+    #[test]
+    fn tiles_are_cached_per_iteration_parameters() {
+        let params = RenderParams::default();
+        let deeper = RenderParams {
+            max_iter: params.max_iter * 2,
+            ..params
+        };
+        let mut cache = TileCache::new(1000);
+        cache.insert(key(0), &params, values(10));
+        assert!(cache.get(key(0), &deeper).is_none());
+        assert!(cache.get(key(0), &params).is_some());
+    }
+
+    #[test]
+    fn shared_cache_computes_a_tile_once() {
+        let cache = SharedTileCache::default();
+        let params = RenderParams {
+            max_iter: 32,
+            ..RenderParams::default()
+        };
+        let first = cache.values(key(1), &params);
+        assert_eq!(first.len(), TILE_SIZE * TILE_SIZE);
+        assert!(Arc::ptr_eq(&first, &cache.values(key(1), &params)));
+        assert_eq!(cache.lock().used_bytes(), size_of_val(&*first));
+    }
+}
@@ -0,0 +1,24 @@
+//! Self-contained description of what was rendered.
+
+use crate::render::{RenderParams, Viewport};
+
+/// Center, scale and render parameters of a view, independent of the
+/// output size. Enough to reopen the exact location.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ViewDescription {
+    pub center_x: f64,
+    pub center_y: f64,
+    /// pixels per world unit
+    pub zoom: u64,
+    /// counter-clockwise rotation in degrees
+    pub rotation: f64,
+    pub params: RenderParams,
+}
+
+impl ViewDescription {
+    pub fn viewport(&self, width: usize, height: usize) -> Option<Viewport> {
+        Viewport::new(self.center_x, self.center_y, self.zoom, width, height)
+            .map(|v| v.rotated(self.rotation))
+    }
+}
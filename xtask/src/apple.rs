@@ -0,0 +1,295 @@
+//! Static libraries and an XCFramework for Apple platforms.
+//!
+//! App Store builds link the library statically, so besides the dylib staged
+//! by `build-native` the crate is built as a `staticlib` for every Apple slice,
+//! the architectures of a slice are merged with `lipo` and the slices are
+//! bundled with `xcodebuild -create-xcframework`, each with the C header the
+//! build script generates. Every step goes through an [`Executor`], so the
+//! sequence can be recorded on any host.
+
+use crate::build_native::cargo_build_command;
+use crate::paths::{self, ProjectLayout};
+use crate::process::{Executor, cmd_in_dir};
+use anyhow::{Context, Result};
+use std::path::PathBuf;
+
+/// One platform variant of the XCFramework.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Slice {
+    IosDevice,
+    IosSimulator,
+    MacCatalyst,
+}
+
+impl Slice {
+    pub const ALL: [Slice; 3] = [Slice::IosDevice, Slice::IosSimulator, Slice::MacCatalyst];
+
+    /// Rust targets merged into this slice.
+    pub fn rust_targets(self) -> &'static [&'static str] {
+        match self {
+            Slice::IosDevice => &["aarch64-apple-ios"],
+            Slice::IosSimulator => &["aarch64-apple-ios-sim", "x86_64-apple-ios"],
+            Slice::MacCatalyst => &["aarch64-apple-ios-macabi", "x86_64-apple-ios-macabi"],
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Slice::IosDevice => "ios",
+            Slice::IosSimulator => "ios-simulator",
+            Slice::MacCatalyst => "maccatalyst",
+        }
+    }
+}
+
+/// Builds every slice and assembles [`ProjectLayout::apple_xcframework`], replacing
+/// an existing one. Returns its path.
+pub fn build_xcframework(
+    exec: &mut dyn Executor,
+    layout: &ProjectLayout,
+    release: bool,
+) -> Result<PathBuf> {
+    layout.require_rust_fractal()?;
+    let profile_dir = if release { "release" } else { "debug" };
+
+    let builds = Slice::ALL
+        .iter()
+        .flat_map(|slice| slice.rust_targets())
+        .map(|cargo_target| cargo_build_command(layout, cargo_target, release, &[]))
+        .collect();
+    exec.run_all(builds).context("cargo build failed")?;
+
+    let mut libraries = Vec::new();
+    for slice in Slice::ALL {
+        let inputs = slice
+            .rust_targets()
+            .iter()
+            .map(|cargo_target| static_lib_path(layout, cargo_target, profile_dir))
+            .collect();
+        libraries.push(merge_architectures(
+            exec,
+            layout,
+            slice,
+            profile_dir,
+            inputs,
+        )?);
+    }
+
+    let output = layout.apple_xcframework();
+    // xcodebuild refuses to overwrite an existing framework
+    exec.remove_dir_all(&output)?;
+    let mut cmd = cmd_in_dir("xcodebuild", layout.rust_fractal_root());
+    cmd.arg("-create-xcframework");
+    let headers = layout.rust_fractal_include();
+    for library in &libraries {
+        cmd.arg("-library")
+            .arg(library)
+            .arg("-headers")
+            .arg(&headers);
+    }
+    cmd.arg("-output").arg(&output);
+    exec.run(cmd)
+        .context("xcodebuild -create-xcframework failed")?;
+    Ok(output)
+}
+
+/// `lipo`s the static libraries of a slice into one, a single library is used as is.
+fn merge_architectures(
+    exec: &mut dyn Executor,
+    layout: &ProjectLayout,
+    slice: Slice,
+    profile_dir: &str,
+    inputs: Vec<PathBuf>,
+) -> Result<PathBuf> {
+    if let [single] = inputs.as_slice() {
+        return Ok(single.clone());
+    }
+
+    let dir = layout
+        .apple_build_dir()
+        .join(slice.as_str())
+        .join(profile_dir);
+    exec.create_dir_all(&dir)?;
+    let output = dir.join(paths::static_lib_filename());
+
+    let mut cmd = cmd_in_dir("lipo", layout.rust_fractal_root());
+    cmd.arg("-create").args(&inputs).arg("-output").arg(&output);
+    exec.run(cmd)
+        .with_context(|| format!("lipo failed for the {} slice", slice.as_str()))?;
+    Ok(output)
+}
+
+fn static_lib_path(layout: &ProjectLayout, cargo_target: &str, profile_dir: &str) -> PathBuf {
+    layout
+        .cargo_output_dir(cargo_target, profile_dir)
+        .join(paths::static_lib_filename())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::paths::LayoutArgs;
+    use crate::plan::{Plan, Step};
+    use std::path::Path;
+
+    fn layout() -> ProjectLayout {
+        ProjectLayout::discover(&LayoutArgs {
+            project_root: Some(Path::new(env!("CARGO_MANIFEST_DIR")).join("..")),
+            ..LayoutArgs::default()
+        })
+        .unwrap()
+    }
+
+    fn args(step: &Step) -> (&str, Vec<&str>) {
+        match step {
+            Step::Run { program, args, .. } => {
+                (program.as_str(), args.iter().map(String::as_str).collect())
+            }
+            other => panic!("expected a command, found {other:?}"),
+        }
+    }
+
+    fn path(path: &Path) -> &str {
+        path.to_str().unwrap()
+    }
+
+    #[test]
+    fn records_the_xcframework_build() {
+        let layout = layout();
+        let mut plan = Plan::default();
+        let output = build_xcframework(&mut plan, &layout, true).unwrap();
+        assert_eq!(output, layout.apple_xcframework());
+
+        let lib = |triple| static_lib_path(&layout, triple, "release");
+        let universal = |slice: Slice| {
+            layout
+                .apple_build_dir()
+                .join(slice.as_str())
+                .join("release")
+                .join("librust_fractal.a")
+        };
+        let [
+            builds,
+            sim_dir,
+            sim_lipo,
+            catalyst_dir,
+            catalyst_lipo,
+            remove,
+            xcodebuild,
+        ] = plan.steps.as_slice()
+        else {
+            panic!("unexpected steps: {:#?}", plan.steps);
+        };
+
+        let Step::Parallel { steps: builds } = builds else {
+            panic!("expected the cargo builds to run together, found {builds:?}");
+        };
+        let triples = builds
+            .iter()
+            .map(|build| {
+                let (program, args) = args(build);
+                assert_eq!(program, "cargo");
+                assert!(args.contains(&"--release"));
+                let target = args.iter().position(|&arg| arg == "--target").unwrap();
+                args[target + 1].to_string()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            triples,
+            [
+                "aarch64-apple-ios",
+                "aarch64-apple-ios-sim",
+                "x86_64-apple-ios",
+                "aarch64-apple-ios-macabi",
+                "x86_64-apple-ios-macabi",
+            ]
+        );
+
+        for (dir, lipo, slice, triples) in [
+            (
+                sim_dir,
+                sim_lipo,
+                Slice::IosSimulator,
+                ["aarch64-apple-ios-sim", "x86_64-apple-ios"],
+            ),
+            (
+                catalyst_dir,
+                catalyst_lipo,
+                Slice::MacCatalyst,
+                ["aarch64-apple-ios-macabi", "x86_64-apple-ios-macabi"],
+            ),
+        ] {
+            assert_eq!(
+                dir,
+                &Step::CreateDir {
+                    path: universal(slice).parent().unwrap().to_path_buf()
+                }
+            );
+            let (program, args) = args(lipo);
+            assert_eq!(program, "lipo");
+            let (lib0, lib1, out) = (lib(triples[0]), lib(triples[1]), universal(slice));
+            assert_eq!(
+                args,
+                ["-create", path(&lib0), path(&lib1), "-output", path(&out)]
+            );
+        }
+
+        assert_eq!(
+            remove,
+            &Step::RemoveDir {
+                path: layout.apple_xcframework()
+            }
+        );
+        let (program, args) = args(xcodebuild);
+        assert_eq!(program, "xcodebuild");
+        let headers = layout.rust_fractal_include();
+        let device = lib("aarch64-apple-ios");
+        let (sim, catalyst) = (
+            universal(Slice::IosSimulator),
+            universal(Slice::MacCatalyst),
+        );
+        let framework = layout.apple_xcframework();
+        #[rustfmt::skip]
+        let expected = [
+            "-create-xcframework",
+            "-library", path(&device), "-headers", path(&headers),
+            "-library", path(&sim), "-headers", path(&headers),
+            "-library", path(&catalyst), "-headers", path(&headers),
+            "-output", path(&framework),
+        ];
+        assert_eq!(args, expected);
+    }
+
+    #[test]
+    fn stages_the_static_library() {
+        let layout = layout();
+        let lib = static_lib_path(&layout, "aarch64-apple-ios", "debug");
+        assert_eq!(lib.file_name().unwrap(), "librust_fractal.a");
+        assert_eq!(
+            lib.parent().unwrap(),
+            layout.cargo_output_dir("aarch64-apple-ios", "debug")
+        );
+
+        let mut plan = Plan::default();
+        build_xcframework(&mut plan, &layout, false).unwrap();
+        let libraries = plan
+            .steps
+            .iter()
+            .filter_map(|step| match step {
+                Step::Run { program, args, .. } if program == "lipo" || program == "xcodebuild" => {
+                    Some(args)
+                }
+                _ => None,
+            })
+            .flatten()
+            .filter(|arg| arg.contains("librust_fractal"))
+            .collect::<Vec<_>>();
+        // two lipo inputs and an output for each universal slice, three xcodebuild libraries
+        assert_eq!(libraries.len(), 9);
+        assert!(
+            libraries
+                .iter()
+                .all(|arg| arg.ends_with("librust_fractal.a"))
+        );
+    }
+}
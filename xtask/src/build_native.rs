@@ -1,10 +1,12 @@
 use crate::Target;
-use crate::paths::{self, Platform};
-use crate::process::{self, cmd_in_dir, run};
+use crate::ndk::{AndroidArgs, Ndk};
+use crate::paths::{self, Platform, ProjectLayout};
+use crate::process::{self, Executor, cmd_in_dir};
+use crate::verify;
+use crate::version;
 use anyhow::{Context, Result, anyhow};
-use std::fs;
-use std::path::{Path, PathBuf};
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::path::PathBuf;
+use std::process::Command;
 
 fn host_os_is_macos() -> bool {
     cfg!(target_os = "macos")
@@ -12,29 +14,29 @@ fn host_os_is_macos() -> bool {
 fn host_os_is_windows() -> bool {
     cfg!(target_os = "windows")
 }
-
-pub fn build_hash() -> String {
-    let secs = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .expect("system time before UNIX_EPOCH")
-        .as_secs();
-
-    secs.to_string()
+fn host_os_is_linux() -> bool {
+    cfg!(target_os = "linux")
 }
-pub(crate) fn pack_nuget(release: bool, version: &str) -> Result<()> {
-    paths::assert_rust_fractal_exists();
 
-    let csproj = paths::bindings_root_project_file();
+pub(crate) fn pack_nuget(
+    exec: &mut dyn Executor,
+    layout: &ProjectLayout,
+    release: bool,
+    version: &str,
+) -> Result<()> {
+    layout.require_rust_fractal()?;
+
+    let csproj = layout.bindings_root_project_file();
     if !csproj.is_file() {
         anyhow::bail!("NuGet csproj not found: {}", csproj.display());
     }
 
     // Ensure output feed dir exists (allowed)
-    let out_dir = paths::bindings_nupkgs();
+    let out_dir = layout.bindings_nupkgs();
 
     let config = if release { "Release" } else { "Debug" };
 
-    let mut cmd = process::cmd_in_dir("dotnet", &paths::bindings_root());
+    let mut cmd = process::cmd_in_dir("dotnet", &layout.bindings_root());
     cmd.arg("pack")
         .arg(csproj)
         .arg("-c")
@@ -44,95 +46,164 @@ pub(crate) fn pack_nuget(release: bool, version: &str) -> Result<()> {
 
     cmd.arg(format!("/p:PackageVersion={}", version)); // see note below
 
-    process::run(cmd).context("dotnet pack failed")?;
+    exec.run(cmd).context("dotnet pack failed")?;
     Ok(())
 }
-/// builds and copies the rust Library for the given target
-pub fn build_and_stage(target: &Target, release: bool) -> Result<()> {
-    paths::assert_rust_fractal_exists();
+/// Builds every target concurrently, verifies and stages the libraries, then
+/// packs the NuGet package around them as `package_version` or the version
+/// derived by [`version::package_version`].
+/// The crate's build script regenerates the C# bindings as part of the build.
+pub fn stage_and_pack(
+    exec: &mut dyn Executor,
+    layout: &ProjectLayout,
+    targets: &[Target],
+    release: bool,
+    android: &AndroidArgs,
+    package_version: Option<&str>,
+) -> Result<()> {
+    layout.require_rust_fractal()?;
+    let builds = targets
+        .iter()
+        .map(|target| NativeBuild::new(target, release, android))
+        .collect::<Result<Vec<_>>>()?;
+
+    exec.run_all(builds.iter().map(|build| build.command(layout)).collect())
+        .context("cargo build failed")?;
+
+    exec.create_dir_all(&layout.bindings_runtime_dir())?;
+    let exports = if exec.dry_run() {
+        Vec::new()
+    } else {
+        verify::declared_exports(&layout.rust_fractal_src().join("lib.rs"))?
+    };
+    for build in &builds {
+        build.stage(exec, layout, &exports)?;
+    }
+
+    let nuget_package_version = match package_version {
+        Some(version) => version.to_string(),
+        // nothing is staged yet to fingerprint
+        None if exec.dry_run() => version::planned_package_version(layout)?,
+        None => version::package_version(layout)?,
+    };
+    pack_nuget(exec, layout, release, &nuget_package_version)?;
+    version::write_version_props(exec, layout, &nuget_package_version)
+}
+
+/// The cargo build of the native library for one target.
+struct NativeBuild {
+    target: Target,
+    cargo_target: String,
+    release: bool,
+    /// e.g. the Android linker
+    env: Vec<(String, PathBuf)>,
+}
 
-    if target.platform.requires_macos() && !host_os_is_macos() {
-        return Err(anyhow!("{target:?} builds require a macOS host"));
+impl NativeBuild {
+    fn new(target: &Target, release: bool, android: &AndroidArgs) -> Result<Self> {
+        if target.platform.requires_macos() && !host_os_is_macos() {
+            return Err(anyhow!("{target:?} builds require a macOS host"));
+        }
+        if target.platform.requires_windwos() && !host_os_is_windows() {
+            return Err(anyhow!(
+                "Windows builds are expected to run on a Windows host"
+            ));
+        }
+        if target.platform.requires_linux() && !host_os_is_linux() {
+            return Err(anyhow!("Linux builds are expected to run on a Linux host"));
+        }
+
+        let cargo_target = target.get_rust_architecture_representation();
+        let env = if target.platform == Platform::Android {
+            let ndk = Ndk::locate(android.ndk.as_deref())?;
+            eprintln!("using Android NDK at {}", ndk.root().display());
+            ndk.cargo_env(target, android.android_api)?
+        } else {
+            Vec::new()
+        };
+        Ok(Self {
+            target: target.clone(),
+            cargo_target,
+            release,
+            env,
+        })
     }
-    if target.platform.requires_windwos() && !host_os_is_windows() {
-        return Err(anyhow!(
-            "Windows builds are expected to run on a Windows host"
-        ));
+
+    fn command(&self, layout: &ProjectLayout) -> Command {
+        cargo_build_command(layout, &self.cargo_target, self.release, &self.env)
     }
 
-    let rust_root = paths::rust_fractal_root();
-    let profile_dir = if release { "release" } else { "debug" };
-    let cargo_target = &target.get_rust_architecture_representation();
-    cargo_build(&rust_root, cargo_target, release)?;
-
-    let built_artifact =
-        built_artifact_path(&rust_root, cargo_target, profile_dir, target.platform)?;
-    let dest_file = paths::destination_native_lib_path(target.platform, target.arch);
-
-    // Create destination directory
-    fs::create_dir_all(
-        dest_file
-            .parent()
-            .expect("destination native lib must have parent dir"),
-    )
-    .with_context(|| format!("create_dir_all failed: {}", dest_file.display()))?;
-
-    fs::copy(&built_artifact, &dest_file).with_context(|| {
-        format!(
-            "copy failed: {} -> {}",
-            built_artifact.display(),
-            dest_file.display()
-        )
-    })?;
-
-    eprintln!(
-        "staged: {} -> {}",
-        built_artifact.display(),
-        dest_file.display()
-    );
-    Ok(())
+    /// Checks the built library against the target and `exports`, the symbols
+    /// lib.rs declares, and copies it into the runtimes folder.
+    fn stage(
+        &self,
+        exec: &mut dyn Executor,
+        layout: &ProjectLayout,
+        exports: &[String],
+    ) -> Result<()> {
+        let profile_dir = if self.release { "release" } else { "debug" };
+        let built_artifact = built_artifact_path(
+            layout,
+            &self.cargo_target,
+            profile_dir,
+            self.target.platform,
+        );
+        if !exec.dry_run() {
+            verify::verify_library(&built_artifact, &self.target, exports)
+                .with_context(|| format!("{} failed verification", built_artifact.display()))?;
+        }
+
+        let dest_file = layout.destination_native_lib_path(self.target.platform, self.target.arch);
+        // Create destination directory
+        exec.create_dir_all(
+            dest_file
+                .parent()
+                .expect("destination native lib must have parent dir"),
+        )?;
+        exec.copy(&built_artifact, &dest_file)
+            .with_context(|| format!("staging the {} build failed", self.cargo_target))?;
+        Ok(())
+    }
 }
 
-fn cargo_build(rust_root: &Path, cargo_target: &str, release: bool) -> Result<()> {
-    let mut cmd = cmd_in_dir("cargo", rust_root);
+/// `cargo build` of the library for one target in its own target directory
+/// ([`ProjectLayout::cargo_target_dir`]), so builds for several targets do not wait
+/// for each other's lock. `env` is set for cargo, e.g. the Android linker.
+pub(crate) fn cargo_build_command(
+    layout: &ProjectLayout,
+    cargo_target: &str,
+    release: bool,
+    env: &[(String, PathBuf)],
+) -> Command {
+    let mut cmd = cmd_in_dir("cargo", layout.rust_fractal_root());
+    cmd.envs(env.iter().map(|(key, value)| (key, value)));
     cmd.arg("build")
         .arg("-p")
         .arg("rust_fractal")
+        .arg("--lib")
         .arg("--target")
-        .arg(cargo_target);
+        .arg(cargo_target)
+        .arg("--target-dir")
+        .arg(layout.cargo_target_dir(cargo_target));
 
     if release {
         cmd.arg("--release");
     }
-
-    run(cmd).with_context(|| format!("cargo build failed for target {cargo_target}"))?;
-    Ok(())
+    cmd
 }
 
 /// Deterministic expected artifact path based on naming rules.
 /// This avoids “find a file that contains rust_fractal”.
 fn built_artifact_path(
-    rust_root: &Path,
+    layout: &ProjectLayout,
     cargo_target: &str,
     profile_dir: &str,
     platform: Platform,
-) -> Result<PathBuf> {
+) -> PathBuf {
     let filename = paths::native_lib_filename(platform);
 
-    // Standard cargo output dir:
-    // rust_fractal/target/<triple>/<debug|release>/<libname>
-    let p = rust_root
-        .join("target")
-        .join(cargo_target)
-        .join(profile_dir)
-        .join(filename);
-
-    if !p.is_file() {
-        return Err(anyhow!(
-            "expected built artifact not found: {}",
-            p.display()
-        ));
-    }
-
-    Ok(p)
+    // rust_fractal/target/xtask/<triple>/<triple>/<debug|release>/<libname>
+    layout
+        .cargo_output_dir(cargo_target, profile_dir)
+        .join(filename)
 }
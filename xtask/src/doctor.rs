@@ -0,0 +1,571 @@
+//! `xtask doctor`: checks the toolchains and the project layout the build
+//! commands rely on and prints how to fix whatever is missing, instead of
+//! failing halfway through a build.
+
+use crate::Target;
+use crate::apple::Slice;
+use crate::ndk::{AndroidArgs, Ndk};
+use crate::paths::{LAYOUT_CONFIG_FILE, Platform, ProjectLayout};
+use crate::process::{self, cmd_in_dir};
+use anyhow::{Result, bail};
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Status {
+    Ok,
+    Skipped,
+    /// missing, but only for targets that are not checked
+    Warning,
+    Problem,
+}
+
+#[derive(Default)]
+struct Report {
+    entries: Vec<(Status, String)>,
+}
+
+impl Report {
+    fn section(&self, title: &str) {
+        println!("\n{title}");
+    }
+
+    fn ok(&mut self, what: impl AsRef<str>) {
+        println!("  ok    {}", what.as_ref());
+        self.entries.push((Status::Ok, what.as_ref().to_string()));
+    }
+
+    fn skipped(&mut self, what: impl AsRef<str>, why: impl AsRef<str>) {
+        println!("  skip  {}: {}", what.as_ref(), why.as_ref());
+        self.entries
+            .push((Status::Skipped, what.as_ref().to_string()));
+    }
+
+    fn missing(&mut self, what: impl AsRef<str>, fix: impl AsRef<str>) {
+        self.missing_if(true, what, fix);
+    }
+
+    /// A problem if the checked targets need `what`, else a warning.
+    fn missing_if(&mut self, required: bool, what: impl AsRef<str>, fix: impl AsRef<str>) {
+        let (status, label) = if required {
+            (Status::Problem, "FAIL")
+        } else {
+            (Status::Warning, "warn")
+        };
+        println!("  {label}  {}", what.as_ref());
+        println!("        fix: {}", fix.as_ref());
+        self.entries.push((status, what.as_ref().to_string()));
+    }
+
+    fn count(&self, status: Status) -> usize {
+        self.entries.iter().filter(|(s, _)| *s == status).count()
+    }
+
+    /// Fails if any check found a problem, warnings alone pass.
+    fn finish(&self) -> Result<()> {
+        let (problems, warnings) = (self.count(Status::Problem), self.count(Status::Warning));
+        println!();
+        if problems > 0 {
+            bail!("{problems} problem(s) found");
+        }
+        if warnings > 0 {
+            println!("ready for the checked targets, {warnings} warning(s) for other targets");
+        } else {
+            println!("everything looks good");
+        }
+        Ok(())
+    }
+}
+
+/// Runs every check for building `targets`, failing if any of them found a
+/// problem. What only other targets need is reported as a warning.
+pub fn doctor(layout: &ProjectLayout, targets: &[Target], android: &AndroidArgs) -> Result<()> {
+    let mut report = Report::default();
+    let root = layout.project_root();
+    println!(
+        "checking for {}",
+        targets
+            .iter()
+            .map(Target::to_string)
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+    check_project(&mut report, layout);
+    check_tools(&mut report, root);
+    check_rust_targets(&mut report, root, targets);
+    check_dotnet(&mut report, layout, targets);
+    check_android(&mut report, android, targets);
+    check_apple(&mut report, root, targets);
+    report.finish()
+}
+
+fn check_project(report: &mut Report, layout: &ProjectLayout) {
+    report.section("project layout");
+    report.ok(format!("project root: {}", layout.project_root().display()));
+    let expected = [
+        ("rust_fractal crate", layout.rust_fractal_manifest()),
+        ("rust_fractal FFI", layout.rust_fractal_src().join("lib.rs")),
+        ("NuGet project", layout.bindings_root_project_file()),
+        ("MAUI project", layout.maui_project_path().to_path_buf()),
+        ("NuGet.config", layout.project_root().join("NuGet.config")),
+    ];
+    for (what, path) in expected {
+        if path.is_file() {
+            report.ok(format!("{what}: {}", path.display()));
+        } else {
+            report.missing(
+                format!("{what} not found at {}", path.display()),
+                format!(
+                    "run xtask from a complete checkout, or point --project-root or \
+                     {LAYOUT_CONFIG_FILE} at it"
+                ),
+            );
+        }
+    }
+}
+
+fn check_tools(report: &mut Report, root: &Path) {
+    report.section("tools");
+    let tools = [
+        ("cargo", "install Rust through https://rustup.rs"),
+        ("rustup", "install Rust through https://rustup.rs"),
+        ("git", "install git, package versions are derived from it"),
+        (
+            "dotnet",
+            "install the .NET SDK from https://dotnet.microsoft.com/download",
+        ),
+    ];
+    for (tool, fix) in tools {
+        let mut cmd = cmd_in_dir(tool, root);
+        cmd.arg("--version");
+        match process::output(cmd) {
+            Ok(version) => report.ok(format!("{tool}: {}", first_line(&version))),
+            Err(_) => report.missing(format!("{tool} not found"), fix),
+        }
+    }
+}
+
+fn check_rust_targets(report: &mut Report, root: &Path, targets: &[Target]) {
+    report.section("rustup targets");
+    let mut cmd = cmd_in_dir("rustup", root);
+    cmd.args(["target", "list", "--installed"]);
+    let Ok(installed) = process::output(cmd) else {
+        report.skipped("rustup targets", "rustup is not available");
+        return;
+    };
+    let installed = installed.lines().map(str::trim).collect::<BTreeSet<_>>();
+    rust_targets(report, &installed, targets);
+}
+
+fn rust_targets(report: &mut Report, installed: &BTreeSet<&str>, targets: &[Target]) {
+    let mut check = |what: String, triple: &str, platform: Platform, required: bool| {
+        if !platform.builds_on_host() {
+            report.skipped(what, "not buildable on this host");
+        } else if installed.contains(triple) {
+            report.ok(what);
+        } else {
+            report.missing_if(
+                required,
+                format!("{what} not installed"),
+                format!("rustup target add {triple}"),
+            );
+        }
+    };
+    for target in Target::all() {
+        let triple = target.get_rust_architecture_representation();
+        let required = targets.contains(&target);
+        check(
+            format!("{target} ({triple})"),
+            &triple,
+            target.platform,
+            required,
+        );
+    }
+    // the iOS head links the XCFramework
+    let ios = targets.iter().any(|t| t.platform == Platform::Ios);
+    for slice in Slice::ALL {
+        for triple in slice.rust_targets() {
+            check(
+                format!("xcframework {} ({triple})", slice.as_str()),
+                triple,
+                Platform::Ios,
+                ios,
+            );
+        }
+    }
+}
+
+fn check_dotnet(report: &mut Report, layout: &ProjectLayout, targets: &[Target]) {
+    report.section(".NET");
+    let mut majors = BTreeSet::new();
+    let projects = [
+        layout.maui_project_path().to_path_buf(),
+        layout.bindings_root_project_file(),
+    ];
+    for project in &projects {
+        // a missing project is reported with the layout
+        if let Ok(text) = fs::read_to_string(project) {
+            majors.extend(target_framework_majors(&text));
+        }
+    }
+    if majors.is_empty() {
+        report.skipped(".NET SDKs and workloads", "no target frameworks found");
+        return;
+    }
+
+    let mut cmd = cmd_in_dir("dotnet", layout.project_root());
+    cmd.arg("--list-sdks");
+    let Ok(sdks) = process::output(cmd) else {
+        report.skipped(".NET SDKs and workloads", "dotnet is not available");
+        return;
+    };
+
+    for major in majors {
+        let Some(sdk) = newest_sdk(&sdks, major) else {
+            report.missing(
+                format!(".NET {major} SDK not installed"),
+                format!("install it from https://dotnet.microsoft.com/download/dotnet/{major}.0"),
+            );
+            continue;
+        };
+        report.ok(format!(".NET {major} SDK {sdk}"));
+        check_workloads(report, major, sdk, targets);
+    }
+}
+
+/// SDK majors of the `net<major>.0...` frameworks a project targets.
+fn target_framework_majors(project: &str) -> BTreeSet<u32> {
+    project
+        .lines()
+        .filter(|line| line.contains("<TargetFramework"))
+        .filter_map(|line| line.split_once('>')?.1.split_once("</"))
+        .flat_map(|(frameworks, _)| frameworks.split(';'))
+        .filter_map(|framework| {
+            let version = framework.trim().strip_prefix("net")?;
+            version.split_once('.')?.0.parse().ok()
+        })
+        .collect()
+}
+
+/// Newest `major` SDK in `dotnet --list-sdks` output, lines like
+/// `10.0.100 [/usr/share/dotnet/sdk]` sorted ascending.
+fn newest_sdk(sdks: &str, major: u32) -> Option<&str> {
+    sdks.lines()
+        .filter_map(|line| line.split_whitespace().next())
+        .rfind(|sdk| sdk.split('.').next() == Some(&major.to_string()))
+}
+
+/// Workloads are installed per SDK band, so they are listed with `sdk`
+/// pinned through a `global.json` in a scratch directory.
+fn check_workloads(report: &mut Report, major: u32, sdk: &str, targets: &[Target]) {
+    let dir = std::env::temp_dir().join(format!("xtask-doctor-net{major}"));
+    let global_json =
+        format!("{{ \"sdk\": {{ \"version\": \"{sdk}\", \"rollForward\": \"disable\" }} }}\n");
+    if fs::create_dir_all(&dir)
+        .and_then(|()| fs::write(dir.join("global.json"), global_json))
+        .is_err()
+    {
+        report.skipped(format!(".NET {major} workloads"), "could not pin the SDK");
+        return;
+    }
+
+    let mut cmd = cmd_in_dir("dotnet", &dir);
+    cmd.args(["workload", "list"]);
+    let listed = process::output(cmd);
+    let _ = fs::remove_dir_all(&dir);
+    let Ok(listed) = listed else {
+        report.skipped(
+            format!(".NET {major} workloads"),
+            "dotnet workload list failed",
+        );
+        return;
+    };
+    let installed = listed
+        .lines()
+        .filter_map(|line| line.split_whitespace().next())
+        .collect::<BTreeSet<_>>();
+    workloads(report, major, &installed, targets);
+}
+
+fn workloads(report: &mut Report, major: u32, installed: &BTreeSet<&str>, targets: &[Target]) {
+    // the app's heads this host can build, the `maui` workload covers all
+    let heads = [
+        (Platform::Android, "maui-android"),
+        (Platform::Windows, "maui-windows"),
+        (Platform::Ios, "maui-ios"),
+        (Platform::Osx, "maui-maccatalyst"),
+    ];
+    for (platform, workload) in heads {
+        let what = format!(".NET {major} workload {workload}");
+        if !platform.builds_on_host() {
+            report.skipped(what, "not buildable on this host");
+        } else if installed.contains(workload) || installed.contains("maui") {
+            report.ok(what);
+        } else {
+            report.missing_if(
+                targets.iter().any(|t| t.platform == platform),
+                format!("{what} not installed"),
+                format!("dotnet workload install {workload} (with the .NET {major} SDK)"),
+            );
+        }
+    }
+}
+
+fn check_android(report: &mut Report, android: &AndroidArgs, targets: &[Target]) {
+    report.section("Android NDK");
+    let required = targets.iter().any(|t| t.platform == Platform::Android);
+    let ndk = match Ndk::locate(android.ndk.as_deref()) {
+        Ok(ndk) => ndk,
+        Err(e) => {
+            report.missing_if(
+                required,
+                format!("{e:#}"),
+                "install the NDK with the Android Studio SDK manager (SDK Tools > NDK), \
+                 or set ANDROID_NDK_HOME / pass --ndk",
+            );
+            return;
+        }
+    };
+    report.ok(format!("NDK at {}", ndk.root().display()));
+
+    for target in Target::all() {
+        if target.platform != Platform::Android {
+            continue;
+        }
+        match ndk.clang(&target, android.android_api) {
+            Ok(clang) => report.ok(format!("{target} linker {}", file_name(&clang))),
+            Err(e) => report.missing_if(
+                targets.contains(&target),
+                format!("{target}: {e:#}"),
+                "install a newer NDK or pass a supported --android-api",
+            ),
+        }
+    }
+}
+
+fn check_apple(report: &mut Report, root: &Path, targets: &[Target]) {
+    report.section("Apple");
+    if !cfg!(target_os = "macos") {
+        report.skipped("Xcode", "only needed on macOS");
+        return;
+    }
+    let tools: [(&str, &[&str]); 2] = [
+        ("xcodebuild", &["-version"]),
+        ("xcrun", &["--find", "lipo"]),
+    ];
+    for (tool, args) in tools {
+        let mut cmd = cmd_in_dir(tool, root);
+        cmd.args(args);
+        match process::output(cmd) {
+            Ok(output) => report.ok(format!("{tool}: {}", first_line(&output))),
+            Err(_) => report.missing_if(
+                targets.iter().any(|t| t.platform.requires_macos()),
+                format!("{tool} {} failed", args.join(" ")),
+                "install Xcode and run `sudo xcode-select --switch /Applications/Xcode.app`",
+            ),
+        }
+    }
+}
+
+fn first_line(output: &str) -> &str {
+    output.lines().next().unwrap_or_default()
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn targets(names: &[&str]) -> Vec<Target> {
+        names.iter().map(|name| name.parse().unwrap()).collect()
+    }
+
+    fn with(report: &Report, status: Status) -> Vec<&str> {
+        report
+            .entries
+            .iter()
+            .filter(|(s, _)| *s == status)
+            .map(|(_, what)| what.as_str())
+            .collect()
+    }
+
+    fn android(ndk: &Path) -> AndroidArgs {
+        AndroidArgs {
+            ndk: Some(ndk.to_path_buf()),
+            android_api: crate::ndk::DEFAULT_API_LEVEL,
+        }
+    }
+
+    /// An NDK with only the aarch64 API 21 clang wrapper, for every host.
+    fn fake_ndk(name: &str) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("xtask-doctor-{name}-{}", std::process::id()));
+        for host in ["linux-x86_64", "darwin-x86_64", "windows-x86_64"] {
+            let bin = root.join("toolchains/llvm/prebuilt").join(host).join("bin");
+            fs::create_dir_all(&bin).unwrap();
+            for clang in [
+                "aarch64-linux-android21-clang",
+                "aarch64-linux-android21-clang.cmd",
+            ] {
+                fs::write(bin.join(clang), "").unwrap();
+            }
+        }
+        root
+    }
+
+    #[test]
+    fn only_problems_fail() {
+        let mut report = Report::default();
+        report.ok("cargo");
+        report.skipped("Xcode", "only needed on macOS");
+        report.missing_if(false, "android-x64 not installed", "rustup target add");
+        report.finish().unwrap();
+
+        report.missing("git not found", "install git");
+        report.missing_if(true, "android-arm64 not installed", "rustup target add");
+        let err = report.finish().unwrap_err();
+        assert_eq!(err.to_string(), "2 problem(s) found");
+        assert_eq!(report.count(Status::Warning), 1);
+    }
+
+    #[test]
+    fn only_checked_rust_targets_are_required() {
+        let installed = BTreeSet::from(["x86_64-linux-android"]);
+        let mut report = Report::default();
+        rust_targets(
+            &mut report,
+            &installed,
+            &targets(&["android-arm64", "android-x64"]),
+        );
+        assert_eq!(
+            with(&report, Status::Problem),
+            ["android-arm64 (aarch64-linux-android) not installed"]
+        );
+        assert_eq!(
+            with(&report, Status::Ok),
+            ["android-x64 (x86_64-linux-android)"]
+        );
+        assert!(
+            with(&report, Status::Warning)
+                .contains(&"android-arm (armv7-linux-androideabi) not installed")
+        );
+        // the XCFramework slices are only needed for iOS
+        assert!(
+            !with(&report, Status::Problem)
+                .iter()
+                .any(|what| what.starts_with("xcframework"))
+        );
+
+        let mut report = Report::default();
+        let all: BTreeSet<_> = Target::all()
+            .iter()
+            .map(Target::get_rust_architecture_representation)
+            .collect();
+        let all = all.iter().map(String::as_str).collect();
+        rust_targets(&mut report, &all, &targets(&["android-arm64"]));
+        assert!(with(&report, Status::Problem).is_empty());
+    }
+
+    #[test]
+    fn workloads_are_required_for_checked_platforms() {
+        let mut report = Report::default();
+        workloads(
+            &mut report,
+            10,
+            &BTreeSet::new(),
+            &targets(&["android-arm64"]),
+        );
+        assert_eq!(
+            with(&report, Status::Problem),
+            [".NET 10 workload maui-android not installed"]
+        );
+
+        let mut report = Report::default();
+        workloads(
+            &mut report,
+            10,
+            &BTreeSet::from(["maui"]),
+            &targets(&["android-arm64"]),
+        );
+        assert!(with(&report, Status::Problem).is_empty());
+        assert!(with(&report, Status::Warning).is_empty());
+
+        let mut report = Report::default();
+        workloads(&mut report, 10, &BTreeSet::new(), &targets(&["linux-x64"]));
+        assert!(with(&report, Status::Problem).is_empty());
+        assert!(
+            with(&report, Status::Warning).contains(&".NET 10 workload maui-android not installed")
+        );
+    }
+
+    #[test]
+    fn requires_the_sdks_the_projects_target() {
+        let app = "<TargetFrameworks>net10.0-android;net10.0-ios</TargetFrameworks>\n\
+                   <TargetFrameworks Condition=\"x\">$(TargetFrameworks);net9.0-windows10.0.19041.0</TargetFrameworks>\n\
+                   <SupportedOSPlatformVersion>21.0</SupportedOSPlatformVersion>";
+        assert_eq!(target_framework_majors(app), BTreeSet::from([9, 10]));
+        assert_eq!(
+            target_framework_majors("  <TargetFramework>net8.0</TargetFramework>"),
+            BTreeSet::from([8])
+        );
+
+        let layout = crate::paths::ProjectLayout::discover(&crate::paths::LayoutArgs {
+            project_root: Some(Path::new(env!("CARGO_MANIFEST_DIR")).join("..")),
+            ..Default::default()
+        })
+        .unwrap();
+        for project in [
+            layout.maui_project_path().to_path_buf(),
+            layout.bindings_root_project_file(),
+        ] {
+            let text = fs::read_to_string(project).unwrap();
+            assert_eq!(target_framework_majors(&text), BTreeSet::from([10]));
+        }
+
+        let sdks = "8.0.404 [/usr/share/dotnet/sdk]\n\
+                    10.0.100-rc.2.25502.107 [/usr/share/dotnet/sdk]\n\
+                    10.0.100 [/usr/share/dotnet/sdk]";
+        assert_eq!(newest_sdk(sdks, 10), Some("10.0.100"));
+        assert_eq!(newest_sdk(sdks, 8), Some("8.0.404"));
+        assert_eq!(newest_sdk(sdks, 1), None);
+        assert_eq!(newest_sdk("", 10), None);
+    }
+
+    #[test]
+    fn the_ndk_is_required_for_android_targets_only() {
+        let missing =
+            std::env::temp_dir().join(format!("xtask-doctor-no-ndk-{}", std::process::id()));
+
+        let mut report = Report::default();
+        check_android(&mut report, &android(&missing), &targets(&["linux-x64"]));
+        assert_eq!(report.count(Status::Problem), 0);
+        assert_eq!(report.count(Status::Warning), 1);
+
+        let mut report = Report::default();
+        check_android(
+            &mut report,
+            &android(&missing),
+            &targets(&["android-arm64"]),
+        );
+        assert_eq!(report.count(Status::Problem), 1);
+
+        // only the checked ABIs need a linker
+        let ndk = fake_ndk("abis");
+        let mut report = Report::default();
+        check_android(&mut report, &android(&ndk), &targets(&["android-arm64"]));
+        let mut other = Report::default();
+        check_android(&mut other, &android(&ndk), &targets(&["android-x64"]));
+        fs::remove_dir_all(&ndk).unwrap();
+
+        assert_eq!(report.count(Status::Problem), 0);
+        assert_eq!(report.count(Status::Warning), 2);
+        assert_eq!(other.count(Status::Problem), 1);
+        assert!(with(&other, Status::Problem)[0].starts_with("android-x64: "));
+    }
+}
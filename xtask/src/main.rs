@@ -1,12 +1,21 @@
+mod apple;
 mod build_native;
+mod doctor;
+mod monitor;
+mod ndk;
 mod paths;
+mod plan;
 mod process;
+mod verify;
+mod version;
 
-use std::{fs, str::FromStr};
+use std::{str::FromStr, time::Duration};
 
-use crate::{build_native::build_hash, paths::*};
+use crate::paths::*;
+use crate::plan::{Plan, PlanFormat};
+use crate::process::Executor;
 use anyhow::{self, Context, Result};
-use clap::{Parser, Subcommand, ValueEnum};
+use clap::{Args, Parser, Subcommand, ValueEnum};
 
 #[derive(Debug, Parser)]
 #[command(name = "xtask")]
@@ -14,9 +23,12 @@ use clap::{Parser, Subcommand, ValueEnum};
 struct Cli {
     #[command(subcommand)]
     cmd: Cmd,
+
+    #[command(flatten)]
+    layout: LayoutArgs,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 struct Target {
     arch: paths::Arch,
     platform: paths::Platform,
@@ -35,35 +47,95 @@ impl FromStr for Target {
         let arch =
             Arch::from_str(arch_s, true).map_err(|_| anyhow::anyhow!("bad arch: {arch_s}"))?;
 
-        match (arch, platform) {
-            (Arch::X64, Platform::Windows)
-            | (Arch::X64, Platform::Osx)
-            | (Arch::Arm64, Platform::Osx)
-            | (Arch::Arm64, Platform::Android)
-            | (Arch::Arm64, Platform::Ios) => {}
-            (arch, os) => {
-                return Err(anyhow::anyhow!(
-                    "{}-{} is not a supported architecture",
-                    arch.as_str(),
-                    os.as_str()
-                ));
-            }
+        if !Target::is_supported(platform, arch) {
+            return Err(anyhow::anyhow!(
+                "{}-{} is not a supported architecture",
+                arch.as_str(),
+                platform.as_str()
+            ));
         }
 
         Ok(Target { platform, arch })
     }
 }
+impl std::fmt::Display for Target {
+    /// `<platform>-<arch>` as accepted by `--targets`
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = |value: Option<clap::builder::PossibleValue>| {
+            value.map(|v| v.get_name().to_string()).unwrap_or_default()
+        };
+        write!(
+            f,
+            "{}-{}",
+            name(self.platform.to_possible_value()),
+            name(self.arch.to_possible_value())
+        )
+    }
+}
 impl Target {
+    fn is_supported(platform: Platform, arch: Arch) -> bool {
+        matches!(
+            (arch, platform),
+            (Arch::X64, Platform::Windows)
+                | (Arch::X64, Platform::Osx)
+                | (Arch::Arm64, Platform::Osx)
+                | (Arch::Arm64, Platform::Android)
+                | (Arch::X64, Platform::Android)
+                | (Arch::Arm, Platform::Android)
+                | (Arch::Arm64, Platform::Ios)
+                | (Arch::X64, Platform::Linux)
+                | (Arch::Arm64, Platform::Linux)
+        )
+    }
+
+    /// Every target `--targets` accepts.
+    pub fn all() -> Vec<Self> {
+        Platform::value_variants()
+            .iter()
+            .flat_map(|&platform| {
+                Arch::value_variants()
+                    .iter()
+                    .filter(move |&&arch| Target::is_supported(platform, arch))
+                    .map(move |&arch| Target { platform, arch })
+            })
+            .collect()
+    }
+
+    /// The target matching the machine xtask runs on, if it is supported.
+    pub fn host() -> Option<Self> {
+        let platform = if cfg!(target_os = "windows") {
+            Platform::Windows
+        } else if cfg!(target_os = "macos") {
+            Platform::Osx
+        } else if cfg!(target_os = "linux") {
+            Platform::Linux
+        } else {
+            return None;
+        };
+        let arch = if cfg!(target_arch = "x86_64") {
+            Arch::X64
+        } else if cfg!(target_arch = "aarch64") {
+            Arch::Arm64
+        } else {
+            return None;
+        };
+        Target::is_supported(platform, arch).then_some(Target { platform, arch })
+    }
+
     pub fn get_rust_architecture_representation(&self) -> String {
         let arch = match self.arch {
             Arch::X64 => "x86_64",
             Arch::Arm64 => "aarch64",
+            Arch::Arm => "armv7",
         };
-        let platform = match self.platform {
-            Platform::Windows => "pc-windows-msvc",
-            Platform::Android => "linux-android",
-            Platform::Ios => "apple-ios",
-            Platform::Osx => "apple-darwin",
+        let platform = match (self.platform, self.arch) {
+            (Platform::Windows, _) => "pc-windows-msvc",
+            // 32 bit ARM uses the hard float EABI triple
+            (Platform::Android, Arch::Arm) => "linux-androideabi",
+            (Platform::Android, _) => "linux-android",
+            (Platform::Ios, _) => "apple-ios",
+            (Platform::Osx, _) => "apple-darwin",
+            (Platform::Linux, _) => "unknown-linux-gnu",
         };
 
         format!("{arch}-{platform}")
@@ -74,127 +146,268 @@ impl Target {
 enum Cmd {
     /// Build Rust native libraries for a platform, stage them into destination,
     /// then optionally build .NET.
-    BuildNative {
-        /// Targets
+    BuildNative(BuildNativeArgs),
+    /// Build static libraries for iOS device, iOS simulator and Mac Catalyst
+    /// and bundle them into an XCFramework (macOS only).
+    BuildXcframework {
+        /// Use release profile
+        #[arg(long)]
+        release: bool,
+
+        #[command(flatten)]
+        dry_run: DryRunArgs,
+    },
+    /// Check toolchains, SDKs and the project layout, and print how to fix
+    /// what is missing.
+    Doctor {
+        /// Targets to check for, defaults to the host. What only other
+        /// targets need is reported as a warning.
         #[arg(
-            long, 
-            required = true, 
+            long,
             num_args=1..,
-            help = "Build target in the form <platform>-<arch>",
-            long_help = r#"
+            help = "Target in the form <platform>-<arch> to check for, defaults to the host"
+        )]
+        targets: Vec<Target>,
+
+        #[command(flatten)]
+        android: ndk::AndroidArgs,
+    },
+    /// Watch rust_fractal sources and rebuild, restage and repack the native
+    /// library on every change.
+    MonitorRustSource {
+        /// Targets, defaults to the host
+        #[arg(
+            long,
+            num_args=1..,
+            help = "Build target in the form <platform>-<arch>, defaults to the host"
+        )]
+        targets: Vec<Target>,
+
+        /// Use release profile for Rust + dotnet
+        #[arg(long)]
+        release: bool,
+
+        /// restores solution after every repack
+        #[arg(long)]
+        reload_dotnet: bool,
+
+        /// Quiet period after the last change before rebuilding, in milliseconds
+        #[arg(long, default_value_t = 500)]
+        debounce_ms: u64,
+
+        #[command(flatten)]
+        android: ndk::AndroidArgs,
+    },
+}
+
+#[derive(Debug, Args)]
+struct BuildNativeArgs {
+    /// Targets
+    #[arg(
+        long,
+        required = true,
+        num_args=1..,
+        help = "Build target in the form <platform>-<arch>",
+        long_help = r#"
 Build target in the form <platform>-<arch>.
 
 Examples:
   --target windows-x64
   --target android-arm64
+  --target android-x64     (emulator)
+  --target android-arm     (armeabi-v7a)
   --target ios-arm64
   --target osx-arm64
+  --target linux-x64
 
 This option may be repeated to build multiple targets.
 "#
-        )]
-        targets: Vec<Target>,
+    )]
+    targets: Vec<Target>,
 
-        /// Use release profile for Rust + dotnet
-        #[arg(long)]
-        release: bool,
+    /// Use release profile for Rust + dotnet
+    #[arg(long)]
+    release: bool,
 
-        /// cleans up the nugetpackages before building.
-        #[arg(long)]
-        clean_nuget_dir: bool,
+    /// cleans up the nugetpackages before building.
+    #[arg(long)]
+    clean_nuget_dir: bool,
 
-        /// restores solution with the current build hash as Parameter
-        #[arg(long)]
-        reload_dotnet: bool,
+    /// restores solution with the current build hash as Parameter
+    #[arg(long)]
+    reload_dotnet: bool,
 
-        /// .NET version (defaults to 10)
-        #[arg(long, value_enum,  default_value_t = paths::DotNetVersion::default())]
-        dotnet_version: paths::DotNetVersion,
+    /// .NET version (defaults to 10)
+    #[arg(long, value_enum,  default_value_t = paths::DotNetVersion::default())]
+    dotnet_version: paths::DotNetVersion,
 
-        /// Build the .NET MAUI solution after staging natives
-        #[arg(long)]
-        build_dotnet: bool,
+    /// Build the .NET MAUI solution after staging natives
+    #[arg(long)]
+    build_dotnet: bool,
 
-    },
+    /// NuGet package version, defaults to one derived from the crate version and git
+    #[arg(long, value_parser = parse_package_version)]
+    package_version: Option<String>,
+
+    #[command(flatten)]
+    android: ndk::AndroidArgs,
+
+    #[command(flatten)]
+    dry_run: DryRunArgs,
+}
+
+impl BuildNativeArgs {
+    /// Builds, stages and packs the targets, then restores and builds the
+    /// MAUI app as requested.
+    fn run(&self, exec: &mut dyn Executor, layout: &ProjectLayout) -> Result<()> {
+        if self.build_dotnet
+            && let Some(target) = self.targets.iter().find(|t| t.platform == Platform::Linux)
+        {
+            anyhow::bail!(
+                "the MAUI app has no Linux head to build for {target}, use the NuGet package in {} \
+                 from a .NET project instead",
+                layout.bindings_nupkgs().display()
+            );
+        }
+
+        if self.clean_nuget_dir
+            && let Err(e) = exec.remove_dir_all(&layout.bindings_nupkgs())
+        {
+            eprintln!("unable to remove builde dirs due to  {e:#}. Continuing....");
+        }
+        build_native::stage_and_pack(
+            exec,
+            layout,
+            &self.targets,
+            self.release,
+            &self.android,
+            self.package_version.as_deref(),
+        )?;
+        if self.reload_dotnet {
+            reload_dotnet_packages(exec, layout)?;
+        }
+
+        if self.build_dotnet {
+            for target in &self.targets {
+                build_dotnet_solution(
+                    exec,
+                    layout,
+                    target.platform,
+                    target.arch,
+                    self.dotnet_version,
+                    self.release,
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Args)]
+struct DryRunArgs {
+    /// Print the steps (commands, copies, file writes) instead of running them
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Format of the --dry-run plan
+    #[arg(long, value_enum, default_value_t = PlanFormat::default(), requires = "dry_run")]
+    plan_format: PlanFormat,
+}
+
+impl DryRunArgs {
+    /// Runs `build` with the real executor, or records and prints its plan.
+    fn execute(&self, build: impl FnOnce(&mut dyn Executor) -> Result<()>) -> Result<()> {
+        if !self.dry_run {
+            return build(&mut process::System);
+        }
+        let mut plan = Plan::default();
+        build(&mut plan)?;
+        plan.print(self.plan_format)
+    }
 }
 
 fn main() -> Result<()> {
     let cli = Cli::parse();
+    let layout = ProjectLayout::discover(&cli.layout).context("failed to locate the project")?;
 
     match cli.cmd {
-        Cmd::BuildNative {
+        Cmd::BuildNative(args) => args.dry_run.execute(|exec| args.run(exec, &layout)),
+        Cmd::BuildXcframework { release, dry_run } => {
+            if !dry_run.dry_run && !cfg!(target_os = "macos") {
+                anyhow::bail!("building an XCFramework requires a macOS host with Xcode");
+            }
+            dry_run.execute(|exec| apple::build_xcframework(exec, &layout, release).map(drop))
+        }
+        Cmd::Doctor { targets, android } => {
+            doctor::doctor(&layout, &targets_or_host(targets)?, &android)
+        }
+        Cmd::MonitorRustSource {
             targets,
             release,
             reload_dotnet,
-            dotnet_version,
-            build_dotnet,
-            clean_nuget_dir
-        } => {
-            if clean_nuget_dir && let Err(e) = fs::remove_dir_all(paths::bindings_nupkgs()){
-                eprintln!("unable to remove builde dirs due to  {e}. Continuing....");
-                
-            }
-            fs::create_dir_all(paths::bindings_runtime_dir()).with_context(|| {
-                format!(
-                    "create_dir_all failed: {}",
-                    paths::bindings_runtime_dir().display()
-                )
-            })?;
-
-            for target in &targets {
-                build_native::build_and_stage(target, release)?;
-            }
-            let nuget_package_version = format!("1.0.{}", build_hash());
-            build_native::pack_nuget(release, &nuget_package_version)?;
-            if reload_dotnet{
-                println!("Removing cache file");
-                if let Some(cache_file) = paths::maui_obj_nuget_cache()
-                && let Err(e) = fs::remove_file(cache_file){
-                    eprintln!("failed to delete nuget cache due to {e}");
-                }
-
-                println!("Reloading package cache");
-                let mut restore = process::cmd_in_dir("dotnet", &paths::maui_root());
-                restore
-                    .arg("restore")
-                    .arg(paths::maui_project_file());
-                process::run(restore)?;
-            } 
-
-            if build_dotnet {
-                for target in targets {
-                        build_dotnet_solution(
-                            target.platform,
-                            target.arch,
-                            dotnet_version,
-                            release,
-                        )?;
-                }
-            }
+            debounce_ms,
+            android,
+        } => monitor::monitor_rust_source(
+            &layout,
+            &targets_or_host(targets)?,
+            release,
+            reload_dotnet,
+            &android,
+            Duration::from_millis(debounce_ms),
+        ),
+    }
+}
 
-            Ok(())
-        }
-        
+/// `targets`, or the host when none are given.
+fn targets_or_host(targets: Vec<Target>) -> Result<Vec<Target>> {
+    if !targets.is_empty() {
+        return Ok(targets);
     }
+    Ok(vec![Target::host().context(
+        "the host is not a supported target, pass --targets",
+    )?])
 }
 
+fn parse_package_version(s: &str) -> Result<String> {
+    version::validate(s)?;
+    Ok(s.to_string())
+}
+
+/// Drops the MAUI project's NuGet cache and restores it, picking up a
+/// freshly packed package.
+pub(crate) fn reload_dotnet_packages(
+    exec: &mut dyn Executor,
+    layout: &ProjectLayout,
+) -> Result<()> {
+    let csproj = layout.maui_project_file()?;
+    if let Some(cache_file) = layout.maui_obj_nuget_cache()
+        && let Err(e) = exec.remove_file(&cache_file)
+    {
+        eprintln!("failed to delete nuget cache due to {e:#}");
+    }
+
+    let mut restore = process::cmd_in_dir("dotnet", layout.maui_root());
+    restore.arg("restore").arg(csproj);
+    exec.run(restore).context("dotnet restore failed")
+}
 
 fn build_dotnet_solution(
+    exec: &mut dyn Executor,
+    layout: &ProjectLayout,
     platform: paths::Platform,
     arch: paths::Arch,
     version: paths::DotNetVersion,
     release: bool,
 ) -> Result<()> {
-    let repo_root = paths::project_root();
     let config = if release { "Release" } else { "Debug" };
 
     let tfm = dotnet_tfm(version, platform);
-    let rid = paths::dotnet_rid(platform, arch);
+    let rid = paths::dotnet_rid(platform, arch)?;
 
-    let csproj = paths::maui_project_file();
+    let csproj = layout.maui_project_file()?;
 
-
-    let mut cmd = process::cmd_in_dir("dotnet", &repo_root);
+    let mut cmd = process::cmd_in_dir("dotnet", layout.project_root());
     cmd.arg("build")
         .arg(csproj)
         .arg("-c")
@@ -207,6 +420,286 @@ fn build_dotnet_solution(
     if platform == paths::Platform::Windows {
         cmd.arg("/p:UseMonoRuntime=false");
     }
-    process::run(cmd)?;
+    exec.run(cmd).context("dotnet build failed")?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plan::Step;
+    use serde_json::json;
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    /// Records `xtask <args>` run from this checkout.
+    fn plan(args: &[&str]) -> (ProjectLayout, Plan, Result<()>) {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("..");
+        let cli = Cli::try_parse_from(
+            ["xtask"]
+                .iter()
+                .chain(args)
+                .chain(&["--project-root", root.to_str().unwrap()]),
+        )
+        .unwrap();
+        let layout = ProjectLayout::discover(&cli.layout).unwrap();
+        let Cmd::BuildNative(args) = cli.cmd else {
+            panic!("expected build-native");
+        };
+        let mut plan = Plan::default();
+        let result = args.run(&mut plan, &layout);
+        (layout, plan, result)
+    }
+
+    fn path(path: &Path) -> &str {
+        path.to_str().unwrap()
+    }
+
+    /// An NDK with only the API 21 clang wrappers of the Android ABIs, for every host.
+    fn fake_ndk(name: &str) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("xtask-{name}-{}", std::process::id()));
+        for host in ["linux-x86_64", "darwin-x86_64", "windows-x86_64"] {
+            let bin = root.join("toolchains/llvm/prebuilt").join(host).join("bin");
+            fs::create_dir_all(&bin).unwrap();
+            for triple in [
+                "aarch64-linux-android",
+                "x86_64-linux-android",
+                "armv7a-linux-androideabi",
+            ] {
+                for ext in ["", ".cmd"] {
+                    fs::write(bin.join(format!("{triple}21-clang{ext}")), "").unwrap();
+                }
+            }
+        }
+        root
+    }
+
+    #[test]
+    fn every_android_abi_links_through_the_ndk_environment() {
+        let ndk = fake_ndk("plan-android-abis");
+        let (_, plan, result) = plan(&[
+            "build-native",
+            "--targets",
+            "android-arm64",
+            "android-x64",
+            "android-arm",
+            "--ndk",
+            path(&ndk),
+        ]);
+        fs::remove_dir_all(&ndk).unwrap();
+        result.unwrap();
+
+        // several targets build in parallel
+        let cargo_runs: Vec<_> = plan
+            .steps
+            .iter()
+            .flat_map(|step| match step {
+                Step::Parallel { steps } => steps.iter().collect(),
+                step => vec![step],
+            })
+            .filter_map(|step| match step {
+                Step::Run {
+                    program, args, env, ..
+                } if program == "cargo" => Some((args, env)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(cargo_runs.len(), 3);
+        for ((args, env), (triple, clang)) in cargo_runs.into_iter().zip([
+            ("aarch64-linux-android", "aarch64-linux-android21-clang"),
+            ("x86_64-linux-android", "x86_64-linux-android21-clang"),
+            (
+                "armv7-linux-androideabi",
+                "armv7a-linux-androideabi21-clang",
+            ),
+        ]) {
+            assert_eq!(args[4..6], ["--target", triple]);
+            let var = format!(
+                "CARGO_TARGET_{}_LINKER",
+                triple.to_uppercase().replace('-', "_")
+            );
+            let linker = Path::new(&env[&var]);
+            assert!(linker.starts_with(&ndk), "{var}");
+            let name = linker.file_name().unwrap().to_str().unwrap();
+            assert!(name.starts_with(clang), "{var} = {name}");
+        }
+    }
+
+    #[test]
+    fn cargo_config_leaves_the_linkers_to_xtask() {
+        let config = Path::new(env!("CARGO_MANIFEST_DIR")).join("../.cargo/config.toml");
+        let config: toml::Table = toml::from_str(&fs::read_to_string(config).unwrap()).unwrap();
+        // linkers come from CARGO_TARGET_<TRIPLE>_LINKER, see ndk.rs
+        assert!(!config.contains_key("target"), "{config:#?}");
+        let aliases = config["alias"].as_table().unwrap();
+        assert!(
+            aliases["build-package-android-all-release"]
+                .as_str()
+                .unwrap()
+                .contains("android-arm64 android-x64 android-arm")
+        );
+    }
+
+    #[test]
+    fn linux_targets_have_no_dotnet_build() {
+        let (_, plan, result) = plan(&["build-native", "--targets", "linux-x64", "--build-dotnet"]);
+        let err = result.unwrap_err().to_string();
+        assert!(err.contains("no Linux head"), "{err}");
+        // rejected before anything is built
+        assert!(plan.steps.is_empty(), "{:#?}", plan.steps);
+    }
+
+    #[test]
+    fn plans_a_linux_build_as_json() {
+        if !cfg!(target_os = "linux") {
+            return;
+        }
+        let (layout, plan, result) = plan(&[
+            "build-native",
+            "--targets",
+            "linux-x64",
+            "--release",
+            "--package-version",
+            "1.2.3",
+        ]);
+        result.unwrap();
+
+        let triple = "x86_64-unknown-linux-gnu";
+        let built = layout
+            .cargo_output_dir(triple, "release")
+            .join("librust_fractal.so");
+        let staged = layout.destination_native_lib_path(Platform::Linux, Arch::X64);
+        let expected = json!({
+            "steps": [
+                {
+                    "kind": "run",
+                    "program": "cargo",
+                    "args": [
+                        "build", "-p", "rust_fractal", "--lib",
+                        "--target", triple,
+                        "--target-dir", path(&layout.cargo_target_dir(triple)),
+                        "--release",
+                    ],
+                    "cwd": path(layout.rust_fractal_root()),
+                    "env": {},
+                },
+                { "kind": "create_dir", "path": path(&layout.bindings_runtime_dir()) },
+                { "kind": "create_dir", "path": path(staged.parent().unwrap()) },
+                { "kind": "copy", "from": path(&built), "to": path(&staged) },
+                {
+                    "kind": "run",
+                    "program": "dotnet",
+                    "args": [
+                        "pack", path(&layout.bindings_root_project_file()),
+                        "-c", "Release",
+                        "-o", path(&layout.bindings_nupkgs()),
+                        "/p:PackageVersion=1.2.3",
+                    ],
+                    "cwd": path(&layout.bindings_root()),
+                    "env": {},
+                },
+                {
+                    "kind": "write",
+                    "path": path(&layout.bindings_version_props()),
+                    "contents": "<Project>\n  <PropertyGroup>\n    \
+                                 <RustFractalsVersion>1.2.3</RustFractalsVersion>\n  \
+                                 </PropertyGroup>\n</Project>\n",
+                },
+            ]
+        });
+        assert_eq!(serde_json::to_value(&plan).unwrap(), expected);
+    }
+
+    #[test]
+    fn plans_an_android_build_with_the_ndk_linker() {
+        let ndk = fake_ndk("plan-android");
+        let (layout, plan, result) = plan(&[
+            "build-native",
+            "--targets",
+            "android-arm64",
+            "--build-dotnet",
+            "--package-version",
+            "1.2.3",
+            "--ndk",
+            path(&ndk),
+        ]);
+        fs::remove_dir_all(&ndk).unwrap();
+        result.unwrap();
+
+        let kinds = serde_json::to_value(&plan).unwrap()["steps"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|step| step["kind"].as_str().unwrap().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            [
+                "run",
+                "create_dir",
+                "create_dir",
+                "copy",
+                "run",
+                "write",
+                "run"
+            ]
+        );
+
+        let triple = "aarch64-linux-android";
+        let Step::Run {
+            program, args, env, ..
+        } = &plan.steps[0]
+        else {
+            unreachable!();
+        };
+        assert_eq!(program, "cargo");
+        assert_eq!(args[4..6], ["--target", triple]);
+        assert!(!args.contains(&"--release".to_string()));
+        let linker = Path::new(&env["CARGO_TARGET_AARCH64_LINUX_ANDROID_LINKER"]);
+        assert!(linker.starts_with(&ndk));
+        assert!(
+            linker
+                .file_name()
+                .unwrap()
+                .to_str()
+                .unwrap()
+                .starts_with("aarch64-linux-android21-clang")
+        );
+        assert_eq!(env["CC_aarch64_linux_android"], path(linker));
+        assert!(env["AR_aarch64_linux_android"].starts_with(path(&ndk)));
+
+        let staged = layout.destination_native_lib_path(Platform::Android, Arch::Arm64);
+        assert_eq!(
+            plan.steps[3],
+            Step::Copy {
+                from: layout
+                    .cargo_output_dir(triple, "debug")
+                    .join("librust_fractal.so"),
+                to: staged,
+            }
+        );
+
+        let Step::Run {
+            program, args, cwd, ..
+        } = &plan.steps[6]
+        else {
+            unreachable!();
+        };
+        assert_eq!(program, "dotnet");
+        assert_eq!(cwd.as_deref(), Some(layout.project_root()));
+        let csproj = layout.maui_project_file().unwrap();
+        assert_eq!(
+            args,
+            &[
+                "build",
+                path(&csproj),
+                "-c",
+                "Debug",
+                "-f",
+                "net10.0-android",
+                "-r",
+                "android-arm64",
+            ]
+        );
+    }
+}
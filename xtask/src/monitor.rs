@@ -0,0 +1,110 @@
+use crate::Target;
+use crate::build_native;
+use crate::ndk::AndroidArgs;
+use crate::paths::ProjectLayout;
+use crate::process;
+use anyhow::{Context, Result};
+use notify_debouncer_mini::notify::RecursiveMode;
+use notify_debouncer_mini::{DebounceEventResult, new_debouncer};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::Duration;
+
+/// Watches `rust_fractal/src` (plus the manifest and build script) and reruns
+/// build, staging and packing once changes have settled for `debounce`.
+/// Runs until interrupted; a failing build is reported and the watch goes on.
+pub fn monitor_rust_source(
+    layout: &ProjectLayout,
+    targets: &[Target],
+    release: bool,
+    reload_dotnet: bool,
+    android: &AndroidArgs,
+    debounce: Duration,
+) -> Result<()> {
+    layout.require_rust_fractal()?;
+
+    let (tx, rx) = mpsc::channel::<DebounceEventResult>();
+    let mut debouncer = new_debouncer(debounce, tx).context("failed to start file watcher")?;
+    for (path, mode) in watched_paths(layout) {
+        debouncer
+            .watcher()
+            .watch(&path, mode)
+            .with_context(|| format!("failed to watch {}", path.display()))?;
+    }
+
+    rebuild(layout, targets, release, reload_dotnet, android);
+    eprintln!(
+        "watching {} for changes, press Ctrl+C to stop",
+        layout.rust_fractal_src().display()
+    );
+
+    for result in rx {
+        let events = match result {
+            Ok(events) => events,
+            Err(e) => {
+                eprintln!("watch error: {e}");
+                continue;
+            }
+        };
+
+        let mut changed: Vec<&Path> = events
+            .iter()
+            .map(|event| event.path.as_path())
+            .filter(|path| is_source_file(path))
+            .collect();
+        if changed.is_empty() {
+            continue;
+        }
+        changed.sort();
+        changed.dedup();
+        for path in changed {
+            eprintln!("changed: {}", path.display());
+        }
+        rebuild(layout, targets, release, reload_dotnet, android);
+    }
+    Ok(())
+}
+
+fn watched_paths(layout: &ProjectLayout) -> [(PathBuf, RecursiveMode); 3] {
+    let rust_root = layout.rust_fractal_root();
+    [
+        (layout.rust_fractal_src(), RecursiveMode::Recursive),
+        (layout.rust_fractal_manifest(), RecursiveMode::NonRecursive),
+        (rust_root.join("build.rs"), RecursiveMode::NonRecursive),
+    ]
+}
+
+/// Rust sources and manifests, skipping editor swap and backup files.
+fn is_source_file(path: &Path) -> bool {
+    let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+        return false;
+    };
+    !name.starts_with('.') && (name.ends_with(".rs") || name.ends_with(".toml"))
+}
+
+fn rebuild(
+    layout: &ProjectLayout,
+    targets: &[Target],
+    release: bool,
+    reload_dotnet: bool,
+    android: &AndroidArgs,
+) {
+    let result = build_native::stage_and_pack(
+        &mut process::System,
+        layout,
+        targets,
+        release,
+        android,
+        None,
+    )
+    .and_then(|()| {
+        if reload_dotnet {
+            crate::reload_dotnet_packages(&mut process::System, layout)?;
+        }
+        Ok(())
+    });
+    match result {
+        Ok(()) => eprintln!("rebuild finished"),
+        Err(e) => eprintln!("rebuild failed: {e:#}"),
+    }
+}
@@ -0,0 +1,302 @@
+use crate::Target;
+use crate::paths::{Arch, Platform};
+use anyhow::{Context, Result, anyhow, bail};
+use clap::Args;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Lowest Android API level the app supports (`SupportedOSPlatformVersion`).
+pub const DEFAULT_API_LEVEL: u32 = 21;
+
+/// Android toolchain options shared by the build commands.
+#[derive(Args, Clone, Debug)]
+pub struct AndroidArgs {
+    /// Android NDK root, defaults to ANDROID_NDK_HOME or the newest NDK under ANDROID_HOME/ndk
+    #[arg(long)]
+    pub ndk: Option<PathBuf>,
+
+    /// Minimum Android API level to link against
+    #[arg(long, default_value_t = DEFAULT_API_LEVEL)]
+    pub android_api: u32,
+}
+
+/// An installed Android NDK.
+#[derive(Clone, Debug)]
+pub struct Ndk {
+    root: PathBuf,
+}
+
+impl Ndk {
+    /// Finds the NDK in order: `explicit` (the `--ndk` flag), `ANDROID_NDK_HOME`,
+    /// then the highest version in `ANDROID_HOME/ndk` (or `ANDROID_SDK_ROOT/ndk`).
+    pub fn locate(explicit: Option<&Path>) -> Result<Self> {
+        Self::locate_with(explicit, env_path)
+    }
+
+    /// [`Ndk::locate`] reading environment variables through `env_path`.
+    fn locate_with(
+        explicit: Option<&Path>,
+        env_path: impl Fn(&str) -> Option<PathBuf>,
+    ) -> Result<Self> {
+        if let Some(root) = explicit {
+            return Self::at(root).context("--ndk does not point to an NDK");
+        }
+        if let Some(root) = env_path("ANDROID_NDK_HOME") {
+            return Self::at(&root).context("ANDROID_NDK_HOME does not point to an NDK");
+        }
+
+        for var in ["ANDROID_HOME", "ANDROID_SDK_ROOT"] {
+            let Some(sdk) = env_path(var) else {
+                continue;
+            };
+            let side_by_side = sdk.join("ndk");
+            if let Some(root) = newest_version(&side_by_side)? {
+                return Self::at(&root);
+            }
+        }
+
+        Err(anyhow!(
+            "Android NDK not found: pass --ndk <path>, set ANDROID_NDK_HOME, \
+             or install one through the SDK manager under ANDROID_HOME/ndk"
+        ))
+    }
+
+    fn at(root: &Path) -> Result<Self> {
+        let ndk = Self {
+            root: root.to_path_buf(),
+        };
+        let prebuilt = ndk.root.join("toolchains").join("llvm").join("prebuilt");
+        if !prebuilt.is_dir() {
+            bail!("no LLVM toolchain at {}", prebuilt.display());
+        }
+        Ok(ndk)
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// `toolchains/llvm/prebuilt/<host>/bin`. macOS NDKs ship universal
+    /// binaries under `darwin-x86_64` for both host architectures.
+    pub fn toolchain_bin(&self) -> Result<PathBuf> {
+        let host = if cfg!(target_os = "windows") {
+            "windows-x86_64"
+        } else if cfg!(target_os = "macos") {
+            "darwin-x86_64"
+        } else if cfg!(target_os = "linux") {
+            "linux-x86_64"
+        } else {
+            bail!("the Android NDK has no prebuilt toolchain for this host");
+        };
+        let bin = self
+            .root
+            .join("toolchains")
+            .join("llvm")
+            .join("prebuilt")
+            .join(host)
+            .join("bin");
+        if !bin.is_dir() {
+            bail!("NDK toolchain for this host not found: {}", bin.display());
+        }
+        Ok(bin)
+    }
+
+    /// The API level specific clang wrapper, e.g. `aarch64-linux-android21-clang`.
+    pub fn clang(&self, target: &Target, api_level: u32) -> Result<PathBuf> {
+        let triple = clang_triple(target)?;
+        let ext = if cfg!(target_os = "windows") {
+            ".cmd"
+        } else {
+            ""
+        };
+        let clang = self
+            .toolchain_bin()?
+            .join(format!("{triple}{api_level}-clang{ext}"));
+        if !clang.is_file() {
+            bail!(
+                "{} not found, the NDK at {} may not support API level {api_level}",
+                clang.display(),
+                self.root.display()
+            );
+        }
+        Ok(clang)
+    }
+
+    /// Environment for `cargo build --target <triple>` to link with the NDK.
+    pub fn cargo_env(&self, target: &Target, api_level: u32) -> Result<Vec<(String, PathBuf)>> {
+        let triple = target.get_rust_architecture_representation();
+        let triple_env = triple.to_uppercase().replace('-', "_");
+        let clang = self.clang(target, api_level)?;
+        let ar = self.toolchain_bin()?.join(if cfg!(target_os = "windows") {
+            "llvm-ar.exe"
+        } else {
+            "llvm-ar"
+        });
+
+        // the linker for rustc, CC/AR for crates that compile C through `cc`
+        let cc_triple = triple.replace('-', "_");
+        Ok(vec![
+            (format!("CARGO_TARGET_{triple_env}_LINKER"), clang.clone()),
+            (format!("CC_{cc_triple}"), clang),
+            (format!("AR_{cc_triple}"), ar),
+        ])
+    }
+}
+
+/// A set, non-empty environment variable.
+fn env_path(var: &str) -> Option<PathBuf> {
+    env::var_os(var)
+        .filter(|value| !value.is_empty())
+        .map(PathBuf::from)
+}
+
+/// Target triple prefix of the NDK's clang wrappers, which differs from
+/// Rust's for 32 bit ARM.
+fn clang_triple(target: &Target) -> Result<&'static str> {
+    match (target.platform, target.arch) {
+        (Platform::Android, Arch::Arm64) => Ok("aarch64-linux-android"),
+        (Platform::Android, Arch::X64) => Ok("x86_64-linux-android"),
+        (Platform::Android, Arch::Arm) => Ok("armv7a-linux-androideabi"),
+        _ => Err(anyhow!("{target:?} is not an Android target")),
+    }
+}
+
+/// The side-by-side NDK directory with the highest version, e.g.
+/// `27.2.12479018` over `26.3.11579264`.
+fn newest_version(side_by_side: &Path) -> Result<Option<PathBuf>> {
+    let entries = match fs::read_dir(side_by_side) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => {
+            return Err(e).with_context(|| format!("read_dir failed: {}", side_by_side.display()));
+        }
+    };
+
+    let mut newest: Option<(Vec<u64>, PathBuf)> = None;
+    for entry in entries {
+        let path = entry?.path();
+        let Some(version) = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.split('.').map(|part| part.parse().ok()).collect())
+        else {
+            continue;
+        };
+        if path.is_dir() && newest.as_ref().is_none_or(|(best, _)| version > *best) {
+            newest = Some((version, path));
+        }
+    }
+    Ok(newest.map(|(_, path)| path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    /// A scratch directory removed when dropped.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let dir = env::temp_dir().join(format!("xtask-ndk-{name}-{}", std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            Self(dir)
+        }
+
+        /// An NDK at `relative` with an empty LLVM toolchain.
+        fn ndk(&self, relative: &str) -> PathBuf {
+            let root = self.0.join(relative);
+            fs::create_dir_all(root.join("toolchains/llvm/prebuilt")).unwrap();
+            root
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn locate(explicit: Option<&Path>, vars: &[(&str, &Path)]) -> Result<Ndk> {
+        let vars: HashMap<_, _> = vars.iter().copied().collect();
+        Ndk::locate_with(explicit, |var| vars.get(var).map(|path| path.to_path_buf()))
+    }
+
+    #[test]
+    fn looks_up_the_flag_then_ndk_home_then_the_sdk() {
+        let dir = TempDir::new("lookup");
+        let flag = dir.ndk("flag");
+        let home = dir.ndk("home");
+        let sdk = dir.0.join("sdk");
+        let side_by_side = dir.ndk("sdk/ndk/26.1.10909125");
+        let sdk_root = dir.0.join("sdk-root");
+        let from_sdk_root = dir.ndk("sdk-root/ndk/25.2.9519653");
+
+        let all = [
+            ("ANDROID_NDK_HOME", home.as_path()),
+            ("ANDROID_HOME", &sdk),
+            ("ANDROID_SDK_ROOT", &sdk_root),
+        ];
+        assert_eq!(locate(Some(&flag), &all).unwrap().root(), flag);
+        assert_eq!(locate(None, &all).unwrap().root(), home);
+        assert_eq!(locate(None, &all[1..]).unwrap().root(), side_by_side);
+        assert_eq!(locate(None, &all[2..]).unwrap().root(), from_sdk_root);
+
+        // an SDK without NDKs falls through to the next variable
+        let empty_sdk = dir.0.join("empty-sdk");
+        fs::create_dir_all(empty_sdk.join("ndk")).unwrap();
+        let vars = [("ANDROID_HOME", empty_sdk.as_path()), all[2]];
+        assert_eq!(locate(None, &vars).unwrap().root(), from_sdk_root);
+
+        let err = locate(None, &[]).unwrap_err().to_string();
+        assert!(err.contains("Android NDK not found"), "{err}");
+    }
+
+    #[test]
+    fn explicit_locations_must_hold_an_ndk() {
+        let dir = TempDir::new("invalid");
+        let home = dir.ndk("home");
+        let not_an_ndk = dir.0.join("not-an-ndk");
+        fs::create_dir_all(&not_an_ndk).unwrap();
+
+        // a wrong --ndk or ANDROID_NDK_HOME is reported, not skipped
+        let err = locate(Some(&not_an_ndk), &[("ANDROID_NDK_HOME", &home)]).unwrap_err();
+        assert!(format!("{err:#}").contains("--ndk does not point to an NDK"));
+        let err = locate(None, &[("ANDROID_NDK_HOME", &not_an_ndk)]).unwrap_err();
+        assert!(format!("{err:#}").contains("no LLVM toolchain"));
+    }
+
+    #[test]
+    fn picks_the_numerically_highest_version() {
+        let dir = TempDir::new("versions");
+        let side_by_side = dir.0.join("ndk");
+        assert_eq!(newest_version(&side_by_side).unwrap(), None);
+
+        for version in ["9.9.9", "10.0.1", "10.0.0", "21.4.7075529"] {
+            dir.ndk(&format!("ndk/{version}"));
+        }
+        assert_eq!(
+            newest_version(&side_by_side).unwrap(),
+            Some(side_by_side.join("21.4.7075529"))
+        );
+
+        fs::remove_dir_all(side_by_side.join("21.4.7075529")).unwrap();
+        // "9.9.9" sorts after "10.0.1" as a string
+        assert_eq!(
+            newest_version(&side_by_side).unwrap(),
+            Some(side_by_side.join("10.0.1"))
+        );
+
+        // names that are not versions and plain files are ignored
+        dir.ndk("ndk/99.0-beta");
+        dir.ndk("ndk/latest");
+        fs::write(side_by_side.join("100.0.0"), "").unwrap();
+        assert_eq!(
+            newest_version(&side_by_side).unwrap(),
+            Some(side_by_side.join("10.0.1"))
+        );
+    }
+}
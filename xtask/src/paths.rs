@@ -1,6 +1,10 @@
-use std::path::PathBuf;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
 
-use clap::ValueEnum;
+use anyhow::{Context, Result, bail};
+use clap::{Args, ValueEnum};
+use serde::Deserialize;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
 pub enum Platform {
@@ -8,12 +12,15 @@ pub enum Platform {
     Android,
     Ios,
     Osx,
+    Linux,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
 pub enum Arch {
     X64,
     Arm64,
+    /// 32 bit ARMv7, Android's armeabi-v7a
+    Arm,
 }
 
 #[derive(Default, Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
@@ -40,6 +47,7 @@ impl Platform {
             Platform::Android => "android",
             Platform::Ios => "ios",
             Platform::Osx => "osx",
+            Platform::Linux => "linux",
         }
     }
 
@@ -50,6 +58,17 @@ impl Platform {
     pub fn requires_windwos(self) -> bool {
         matches!(self, Self::Windows)
     }
+
+    pub fn requires_linux(self) -> bool {
+        matches!(self, Self::Linux)
+    }
+
+    /// Whether the machine xtask runs on can build for this platform.
+    pub fn builds_on_host(self) -> bool {
+        (!self.requires_macos() || cfg!(target_os = "macos"))
+            && (!self.requires_windwos() || cfg!(target_os = "windows"))
+            && (!self.requires_linux() || cfg!(target_os = "linux"))
+    }
 }
 
 impl Arch {
@@ -57,86 +76,273 @@ impl Arch {
         match self {
             Arch::X64 => "x64",
             Arch::Arm64 => "arm64",
+            Arch::Arm => "arm",
         }
     }
 }
-/// Root of the xtask crate (where xtask/Cargo.toml lives).
-pub fn xtask_root() -> PathBuf {
-    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+/// Config file in the project root that overrides the default layout.
+pub const LAYOUT_CONFIG_FILE: &str = "xtask.toml";
+
+/// Where the projects live when the checkout differs from the default layout.
+/// Relative paths on the command line are resolved against the working
+/// directory, those in the config file against the file's directory.
+#[derive(Args, Deserialize, Clone, Debug, Default)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct LayoutPaths {
+    /// rust_fractal crate directory, defaults to <project root>/rust_fractal
+    #[arg(long, global = true)]
+    pub rust_fractal_dir: Option<PathBuf>,
+
+    /// Directory with the RustFractals NuGet project and the packed feed,
+    /// defaults to <rust_fractal dir>/nuget
+    #[arg(long, global = true)]
+    pub nuget_dir: Option<PathBuf>,
+
+    /// MAUI app project, defaults to <project root>/mandelbrot/mandelbrot.csproj
+    #[arg(long, global = true)]
+    pub maui_project: Option<PathBuf>,
 }
 
-/// Project root directory containing all subprojects.
-/// Assumes xtask/ lives directly under the project root.
-pub fn project_root() -> PathBuf {
-    xtask_root()
-        .parent()
-        .expect("xtask_root() must have a parent directory")
-        .to_path_buf()
+/// Command line options locating the project, shared by every subcommand.
+#[derive(Args, Clone, Debug, Default)]
+pub struct LayoutArgs {
+    /// Checkout to work on, defaults to the nearest directory above the working
+    /// directory with an xtask.toml or rust_fractal/Cargo.toml
+    #[arg(long, global = true)]
+    pub project_root: Option<PathBuf>,
+
+    /// Layout config file, defaults to <project root>/xtask.toml if present
+    #[arg(long, global = true)]
+    pub layout_config: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub paths: LayoutPaths,
 }
 
-pub fn maui_root() -> PathBuf {
-    project_root().join("mandelbrot")
+/// Locations of the projects xtask builds, resolved once at startup.
+/// Nothing is checked here beyond the config file; commands check what
+/// they need ([`ProjectLayout::require_rust_fractal`],
+/// [`ProjectLayout::maui_project_file`]) so `doctor` can report the rest.
+#[derive(Clone, Debug)]
+pub struct ProjectLayout {
+    root: PathBuf,
+    rust_fractal: PathBuf,
+    nuget: PathBuf,
+    maui_project: PathBuf,
 }
 
-pub fn maui_obj() -> Option<PathBuf> {
-    let obj = maui_root().join("obj");
-    if obj.exists() {
-        return Some(obj);
+impl ProjectLayout {
+    /// Resolves the layout: flags override the config file, which overrides
+    /// the defaults relative to the project root.
+    pub fn discover(args: &LayoutArgs) -> Result<Self> {
+        let root = match &args.project_root {
+            Some(root) => absolute(root)?,
+            None => find_project_root()?,
+        };
+        if !root.is_dir() {
+            bail!("project root {} is not a directory", root.display());
+        }
+
+        let config_file = match &args.layout_config {
+            Some(file) => Some(absolute(file)?),
+            None => Some(root.join(LAYOUT_CONFIG_FILE)).filter(|file| file.is_file()),
+        };
+        let config = match &config_file {
+            Some(file) => load_layout_config(file)?,
+            None => LayoutPaths::default(),
+        };
+
+        let pick =
+            |flag: &Option<PathBuf>, configured: &Option<PathBuf>| -> Result<Option<PathBuf>> {
+                match flag {
+                    Some(path) => absolute(path).map(Some),
+                    None => Ok(configured.clone()),
+                }
+            };
+        let rust_fractal = pick(&args.paths.rust_fractal_dir, &config.rust_fractal_dir)?
+            .unwrap_or_else(|| root.join("rust_fractal"));
+        let nuget = pick(&args.paths.nuget_dir, &config.nuget_dir)?
+            .unwrap_or_else(|| rust_fractal.join("nuget"));
+        let maui_project = pick(&args.paths.maui_project, &config.maui_project)?
+            .unwrap_or_else(|| root.join("mandelbrot").join("mandelbrot.csproj"));
+
+        Ok(Self {
+            root,
+            rust_fractal,
+            nuget,
+            maui_project,
+        })
     }
-    None
-}
 
-pub fn maui_obj_nuget_cache() -> Option<PathBuf> {
-    let obj = maui_obj()?.join("project.nuget.cache");
-    if obj.exists() {
-        return Some(obj);
+    /// Project root directory containing all subprojects.
+    pub fn project_root(&self) -> &Path {
+        &self.root
     }
-    None
-}
 
-pub fn maui_project_file() -> PathBuf {
-    let file = maui_root().join("mandelbrot.csproj");
-    assert!(file.exists());
-    assert!(file.is_file());
-    file
-}
+    /// Path to the Rust library root (contains Cargo.toml)
+    pub fn rust_fractal_root(&self) -> &Path {
+        &self.rust_fractal
+    }
 
-/// c# nuget directory withing rust_fractal.
-pub fn rust_fractal_nuget() -> PathBuf {
-    rust_fractal_root().join("nuget")
-}
+    /// Sources of the Rust library.
+    pub fn rust_fractal_src(&self) -> PathBuf {
+        self.rust_fractal.join("src")
+    }
 
-/// c# packages location within rust_fractal
-pub fn bindings_root() -> PathBuf {
-    rust_fractal_nuget().join("RustFractals")
-}
-/// c# packages location within rust_fractal
-pub fn bindings_root_project_file() -> PathBuf {
-    bindings_root().join("RustFractals.csproj")
-}
-/// packed nuget artifacts
-pub fn bindings_nupkgs() -> PathBuf {
-    rust_fractal_nuget().join("nupkgs")
+    /// The C header rust_fractal's build script generates for Apple apps.
+    pub fn rust_fractal_include(&self) -> PathBuf {
+        self.rust_fractal.join("include")
+    }
+
+    pub fn rust_fractal_manifest(&self) -> PathBuf {
+        self.rust_fractal.join("Cargo.toml")
+    }
+
+    /// Validate that rust_fractal exists (we do NOT create this silently).
+    pub fn require_rust_fractal(&self) -> Result<()> {
+        let cargo_toml = self.rust_fractal_manifest();
+        if !cargo_toml.is_file() {
+            bail!(
+                "rust_fractal Cargo.toml not found at {}, pass --rust-fractal-dir or set \
+                 rust-fractal-dir in {LAYOUT_CONFIG_FILE}",
+                cargo_toml.display()
+            );
+        }
+        Ok(())
+    }
+
+    pub fn maui_root(&self) -> &Path {
+        self.maui_project.parent().unwrap_or(&self.root)
+    }
+
+    pub fn maui_obj_nuget_cache(&self) -> Option<PathBuf> {
+        let cache = self.maui_root().join("obj").join("project.nuget.cache");
+        cache.is_file().then_some(cache)
+    }
+
+    /// Where the MAUI project is expected, without checking.
+    pub fn maui_project_path(&self) -> &Path {
+        &self.maui_project
+    }
+
+    pub fn maui_project_file(&self) -> Result<PathBuf> {
+        if !self.maui_project.is_file() {
+            bail!(
+                "MAUI project not found at {}, pass --maui-project or set maui-project in \
+                 {LAYOUT_CONFIG_FILE}",
+                self.maui_project.display()
+            );
+        }
+        Ok(self.maui_project.clone())
+    }
+
+    /// c# packages location within rust_fractal
+    pub fn bindings_root(&self) -> PathBuf {
+        self.nuget.join("RustFractals")
+    }
+
+    /// c# packages location within rust_fractal
+    pub fn bindings_root_project_file(&self) -> PathBuf {
+        self.bindings_root().join("RustFractals.csproj")
+    }
+
+    /// MSBuild props with the last packed version, imported by the app so its
+    /// restore picks exactly that package.
+    pub fn bindings_version_props(&self) -> PathBuf {
+        self.nuget.join("RustFractals.version.props")
+    }
+
+    /// packed nuget artifacts
+    pub fn bindings_nupkgs(&self) -> PathBuf {
+        self.nuget.join("nupkgs")
+    }
+
+    /// Where native binaries should be staged.
+    pub fn bindings_runtime_dir(&self) -> PathBuf {
+        self.bindings_root().join("runtimes")
+    }
+
+    /// Full staging path:
+    /// `destination_root/runtimes/{platform}-{arch}/{name}{ext}`
+    pub fn destination_native_lib_path(&self, platform: Platform, arch: Arch) -> PathBuf {
+        self.bindings_runtime_dir()
+            .join(runtime_dir_name(platform, arch))
+            .join("native")
+            .join(native_lib_filename(platform))
+    }
+
+    /// Cargo target directory for builds of one target triple, kept apart so
+    /// builds for different targets can run at the same time.
+    pub fn cargo_target_dir(&self, cargo_target: &str) -> PathBuf {
+        self.rust_fractal
+            .join("target")
+            .join("xtask")
+            .join(cargo_target)
+    }
+
+    /// Where cargo puts the libraries built for `cargo_target`.
+    pub fn cargo_output_dir(&self, cargo_target: &str, profile_dir: &str) -> PathBuf {
+        self.cargo_target_dir(cargo_target)
+            .join(cargo_target)
+            .join(profile_dir)
+    }
+
+    /// Intermediate universal static libraries, one directory per XCFramework slice.
+    pub fn apple_build_dir(&self) -> PathBuf {
+        self.rust_fractal.join("target").join("apple")
+    }
+
+    /// The XCFramework for iOS and Mac Catalyst apps to link statically.
+    pub fn apple_xcframework(&self) -> PathBuf {
+        self.nuget
+            .join(format!("{}.xcframework", native_lib_stem()))
+    }
 }
 
-/// Path to the Rust library root (contains Cargo.toml)
-pub fn rust_fractal_root() -> PathBuf {
-    project_root().join("rust_fractal")
+/// The nearest directory above the working directory that looks like a
+/// checkout, else the checkout xtask was built from.
+fn find_project_root() -> Result<PathBuf> {
+    let is_root = |dir: &Path| {
+        dir.join(LAYOUT_CONFIG_FILE).is_file()
+            || dir.join("rust_fractal").join("Cargo.toml").is_file()
+    };
+    let cwd = env::current_dir().context("the working directory is not accessible")?;
+    if let Some(root) = cwd.ancestors().find(|dir| is_root(dir)) {
+        return Ok(root.to_path_buf());
+    }
+    // xtask/ lives directly under the project root
+    let built_from = Path::new(env!("CARGO_MANIFEST_DIR")).parent();
+    match built_from.filter(|dir| is_root(dir)) {
+        Some(root) => Ok(root.to_path_buf()),
+        None => bail!(
+            "no project found above {}, run xtask inside a checkout or pass --project-root",
+            cwd.display()
+        ),
+    }
 }
 
-/// Where native binaries should be staged.
-pub fn bindings_runtime_dir() -> PathBuf {
-    bindings_root().join("runtimes")
+/// Reads a layout config, resolving its paths against the file's directory.
+fn load_layout_config(file: &Path) -> Result<LayoutPaths> {
+    let text =
+        fs::read_to_string(file).with_context(|| format!("read failed: {}", file.display()))?;
+    let mut config: LayoutPaths = toml::from_str(&text)
+        .with_context(|| format!("invalid layout config: {}", file.display()))?;
+    let dir = file.parent().unwrap_or(Path::new("."));
+    for path in [
+        &mut config.rust_fractal_dir,
+        &mut config.nuget_dir,
+        &mut config.maui_project,
+    ]
+    .into_iter()
+    .flatten()
+    {
+        *path = dir.join(&*path);
+    }
+    Ok(config)
 }
 
-/// Validate that rust_fractal exists (we do NOT create this silently).
-pub fn assert_rust_fractal_exists() {
-    let cargo_toml = rust_fractal_root().join("Cargo.toml");
-    assert!(
-        cargo_toml.is_file(),
-        "Expected rust_fractal Cargo.toml at: {}",
-        cargo_toml.display()
-    );
+fn absolute(path: &Path) -> Result<PathBuf> {
+    std::path::absolute(path).with_context(|| format!("cannot resolve {}", path.display()))
 }
 
 /// `{platform}-{arch}`
@@ -152,14 +358,14 @@ pub fn native_lib_stem() -> &'static str {
 pub fn native_lib_prefix(platform: Platform) -> &'static str {
     match platform {
         Platform::Windows => "",
-        Platform::Android | Platform::Ios | Platform::Osx => "lib",
+        Platform::Android | Platform::Ios | Platform::Osx | Platform::Linux => "lib",
     }
 }
 
 pub fn native_lib_ext(platform: Platform) -> &'static str {
     match platform {
         Platform::Windows => "dll",
-        Platform::Android => "so",
+        Platform::Android | Platform::Linux => "so",
         Platform::Osx | Platform::Ios => "dylib",
     }
 }
@@ -175,16 +381,19 @@ pub fn dotnet_tfm(version: DotNetVersion, platform: Platform) -> String {
             // Keep the Windows min version explicit
             format!("{base}-windows10.0.19041.0")
         }
+        // Plain .NET, only the NuGet package targets Linux
+        Platform::Linux => base.to_string(),
     }
 }
 
-pub(crate) fn dotnet_rid(platform: Platform, arch: Arch) -> &'static str {
-    match (platform, arch) {
+pub(crate) fn dotnet_rid(platform: Platform, arch: Arch) -> Result<&'static str> {
+    Ok(match (platform, arch) {
         (Platform::Windows, Arch::X64) => "win-x64",
         (Platform::Windows, Arch::Arm64) => "win-arm64",
 
         (Platform::Android, Arch::Arm64) => "android-arm64",
         (Platform::Android, Arch::X64) => "android-x64",
+        (Platform::Android, Arch::Arm) => "android-arm",
 
         // MacCatalyst RIDs:
         (Platform::Osx, Arch::Arm64) => "maccatalyst-arm64",
@@ -193,8 +402,11 @@ pub(crate) fn dotnet_rid(platform: Platform, arch: Arch) -> &'static str {
         // iOS RIDs (device):
         (Platform::Ios, Arch::Arm64) => "ios-arm64",
 
-        _ => panic!("unsupported .NET RID for {:?} {:?}", platform, arch),
-    }
+        (Platform::Linux, Arch::X64) => "linux-x64",
+        (Platform::Linux, Arch::Arm64) => "linux-arm64",
+
+        _ => bail!("no .NET runtime identifier for {platform:?} {arch:?}"),
+    })
 }
 /// `{prefix}{stem}.{ext}`
 pub fn native_lib_filename(platform: Platform) -> String {
@@ -206,11 +418,187 @@ pub fn native_lib_filename(platform: Platform) -> String {
     )
 }
 
-/// Full staging path:
-/// `destination_root/runtimes/{platform}-{arch}/{name}{ext}`
-pub fn destination_native_lib_path(platform: Platform, arch: Arch) -> PathBuf {
-    bindings_runtime_dir()
-        .join(runtime_dir_name(platform, arch))
-        .join("native")
-        .join(native_lib_filename(platform))
+/// Static library name on Apple platforms, `lib{stem}.a`.
+pub fn static_lib_filename() -> String {
+    format!("lib{}.a", native_lib_stem())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A scratch project root removed when dropped.
+    struct TempRoot(PathBuf);
+
+    impl TempRoot {
+        fn new(name: &str) -> Self {
+            let dir = env::temp_dir().join(format!("xtask-paths-{name}-{}", std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            Self(dir)
+        }
+
+        fn write(&self, relative: &str, text: &str) -> PathBuf {
+            let file = self.0.join(relative);
+            fs::create_dir_all(file.parent().unwrap()).unwrap();
+            fs::write(&file, text).unwrap();
+            file
+        }
+
+        fn args(&self) -> LayoutArgs {
+            LayoutArgs {
+                project_root: Some(self.0.clone()),
+                ..LayoutArgs::default()
+            }
+        }
+    }
+
+    impl Drop for TempRoot {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn paths(layout: &ProjectLayout) -> (PathBuf, PathBuf, PathBuf) {
+        (
+            layout.rust_fractal_root().to_path_buf(),
+            layout.bindings_root(),
+            layout.maui_project_path().to_path_buf(),
+        )
+    }
+
+    #[test]
+    fn defaults_are_relative_to_the_project_root() {
+        let root = TempRoot::new("defaults");
+        let layout = ProjectLayout::discover(&root.args()).unwrap();
+        assert_eq!(layout.project_root(), root.0);
+        assert_eq!(
+            paths(&layout),
+            (
+                root.0.join("rust_fractal"),
+                root.0.join("rust_fractal/nuget/RustFractals"),
+                root.0.join("mandelbrot/mandelbrot.csproj"),
+            )
+        );
+    }
+
+    #[test]
+    fn config_paths_are_relative_to_the_config_file() {
+        let root = TempRoot::new("config");
+        root.write(
+            LAYOUT_CONFIG_FILE,
+            "rust-fractal-dir = \"native/fractal\"\nmaui-project = \"../app/app.csproj\"\n",
+        );
+        let layout = ProjectLayout::discover(&root.args()).unwrap();
+        assert_eq!(
+            paths(&layout),
+            (
+                root.0.join("native/fractal"),
+                // the NuGet directory follows the crate
+                root.0.join("native/fractal/nuget/RustFractals"),
+                root.0.join("../app/app.csproj"),
+            )
+        );
+
+        // an explicit config file elsewhere replaces xtask.toml
+        let other = root.write("config/layout.toml", "nuget-dir = \"../packages\"\n");
+        let layout = ProjectLayout::discover(&LayoutArgs {
+            layout_config: Some(other),
+            ..root.args()
+        })
+        .unwrap();
+        assert_eq!(
+            paths(&layout),
+            (
+                root.0.join("rust_fractal"),
+                root.0.join("config/../packages/RustFractals"),
+                root.0.join("mandelbrot/mandelbrot.csproj"),
+            )
+        );
+    }
+
+    #[test]
+    fn flags_override_the_config() {
+        let root = TempRoot::new("flags");
+        root.write(
+            LAYOUT_CONFIG_FILE,
+            "rust-fractal-dir = \"configured\"\nnuget-dir = \"configured-nuget\"\n",
+        );
+        let elsewhere = root.0.join("elsewhere");
+        let layout = ProjectLayout::discover(&LayoutArgs {
+            paths: LayoutPaths {
+                rust_fractal_dir: Some(elsewhere.clone()),
+                // relative flags resolve against the working directory
+                maui_project: Some(PathBuf::from("app.csproj")),
+                ..LayoutPaths::default()
+            },
+            ..root.args()
+        })
+        .unwrap();
+        assert_eq!(
+            paths(&layout),
+            (
+                elsewhere,
+                root.0.join("configured-nuget/RustFractals"),
+                env::current_dir().unwrap().join("app.csproj"),
+            )
+        );
+    }
+
+    #[test]
+    fn rejects_bad_roots_and_configs() {
+        let root = TempRoot::new("invalid");
+        let file = root.write("file", "");
+        let err = ProjectLayout::discover(&LayoutArgs {
+            project_root: Some(file),
+            ..LayoutArgs::default()
+        })
+        .unwrap_err();
+        assert!(err.to_string().contains("is not a directory"), "{err}");
+
+        root.write(LAYOUT_CONFIG_FILE, "rust-fractal = \"typo\"\n");
+        let err = ProjectLayout::discover(&root.args()).unwrap_err();
+        assert!(
+            format!("{err:#}").contains("invalid layout config"),
+            "{err:#}"
+        );
+
+        let err = ProjectLayout::discover(&LayoutArgs {
+            layout_config: Some(root.0.join("missing.toml")),
+            ..root.args()
+        })
+        .unwrap_err();
+        assert!(err.to_string().contains("read failed"), "{err}");
+    }
+
+    #[test]
+    fn runtime_identifiers_exist_for_supported_targets_only() {
+        for target in crate::Target::all() {
+            dotnet_rid(target.platform, target.arch).unwrap();
+        }
+        assert_eq!(
+            dotnet_rid(Platform::Android, Arch::Arm).unwrap(),
+            "android-arm"
+        );
+        assert_eq!(
+            dotnet_rid(Platform::Osx, Arch::X64).unwrap(),
+            "maccatalyst-x64"
+        );
+        assert_eq!(
+            dotnet_rid(Platform::Linux, Arch::Arm64).unwrap(),
+            "linux-arm64"
+        );
+        for (platform, arch) in [
+            (Platform::Ios, Arch::X64),
+            (Platform::Linux, Arch::Arm),
+            (Platform::Windows, Arch::Arm),
+            (Platform::Osx, Arch::Arm),
+        ] {
+            let err = dotnet_rid(platform, arch).unwrap_err();
+            assert!(
+                err.to_string().contains("no .NET runtime identifier"),
+                "{platform:?} {arch:?}: {err}"
+            );
+        }
+    }
 }
@@ -0,0 +1,191 @@
+//! `--dry-run`: an [`Executor`] that records the steps instead of running them.
+
+use crate::process::Executor;
+use anyhow::Result;
+use clap::ValueEnum;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum PlanFormat {
+    /// numbered steps for reading
+    #[default]
+    Text,
+    /// `{"steps": [...]}` for scripts
+    Json,
+}
+
+/// One recorded step, in the order the build would take it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Step {
+    Run {
+        program: String,
+        args: Vec<String>,
+        cwd: Option<PathBuf>,
+        /// variables set for the command on top of the inherited environment
+        env: BTreeMap<String, String>,
+    },
+    CreateDir {
+        path: PathBuf,
+    },
+    RemoveDir {
+        path: PathBuf,
+    },
+    RemoveFile {
+        path: PathBuf,
+    },
+    Copy {
+        from: PathBuf,
+        to: PathBuf,
+    },
+    Write {
+        path: PathBuf,
+        contents: String,
+    },
+    /// Commands started together, see [`Executor::run_all`].
+    Parallel {
+        steps: Vec<Step>,
+    },
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct Plan {
+    pub steps: Vec<Step>,
+}
+
+impl Plan {
+    pub fn print(&self, format: PlanFormat) -> Result<()> {
+        match format {
+            PlanFormat::Text => {
+                for (index, step) in self.steps.iter().enumerate() {
+                    println!("{:>3}. {}", index + 1, describe(step));
+                }
+            }
+            PlanFormat::Json => println!("{}", serde_json::to_string_pretty(self)?),
+        }
+        Ok(())
+    }
+}
+
+impl Executor for Plan {
+    fn run(&mut self, cmd: Command) -> Result<()> {
+        self.steps.push(run_step(&cmd));
+        Ok(())
+    }
+
+    fn run_all(&mut self, cmds: Vec<Command>) -> Result<()> {
+        let mut steps = cmds.iter().map(run_step).collect::<Vec<_>>();
+        if steps.len() == 1 {
+            self.steps.append(&mut steps);
+        } else {
+            self.steps.push(Step::Parallel { steps });
+        }
+        Ok(())
+    }
+
+    fn create_dir_all(&mut self, dir: &Path) -> Result<()> {
+        self.steps.push(Step::CreateDir {
+            path: dir.to_path_buf(),
+        });
+        Ok(())
+    }
+
+    fn remove_dir_all(&mut self, dir: &Path) -> Result<()> {
+        self.steps.push(Step::RemoveDir {
+            path: dir.to_path_buf(),
+        });
+        Ok(())
+    }
+
+    fn copy(&mut self, from: &Path, to: &Path) -> Result<()> {
+        self.steps.push(Step::Copy {
+            from: from.to_path_buf(),
+            to: to.to_path_buf(),
+        });
+        Ok(())
+    }
+
+    fn remove_file(&mut self, path: &Path) -> Result<()> {
+        self.steps.push(Step::RemoveFile {
+            path: path.to_path_buf(),
+        });
+        Ok(())
+    }
+
+    fn write(&mut self, path: &Path, contents: &str) -> Result<()> {
+        self.steps.push(Step::Write {
+            path: path.to_path_buf(),
+            contents: contents.to_string(),
+        });
+        Ok(())
+    }
+
+    fn dry_run(&self) -> bool {
+        true
+    }
+}
+
+fn run_step(cmd: &Command) -> Step {
+    let lossy = |s: &std::ffi::OsStr| s.to_string_lossy().into_owned();
+    Step::Run {
+        program: lossy(cmd.get_program()),
+        args: cmd.get_args().map(lossy).collect(),
+        cwd: cmd.get_current_dir().map(Path::to_path_buf),
+        env: cmd
+            .get_envs()
+            .filter_map(|(key, value)| Some((lossy(key), lossy(value?))))
+            .collect(),
+    }
+}
+
+fn describe(step: &Step) -> String {
+    match step {
+        Step::Run {
+            program,
+            args,
+            cwd,
+            env,
+        } => {
+            let mut line = String::new();
+            if let Some(cwd) = cwd {
+                line.push_str(&format!("(in {}) ", cwd.display()));
+            }
+            for (key, value) in env {
+                line.push_str(&format!("{key}={} ", quote(value)));
+            }
+            line.push_str(&quote(program));
+            for arg in args {
+                line.push(' ');
+                line.push_str(&quote(arg));
+            }
+            line
+        }
+        Step::CreateDir { path } => format!("create directory {}", path.display()),
+        Step::RemoveDir { path } => format!("remove directory {}", path.display()),
+        Step::RemoveFile { path } => format!("remove file {}", path.display()),
+        Step::Copy { from, to } => format!("copy {} -> {}", from.display(), to.display()),
+        Step::Write { path, contents } => {
+            format!("write {} ({} bytes)", path.display(), contents.len())
+        }
+        Step::Parallel { steps } => {
+            let mut lines = String::from("in parallel:");
+            for step in steps {
+                lines.push_str(&format!("\n       - {}", describe(step)));
+            }
+            lines
+        }
+    }
+}
+
+/// Quotes arguments a shell would split or interpret so the line can be
+/// pasted into one.
+fn quote(arg: &str) -> String {
+    let plain = |c: char| c.is_ascii_alphanumeric() || "-_./\\=:,+@%".contains(c);
+    if !arg.is_empty() && arg.chars().all(plain) {
+        return arg.to_string();
+    }
+    format!("'{}'", arg.replace('\'', r"'\''"))
+}
@@ -1,6 +1,135 @@
 use anyhow::{Context, Result, anyhow};
+use std::fs;
+use std::io::{self, Write};
 use std::path::Path;
 use std::process::{Command, Stdio};
+use std::thread;
+
+/// Carries out the commands and file operations of a build. Build steps go
+/// through this instead of acting directly so they can be recorded, e.g. to
+/// check the Apple steps on a machine without Xcode or for `--dry-run`
+/// (see [`crate::plan::Plan`]).
+pub trait Executor {
+    fn run(&mut self, cmd: Command) -> Result<()>;
+
+    /// Runs independent commands, concurrently where the executor can.
+    fn run_all(&mut self, cmds: Vec<Command>) -> Result<()> {
+        for cmd in cmds {
+            self.run(cmd)?;
+        }
+        Ok(())
+    }
+
+    fn create_dir_all(&mut self, dir: &Path) -> Result<()>;
+
+    /// Removes a directory tree, succeeding if it does not exist.
+    fn remove_dir_all(&mut self, dir: &Path) -> Result<()>;
+
+    fn copy(&mut self, from: &Path, to: &Path) -> Result<()>;
+
+    fn remove_file(&mut self, path: &Path) -> Result<()>;
+
+    fn write(&mut self, path: &Path, contents: &str) -> Result<()>;
+
+    /// Whether steps only get recorded, so nothing built earlier exists.
+    fn dry_run(&self) -> bool {
+        false
+    }
+}
+
+/// Executes everything for real.
+pub struct System;
+
+impl Executor for System {
+    fn run(&mut self, cmd: Command) -> Result<()> {
+        run(cmd)
+    }
+
+    /// Starts every command at once. Their output is collected and printed
+    /// as each one finishes so the logs do not interleave.
+    fn run_all(&mut self, cmds: Vec<Command>) -> Result<()> {
+        if cmds.len() == 1 {
+            return cmds.into_iter().try_for_each(run);
+        }
+
+        let failures = thread::scope(|scope| {
+            let handles = cmds
+                .into_iter()
+                .map(|mut cmd| {
+                    eprintln!("> {:?}", cmd);
+                    scope.spawn(move || {
+                        let output = cmd.stdin(Stdio::null()).output();
+                        (cmd, output)
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            let mut failures = Vec::new();
+            for handle in handles {
+                let (cmd, output) = handle.join().expect("command thread panicked");
+                let program = cmd.get_program().to_string_lossy().into_owned();
+                let args = cmd
+                    .get_args()
+                    .map(|a| a.to_string_lossy())
+                    .collect::<Vec<_>>();
+                match output {
+                    Ok(output) => {
+                        eprintln!("--- {program} {} ---", args.join(" "));
+                        io::stderr().write_all(&output.stdout).ok();
+                        io::stderr().write_all(&output.stderr).ok();
+                        if !output.status.success() {
+                            failures.push(format!("{cmd:?}: {}", output.status));
+                        }
+                    }
+                    Err(e) => failures.push(format!("{cmd:?}: failed to spawn process: {e}")),
+                }
+            }
+            failures
+        });
+
+        if !failures.is_empty() {
+            return Err(anyhow!(
+                "{} command(s) failed:\n{}",
+                failures.len(),
+                failures.join("\n")
+            ));
+        }
+        Ok(())
+    }
+
+    fn create_dir_all(&mut self, dir: &Path) -> Result<()> {
+        fs::create_dir_all(dir).with_context(|| format!("create_dir_all failed: {}", dir.display()))
+    }
+
+    fn remove_dir_all(&mut self, dir: &Path) -> Result<()> {
+        match fs::remove_dir_all(dir) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => {
+                Err(e).with_context(|| format!("remove_dir_all failed: {}", dir.display()))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn copy(&mut self, from: &Path, to: &Path) -> Result<()> {
+        eprintln!("> copy {} -> {}", from.display(), to.display());
+        if !from.is_file() {
+            return Err(anyhow!("expected file not found: {}", from.display()));
+        }
+        fs::copy(from, to)
+            .with_context(|| format!("copy failed: {} -> {}", from.display(), to.display()))?;
+        Ok(())
+    }
+
+    fn remove_file(&mut self, path: &Path) -> Result<()> {
+        eprintln!("> remove {}", path.display());
+        fs::remove_file(path).with_context(|| format!("remove_file failed: {}", path.display()))
+    }
+
+    fn write(&mut self, path: &Path, contents: &str) -> Result<()> {
+        eprintln!("> write {}", path.display());
+        fs::write(path, contents).with_context(|| format!("write failed: {}", path.display()))
+    }
+}
 
 pub fn run(mut cmd: Command) -> Result<()> {
     eprintln!("> {:?}", cmd);
@@ -17,6 +146,20 @@ pub fn run(mut cmd: Command) -> Result<()> {
     Ok(())
 }
 
+/// Runs `cmd` quietly and returns its trimmed stdout.
+pub fn output(mut cmd: Command) -> Result<String> {
+    let output = cmd
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .with_context(|| format!("failed to spawn {:?}", cmd.get_program()))?;
+
+    if !output.status.success() {
+        return Err(anyhow!("{:?} failed with status: {}", cmd, output.status));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
 pub fn cmd_in_dir(exe: &str, dir: &Path) -> Command {
     let mut c = Command::new(exe);
     c.current_dir(dir);
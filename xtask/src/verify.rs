@@ -0,0 +1,444 @@
+//! Checks built native libraries before they are staged: the binary format
+//! and architecture must match the target (ELF, Mach-O or PE headers) and
+//! every `#[no_mangle]` function of lib.rs must be exported, so a stale or
+//! mis-targeted build fails here instead of at `DllImport` time.
+
+use crate::Target;
+use crate::paths::{Arch, Platform};
+use anyhow::{Context, Result, anyhow, bail, ensure};
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Format {
+    Elf,
+    MachO,
+    Pe,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Machine {
+    X64,
+    Arm64,
+    Arm,
+    Other(u32),
+}
+
+struct Binary {
+    format: Format,
+    machine: Machine,
+    exports: BTreeSet<String>,
+}
+
+/// Names of the `#[no_mangle]` functions declared in `lib_rs`.
+pub fn declared_exports(lib_rs: &Path) -> Result<Vec<String>> {
+    let source =
+        fs::read_to_string(lib_rs).with_context(|| format!("read failed: {}", lib_rs.display()))?;
+
+    let mut exports = Vec::new();
+    let mut pending = false;
+    for line in source.lines().map(str::trim) {
+        if line == "#[unsafe(no_mangle)]" || line == "#[no_mangle]" {
+            pending = true;
+        } else if pending && let Some((_, rest)) = line.split_once("fn ") {
+            let name = rest
+                .split(|c: char| !(c.is_alphanumeric() || c == '_'))
+                .next()
+                .unwrap_or_default();
+            exports.push(name.to_string());
+            pending = false;
+        }
+    }
+    Ok(exports)
+}
+
+/// Fails unless `path` is a library for `target` exporting all of `exports`.
+pub fn verify_library(path: &Path, target: &Target, exports: &[String]) -> Result<()> {
+    let bytes = fs::read(path).with_context(|| format!("read failed: {}", path.display()))?;
+    let binary = parse(&bytes)?;
+
+    let format = match target.platform {
+        Platform::Windows => Format::Pe,
+        Platform::Android | Platform::Linux => Format::Elf,
+        Platform::Osx | Platform::Ios => Format::MachO,
+    };
+    ensure!(
+        binary.format == format,
+        "expected the {format:?} format, found {:?}",
+        binary.format
+    );
+    let machine = match target.arch {
+        Arch::X64 => Machine::X64,
+        Arch::Arm64 => Machine::Arm64,
+        Arch::Arm => Machine::Arm,
+    };
+    ensure!(
+        binary.machine == machine,
+        "expected {machine:?} code, found {:?}",
+        binary.machine
+    );
+
+    let missing = exports
+        .iter()
+        .filter(|name| !binary.exports.contains(*name))
+        .map(String::as_str)
+        .collect::<Vec<_>>();
+    ensure!(
+        missing.is_empty(),
+        "{} symbol(s) declared in lib.rs are not exported: {}",
+        missing.len(),
+        missing.join(", ")
+    );
+    Ok(())
+}
+
+fn parse(bytes: &[u8]) -> Result<Binary> {
+    match bytes {
+        [0x7f, b'E', b'L', b'F', ..] => parse_elf(bytes),
+        [0xcf, 0xfa, 0xed, 0xfe, ..] => parse_macho(bytes),
+        [0xca, 0xfe, 0xba, 0xbe, ..] => {
+            bail!("universal Mach-O binaries are not staged per target")
+        }
+        [b'M', b'Z', ..] => parse_pe(bytes),
+        _ => bail!("not an ELF, Mach-O or PE library"),
+    }
+}
+
+/// Bounds-checked reads at absolute offsets.
+struct Reader<'a> {
+    bytes: &'a [u8],
+    little_endian: bool,
+}
+
+impl Reader<'_> {
+    fn slice(&self, offset: u64, len: u64) -> Result<&[u8]> {
+        let end = offset
+            .checked_add(len)
+            .filter(|&end| end <= self.bytes.len() as u64);
+        end.map(|end| &self.bytes[offset as usize..end as usize])
+            .ok_or_else(|| anyhow!("truncated file: {len} bytes at {offset:#x}"))
+    }
+
+    fn u8(&self, offset: u64) -> Result<u8> {
+        Ok(self.slice(offset, 1)?[0])
+    }
+
+    fn u16(&self, offset: u64) -> Result<u16> {
+        let b = self.slice(offset, 2)?.try_into().expect("2 bytes");
+        Ok(if self.little_endian {
+            u16::from_le_bytes(b)
+        } else {
+            u16::from_be_bytes(b)
+        })
+    }
+
+    fn u32(&self, offset: u64) -> Result<u32> {
+        let b = self.slice(offset, 4)?.try_into().expect("4 bytes");
+        Ok(if self.little_endian {
+            u32::from_le_bytes(b)
+        } else {
+            u32::from_be_bytes(b)
+        })
+    }
+
+    fn u64(&self, offset: u64) -> Result<u64> {
+        let b = self.slice(offset, 8)?.try_into().expect("8 bytes");
+        Ok(if self.little_endian {
+            u64::from_le_bytes(b)
+        } else {
+            u64::from_be_bytes(b)
+        })
+    }
+
+    /// NUL terminated string starting at `offset`.
+    fn c_str(&self, offset: u64) -> Result<String> {
+        let rest = self
+            .slice(offset, 0)
+            .map(|_| &self.bytes[offset as usize..])?;
+        let len = rest
+            .iter()
+            .position(|&b| b == 0)
+            .ok_or_else(|| anyhow!("unterminated string at {offset:#x}"))?;
+        Ok(String::from_utf8_lossy(&rest[..len]).into_owned())
+    }
+}
+
+/// Defined global or weak symbols with default or protected visibility in `.dynsym`.
+fn parse_elf(bytes: &[u8]) -> Result<Binary> {
+    let is_64 = match bytes.get(4) {
+        Some(1) => false,
+        Some(2) => true,
+        _ => bail!("unknown ELF class"),
+    };
+    let r = Reader {
+        bytes,
+        little_endian: bytes.get(5) == Some(&1),
+    };
+
+    let machine = match r.u16(18)? {
+        62 => Machine::X64,
+        183 => Machine::Arm64,
+        40 => Machine::Arm,
+        other => Machine::Other(other as u32),
+    };
+
+    let (section_offset, entry_size, count) = if is_64 {
+        (r.u64(0x28)?, r.u16(0x3a)?, r.u16(0x3c)?)
+    } else {
+        (r.u32(0x20)? as u64, r.u16(0x2e)?, r.u16(0x30)?)
+    };
+    // sh_type, sh_offset, sh_size, sh_link, sh_entsize
+    let section = |index: u64| -> Result<(u32, u64, u64, u32, u64)> {
+        let at = section_offset + index * entry_size as u64;
+        Ok(if is_64 {
+            (
+                r.u32(at + 4)?,
+                r.u64(at + 24)?,
+                r.u64(at + 32)?,
+                r.u32(at + 40)?,
+                r.u64(at + 56)?,
+            )
+        } else {
+            (
+                r.u32(at + 4)?,
+                r.u32(at + 16)? as u64,
+                r.u32(at + 20)? as u64,
+                r.u32(at + 24)?,
+                r.u32(at + 36)? as u64,
+            )
+        })
+    };
+
+    const SHT_DYNSYM: u32 = 11;
+    let mut exports = BTreeSet::new();
+    for index in 0..count as u64 {
+        let (kind, offset, size, link, symbol_size) = section(index)?;
+        if kind != SHT_DYNSYM || symbol_size == 0 {
+            continue;
+        }
+        let (_, strings, _, _, _) = section(link as u64)?;
+        for symbol in 0..size / symbol_size {
+            let at = offset + symbol * symbol_size;
+            let (name, info, other, section_index) = if is_64 {
+                (r.u32(at)?, r.u8(at + 4)?, r.u8(at + 5)?, r.u16(at + 6)?)
+            } else {
+                (r.u32(at)?, r.u8(at + 12)?, r.u8(at + 13)?, r.u16(at + 14)?)
+            };
+            let global = matches!(info >> 4, 1 | 2);
+            let visible = matches!(other & 3, 0 | 3);
+            if section_index != 0 && global && visible {
+                exports.insert(r.c_str(strings + name as u64)?);
+            }
+        }
+    }
+
+    Ok(Binary {
+        format: Format::Elf,
+        machine,
+        exports,
+    })
+}
+
+/// External symbols defined in a section, from the `LC_SYMTAB` of a 64 bit Mach-O.
+fn parse_macho(bytes: &[u8]) -> Result<Binary> {
+    let r = Reader {
+        bytes,
+        little_endian: true,
+    };
+    let machine = match r.u32(4)? {
+        0x0100_0007 => Machine::X64,
+        0x0100_000c => Machine::Arm64,
+        other => Machine::Other(other),
+    };
+
+    const LC_SYMTAB: u32 = 0x2;
+    const N_STAB: u8 = 0xe0;
+    const N_PEXT: u8 = 0x10;
+    const N_TYPE: u8 = 0x0e;
+    const N_SECT: u8 = 0x0e;
+    const N_EXT: u8 = 0x01;
+
+    let commands = r.u32(16)?;
+    let mut at = 32;
+    let mut exports = BTreeSet::new();
+    for _ in 0..commands {
+        let (command, size) = (r.u32(at)?, r.u32(at + 4)?);
+        ensure!(size >= 8, "malformed load command at {at:#x}");
+        if command == LC_SYMTAB {
+            let (symbols, count, strings) = (
+                r.u32(at + 8)? as u64,
+                r.u32(at + 12)? as u64,
+                r.u32(at + 16)? as u64,
+            );
+            for symbol in 0..count {
+                let entry = symbols + symbol * 16;
+                let kind = r.u8(entry + 4)?;
+                if kind & N_STAB == 0
+                    && kind & N_PEXT == 0
+                    && kind & N_EXT != 0
+                    && kind & N_TYPE == N_SECT
+                {
+                    let name = r.c_str(strings + r.u32(entry)? as u64)?;
+                    // C symbols carry a leading underscore
+                    exports.insert(name.strip_prefix('_').unwrap_or(&name).to_string());
+                }
+            }
+        }
+        at += size as u64;
+    }
+
+    Ok(Binary {
+        format: Format::MachO,
+        machine,
+        exports,
+    })
+}
+
+/// Names in the export directory of a PE image.
+fn parse_pe(bytes: &[u8]) -> Result<Binary> {
+    let r = Reader {
+        bytes,
+        little_endian: true,
+    };
+    let pe = r.u32(0x3c)? as u64;
+    ensure!(r.slice(pe, 4)? == b"PE\0\0", "missing PE signature");
+
+    let coff = pe + 4;
+    let machine = match r.u16(coff)? {
+        0x8664 => Machine::X64,
+        0xaa64 => Machine::Arm64,
+        0x01c4 => Machine::Arm,
+        other => Machine::Other(other as u32),
+    };
+    let sections = r.u16(coff + 2)? as u64;
+    let optional = coff + 20;
+    let optional_size = r.u16(coff + 16)? as u64;
+    let directories = match r.u16(optional)? {
+        0x20b => optional + 112,
+        0x10b => optional + 96,
+        other => bail!("unknown PE optional header magic {other:#x}"),
+    };
+
+    // virtual address -> file offset through the section table
+    let section_table = optional + optional_size;
+    let offset_of = |rva: u32| -> Result<u64> {
+        for index in 0..sections {
+            let at = section_table + index * 40;
+            let (virtual_size, address) = (r.u32(at + 8)?, r.u32(at + 12)?);
+            let (raw_size, raw_offset) = (r.u32(at + 16)?, r.u32(at + 20)?);
+            if rva >= address && rva - address < virtual_size.max(raw_size) {
+                return Ok((rva - address) as u64 + raw_offset as u64);
+            }
+        }
+        bail!("address {rva:#x} outside every section")
+    };
+
+    let mut exports = BTreeSet::new();
+    let export_rva = r.u32(directories)?;
+    if export_rva != 0 {
+        let directory = offset_of(export_rva)?;
+        let count = r.u32(directory + 24)? as u64;
+        let names = offset_of(r.u32(directory + 32)?)?;
+        for index in 0..count {
+            exports.insert(r.c_str(offset_of(r.u32(names + index * 4)?)?)?);
+        }
+    }
+
+    Ok(Binary {
+        format: Format::Pe,
+        machine,
+        exports,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::paths::{self, LayoutArgs, ProjectLayout};
+    use std::process::Command;
+
+    fn layout() -> ProjectLayout {
+        ProjectLayout::discover(&LayoutArgs {
+            project_root: Some(Path::new(env!("CARGO_MANIFEST_DIR")).join("..")),
+            ..LayoutArgs::default()
+        })
+        .unwrap()
+    }
+
+    /// Builds rust_fractal for the host into its default target directory.
+    fn host_library(layout: &ProjectLayout, target: &Target) -> std::path::PathBuf {
+        let status = Command::new(env!("CARGO"))
+            .current_dir(layout.rust_fractal_root())
+            .args(["build", "--lib"])
+            .status()
+            .unwrap();
+        assert!(status.success(), "cargo build --lib failed");
+        layout
+            .rust_fractal_root()
+            .join("target")
+            .join("debug")
+            .join(paths::native_lib_filename(target.platform))
+    }
+
+    #[test]
+    fn host_library_exports_everything_lib_rs_declares() {
+        let Some(host) = Target::host() else {
+            return;
+        };
+        let layout = layout();
+        let exports = declared_exports(&layout.rust_fractal_src().join("lib.rs")).unwrap();
+        assert!(exports.iter().any(|name| name == "mandelbrot_render_into"));
+        assert!(exports.iter().any(|name| name == "tile_render_into"));
+
+        let library = host_library(&layout, &host);
+        verify_library(&library, &host, &exports).unwrap();
+
+        let missing = [String::from("not_exported_anywhere")];
+        let err = verify_library(&library, &host, &missing).unwrap_err();
+        assert!(err.to_string().contains("not_exported_anywhere"), "{err}");
+
+        let other_arch = Target {
+            arch: if host.arch == Arch::X64 {
+                Arch::Arm64
+            } else {
+                Arch::X64
+            },
+            ..host
+        };
+        assert!(verify_library(&library, &other_arch, &exports).is_err());
+        let windows = Target {
+            platform: Platform::Windows,
+            ..host
+        };
+        if host.platform != Platform::Windows {
+            assert!(verify_library(&library, &windows, &exports).is_err());
+        }
+    }
+
+    #[test]
+    fn declared_exports_reads_no_mangle_functions() {
+        let dir = std::env::temp_dir().join(format!("xtask-exports-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let lib_rs = dir.join("lib.rs");
+        fs::write(
+            &lib_rs,
+            "#[unsafe(no_mangle)]\n\
+             pub extern \"C\" fn first() -> u32 { 0 }\n\
+             fn helper() {}\n\
+             #[no_mangle]\n\
+             /// docs between the attribute and the function\n\
+             pub unsafe extern \"C\" fn second(x: *mut u8) {}\n",
+        )
+        .unwrap();
+        let exports = declared_exports(&lib_rs);
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(exports.unwrap(), ["first", "second"]);
+    }
+
+    #[test]
+    fn rejects_files_that_are_not_libraries() {
+        assert!(parse(b"#!/bin/sh\n").is_err());
+        assert!(parse(&[0x7f, b'E', b'L', b'F']).is_err());
+    }
+}
@@ -0,0 +1,390 @@
+//! NuGet package versions derived from the crate version and git state.
+//!
+//! - `<crate version>` on a clean checkout of the tag `v<crate version>`
+//! - `<crate version>-dev.<commits>.g<hash>` otherwise, `<commits>` counting
+//!   from that tag, or from the first commit while it does not exist
+//! - `...-dev.<commits>.g<hash>.dirty-<fingerprint>` with uncommitted changes,
+//!   the fingerprint covering the staged natives and bindings so every
+//!   rebuild of a different library gets a different version
+//!
+//! The same inputs always give the same version. Bump the crate version
+//! after tagging a release so later builds sort after it.
+
+use crate::paths::ProjectLayout;
+use crate::process::{self, Executor, cmd_in_dir};
+use anyhow::{Context, Result, bail};
+use std::fs;
+use std::path::Path;
+use walkdir::WalkDir;
+
+/// `<crate version>-dev...` as described in the module docs.
+pub fn package_version(layout: &ProjectLayout) -> Result<String> {
+    derive_version(layout, || {
+        Ok(format!("{:016x}", staged_fingerprint(layout)?))
+    })
+}
+
+/// [`package_version`] before anything is staged, with a `<fingerprint>`
+/// placeholder in dirty checkouts.
+pub fn planned_package_version(layout: &ProjectLayout) -> Result<String> {
+    derive_version(layout, || Ok("<fingerprint>".to_string()))
+}
+
+fn derive_version(
+    layout: &ProjectLayout,
+    fingerprint: impl FnOnce() -> Result<String>,
+) -> Result<String> {
+    let crate_version = crate_version(layout)?;
+    GitState::read(layout.project_root(), &crate_version)?.version(&crate_version, fingerprint)
+}
+
+/// Rejects versions NuGet would not accept, e.g. a mistyped `--package-version`.
+pub fn validate(version: &str) -> Result<()> {
+    let release = version.split(['-', '+']).next().unwrap_or_default();
+    let suffix = &version[release.len()..];
+    let numbers = release.split('.').collect::<Vec<_>>();
+    let valid = (2..=4).contains(&numbers.len())
+        && numbers
+            .iter()
+            .all(|n| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()))
+        && suffix
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'.' | b'-' | b'+'));
+    if !valid {
+        bail!("'{version}' is not a valid NuGet package version, e.g. 1.2.3 or 1.2.3-beta.1");
+    }
+    Ok(())
+}
+
+/// Records `version` for the app's restore, see [`ProjectLayout::bindings_version_props`].
+pub fn write_version_props(
+    exec: &mut dyn Executor,
+    layout: &ProjectLayout,
+    version: &str,
+) -> Result<()> {
+    let props = format!(
+        "<Project>\n  <PropertyGroup>\n    <RustFractalsVersion>{version}</RustFractalsVersion>\n  </PropertyGroup>\n</Project>\n"
+    );
+    exec.write(&layout.bindings_version_props(), &props)
+}
+
+fn crate_version(layout: &ProjectLayout) -> Result<String> {
+    let manifest = layout.rust_fractal_manifest();
+    let text = fs::read_to_string(&manifest)
+        .with_context(|| format!("read failed: {}", manifest.display()))?;
+    let table: toml::Table = text
+        .parse()
+        .with_context(|| format!("invalid manifest: {}", manifest.display()))?;
+    table
+        .get("package")
+        .and_then(|package| package.get("version"))
+        .and_then(|version| version.as_str())
+        .map(str::to_string)
+        .with_context(|| format!("no package version in {}", manifest.display()))
+}
+
+struct GitState {
+    /// commits since the release tag, or in total without one
+    commits: u64,
+    hash: String,
+    dirty: bool,
+    tagged: bool,
+}
+
+impl GitState {
+    fn read(root: &Path, crate_version: &str) -> Result<Self> {
+        let git = |args: &[&str]| {
+            let mut cmd = cmd_in_dir("git", root);
+            cmd.args(args);
+            process::output(cmd).context("git is required to derive the package version")
+        };
+
+        let tag = format!("v{crate_version}");
+        let describe = git(&[
+            "describe",
+            "--tags",
+            "--long",
+            "--dirty",
+            "--always",
+            "--abbrev=8",
+            "--match",
+            &tag,
+        ])?;
+        Self::parse(&describe, &tag, || git(&["rev-list", "--count", "HEAD"]))
+    }
+
+    /// Parses `git describe --long --dirty --always` output for `tag`, counting
+    /// all commits with `rev_count` when the tag does not exist.
+    fn parse(
+        describe: &str,
+        tag: &str,
+        rev_count: impl FnOnce() -> Result<String>,
+    ) -> Result<Self> {
+        let (describe, dirty) = match describe.strip_suffix("-dirty") {
+            Some(describe) => (describe, true),
+            None => (describe, false),
+        };
+
+        // v<version>-<commits>-g<hash> with the tag, just <hash> without it
+        if let Some(rest) = describe.strip_prefix(&format!("{tag}-"))
+            && let Some((commits, hash)) = rest.split_once("-g")
+        {
+            return Ok(Self {
+                commits: commits
+                    .parse()
+                    .with_context(|| format!("unexpected git describe output: {describe}"))?,
+                hash: hash.to_string(),
+                dirty,
+                tagged: true,
+            });
+        }
+        if describe.is_empty() || !describe.bytes().all(|b| b.is_ascii_hexdigit()) {
+            bail!("unexpected git describe output: {describe}");
+        }
+
+        let commits = rev_count()?;
+        Ok(Self {
+            commits: commits
+                .parse()
+                .with_context(|| format!("unexpected git rev-list output: {commits}"))?,
+            hash: describe.to_string(),
+            dirty,
+            tagged: false,
+        })
+    }
+
+    /// The package version for this state, see the module docs.
+    fn version(
+        &self,
+        crate_version: &str,
+        fingerprint: impl FnOnce() -> Result<String>,
+    ) -> Result<String> {
+        if self.tagged && self.commits == 0 && !self.dirty {
+            return Ok(crate_version.to_string());
+        }
+        let mut version = format!("{crate_version}-dev.{}.g{}", self.commits, self.hash);
+        if self.dirty {
+            version.push_str(&format!(".dirty-{}", fingerprint()?));
+        }
+        Ok(version)
+    }
+}
+
+/// FNV-1a over the paths and contents of the staged natives and the C# sources.
+fn staged_fingerprint(layout: &ProjectLayout) -> Result<u64> {
+    let root = layout.bindings_root();
+    let mut hash = 0xcbf2_9ce4_8422_2325u64;
+    let mut feed = |bytes: &[u8]| {
+        for &byte in bytes {
+            hash = (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3);
+        }
+    };
+
+    for entry in WalkDir::new(&root).sort_by_file_name() {
+        let entry = entry.with_context(|| format!("walk failed: {}", root.display()))?;
+        let path = entry.path();
+        if !entry.file_type().is_file() || !is_packed(&root, path) {
+            continue;
+        }
+        let relative = path.strip_prefix(&root).unwrap_or(path);
+        feed(relative.to_string_lossy().as_bytes());
+        feed(&fs::read(path).with_context(|| format!("read failed: {}", path.display()))?);
+    }
+    Ok(hash)
+}
+
+/// Files that end up in the package: the runtimes and the C# sources,
+/// not `bin`/`obj`.
+fn is_packed(root: &Path, path: &Path) -> bool {
+    let relative = path.strip_prefix(root).unwrap_or(path);
+    relative.starts_with("runtimes")
+        || (relative.components().count() == 1
+            && path
+                .extension()
+                .is_some_and(|ext| ext == "cs" || ext == "csproj"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::paths::LayoutArgs;
+    use std::path::PathBuf;
+
+    fn layout() -> ProjectLayout {
+        ProjectLayout::discover(&LayoutArgs {
+            project_root: Some(Path::new(env!("CARGO_MANIFEST_DIR")).join("..")),
+            ..LayoutArgs::default()
+        })
+        .unwrap()
+    }
+
+    fn parse(describe: &str) -> Result<GitState> {
+        GitState::parse(describe, "v0.1.0", || Ok("42".to_string()))
+    }
+
+    fn version(describe: &str) -> String {
+        parse(describe)
+            .unwrap()
+            .version("0.1.0", || Ok("0123456789abcdef".to_string()))
+            .unwrap()
+    }
+
+    #[test]
+    fn accepts_nuget_versions_only() {
+        for version in [
+            "1.2",
+            "1.2.3",
+            "1.2.3.4",
+            "1.2.3-beta.1",
+            "1.2.3+build.5",
+            "0.1.0-dev.7.gdeadbeef.dirty-0123456789abcdef",
+        ] {
+            assert!(validate(version).is_ok(), "{version}");
+        }
+        for version in [
+            "",
+            "1",
+            "1.2.3.4.5",
+            "1..3",
+            "1.x.3",
+            "v1.2.3",
+            "-beta",
+            "1.2.3-be ta",
+            "1.2.3_1",
+        ] {
+            assert!(validate(version).is_err(), "{version}");
+        }
+    }
+
+    #[test]
+    fn versions_follow_the_release_tag() {
+        // on the tag
+        assert_eq!(version("v0.1.0-0-g1a2b3c4d"), "0.1.0");
+        assert_eq!(
+            version("v0.1.0-0-g1a2b3c4d-dirty"),
+            "0.1.0-dev.0.g1a2b3c4d.dirty-0123456789abcdef"
+        );
+        // after it
+        assert_eq!(version("v0.1.0-12-g1a2b3c4d"), "0.1.0-dev.12.g1a2b3c4d");
+        // without it, counting every commit
+        assert_eq!(version("1a2b3c4d"), "0.1.0-dev.42.g1a2b3c4d");
+        assert_eq!(
+            version("1a2b3c4d-dirty"),
+            "0.1.0-dev.42.g1a2b3c4d.dirty-0123456789abcdef"
+        );
+        for describe in [
+            "v0.1.0-0-g1a2b3c4d-dirty",
+            "v0.1.0-12-g1a2b3c4d",
+            "1a2b3c4d-dirty",
+        ] {
+            validate(&version(describe)).unwrap();
+        }
+    }
+
+    #[test]
+    fn the_fingerprint_is_only_taken_for_dirty_trees() {
+        let state = parse("v0.1.0-3-g1a2b3c4d").unwrap();
+        let clean = state.version("0.1.0", || bail!("not dirty"));
+        assert_eq!(clean.unwrap(), "0.1.0-dev.3.g1a2b3c4d");
+
+        let state = parse("v0.1.0-3-g1a2b3c4d-dirty").unwrap();
+        assert!(state.version("0.1.0", || bail!("unreadable")).is_err());
+    }
+
+    #[test]
+    fn rejects_unexpected_describe_output() {
+        for describe in [
+            "",
+            "v0.1.0-x-g1a2b3c4d",
+            "v0.2.0-3-g1a2b3c4d",
+            "fatal: not a git repository",
+        ] {
+            assert!(parse(describe).is_err(), "{describe:?}");
+        }
+        let state = GitState::parse("1a2b3c4d", "v0.1.0", || Ok("many".to_string()));
+        assert!(state.is_err());
+    }
+
+    /// A scratch git repository removed when dropped.
+    struct TempRepo(PathBuf);
+
+    impl TempRepo {
+        fn new() -> Self {
+            let dir = std::env::temp_dir().join(format!("xtask-version-{}", std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            let repo = Self(dir);
+            repo.git(&["init", "-q"]);
+            repo
+        }
+
+        fn git(&self, args: &[&str]) -> String {
+            let mut cmd = cmd_in_dir("git", &self.0);
+            cmd.args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+                .args(args);
+            process::output(cmd).unwrap()
+        }
+
+        fn commit(&self, content: &str) {
+            fs::write(self.0.join("file.txt"), content).unwrap();
+            self.git(&["add", "file.txt"]);
+            self.git(&["commit", "-q", "-m", content]);
+        }
+
+        fn read(&self) -> GitState {
+            GitState::read(&self.0, "0.1.0").unwrap()
+        }
+    }
+
+    impl Drop for TempRepo {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn reads_the_state_of_a_repository() {
+        let repo = TempRepo::new();
+        repo.commit("one");
+        repo.commit("two");
+        let head = repo.git(&["rev-parse", "--short=8", "HEAD"]);
+        let state = repo.read();
+        assert_eq!(
+            (state.commits, state.tagged, state.dirty),
+            (2, false, false)
+        );
+        assert_eq!(state.hash, head);
+
+        repo.git(&["tag", "v0.1.0"]);
+        let state = repo.read();
+        assert_eq!((state.commits, state.tagged, state.dirty), (0, true, false));
+        assert_eq!(state.hash, head);
+
+        // other tags are not release tags of this version
+        repo.commit("three");
+        repo.git(&["tag", "v0.0.9"]);
+        let head = repo.git(&["rev-parse", "--short=8", "HEAD"]);
+        let state = repo.read();
+        assert_eq!((state.commits, state.tagged, state.dirty), (1, true, false));
+        assert_eq!(state.hash, head);
+
+        fs::write(repo.0.join("file.txt"), "changed").unwrap();
+        let state = repo.read();
+        assert_eq!((state.commits, state.dirty), (1, true));
+        assert_eq!(state.hash, head);
+    }
+
+    #[test]
+    fn the_app_falls_back_to_dev_builds_of_the_crate_version() {
+        let layout = layout();
+        let csproj = fs::read_to_string(layout.maui_project_file().unwrap()).unwrap();
+        let fallback = format!(
+            "<RustFractalsVersion Condition=\"'$(RustFractalsVersion)' == ''\">{}-dev.*</RustFractalsVersion>",
+            crate_version(&layout).unwrap()
+        );
+        assert!(
+            csproj.contains(&fallback),
+            "bump the fallback in the app project"
+        );
+    }
+}